@@ -0,0 +1,15 @@
+//! Takes a screenshot without tokio, with zbus running on async-io as it
+//! does with the default features. Any executor can drive the futures then.
+use std::error::Error;
+
+use wlscreenaccess::{color_pick, screenshot};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    async_io::block_on(async {
+        let response = screenshot().await?;
+        println!("{}", response);
+        let color = color_pick().await?;
+        println!("{}", color.to_rgb());
+        Ok(())
+    })
+}
@@ -1,12 +1,20 @@
 use std::error::Error;
-use wlscreenaccess::{color_pick,screenshot};
+use wlscreenaccess::{
+    color_pick, pick_color_hex, screenshot_with_options, HandleToken, ScreenshotOptions,
+    WindowIdentifier, RGB,
+};
 // Although we use `async-std` here, you can use any async runtime of choice.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let a = screenshot().await?;
+    let options = ScreenshotOptions::new()
+        .handle_token(HandleToken::try_from("wlscreenaccess_example")?)
+        .modal(true)
+        .interactive(false);
+    let a = screenshot_with_options(&WindowIdentifier::None, options).await?;
     dbg!(a);
     let b = color_pick().await?;
-    let b = b.to_rgb();
-    dbg!(b);
+    let b = RGB::from(b);
+    println!("{} {}", b.preview_block(4), b);
+    println!("{}", pick_color_hex().await?);
     Ok(())
 }
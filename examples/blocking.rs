@@ -0,0 +1,20 @@
+use std::error::Error;
+use wlscreenaccess::{
+    blocking::{color_pick, screenshot_with_options},
+    HandleToken, ScreenshotOptions, WindowIdentifier, RGB,
+};
+
+// The same as the base example, without an async runtime.
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = ScreenshotOptions::new()
+        .handle_token(HandleToken::try_from("wlscreenaccess_example")?)
+        .modal(true)
+        .interactive(false);
+    let a = screenshot_with_options(&WindowIdentifier::None, options)?;
+    dbg!(a);
+    let b = color_pick()?;
+    let b = RGB::from(b);
+    println!("{} {}", b.preview_block(4), b);
+    println!("{}", b.to_hex());
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Records the Response signal of a live portal request as a test fixture.
+//!
+//! ```sh
+//! cargo run --example capture_response -- screenshot tests/fixtures/my-backend.response
+//! cargo run --example capture_response -- pick-color tests/fixtures/my-backend-color.response
+//! ```
+//!
+//! The file holds the body signature on its first line, followed by the raw
+//! body, as `tests/fixtures.rs` reads it. Whatever the user answers is
+//! recorded, cancelling included.
+use std::{error::Error, future::Future, sync::Arc};
+
+use futures_lite::{future, StreamExt};
+use wlscreenaccess::{Portal, RequestHandle, ScreenshotOptions, WindowIdentifier};
+use zbus::{fdo::DBusProxy, Connection, Message, MessageStream};
+
+const RULE: &str = "type='signal',interface='org.freedesktop.portal.Request',member='Response'";
+
+/// Waits for the Response of the request behind `handle`, driving `response`
+/// meanwhile so the request isn't closed.
+async fn capture<T>(
+    mut messages: MessageStream,
+    handle: RequestHandle,
+    response: impl Future<Output = Result<T, wlscreenaccess::Error>>,
+) -> Result<Arc<Message>, Box<dyn Error>> {
+    let find = async {
+        while let Some(message) = messages.try_next().await? {
+            let header = message.header()?;
+            if header.member()?.is_some_and(|member| member == "Response")
+                && header
+                    .path()?
+                    .is_some_and(|path| *path == handle.path().as_ref())
+            {
+                return Ok(message);
+            }
+        }
+        Err("the bus connection closed".into())
+    };
+    let (message, _) = future::zip(find, response).await;
+    message
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(kind), Some(output)) = (args.next(), args.next()) else {
+        return Err("usage: capture_response <screenshot|pick-color> <output>".into());
+    };
+    async_io::block_on(async {
+        let connection = Connection::session().await?;
+        DBusProxy::new(&connection).await?.add_match(RULE).await?;
+        let messages = MessageStream::from(&connection);
+        let portal = Portal::with_connection(&connection).await?;
+        let parent = WindowIdentifier::None;
+        let message = match kind.as_str() {
+            "screenshot" => {
+                let options = ScreenshotOptions::default();
+                let (handle, response) = portal.start_screenshot(&parent, options).await?;
+                capture(messages, handle, response).await?
+            }
+            "pick-color" => {
+                let (handle, response) = portal.start_pick_color(&parent).await?;
+                capture(messages, handle, response).await?
+            }
+            _ => return Err(format!("unknown request {}", kind).into()),
+        };
+        let mut bytes = message.body_signature()?.to_string().into_bytes();
+        bytes.push(b'\n');
+        bytes.extend_from_slice(message.body_as_bytes()?);
+        std::fs::write(&output, bytes)?;
+        println!("recorded {}", output);
+        Ok(())
+    })
+}
@@ -0,0 +1,61 @@
+//! Takes an interactive screenshot with the dialog parented to a winit window.
+//!
+//! Run with `cargo run --example parented --features rwh`.
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+use wlscreenaccess::{screenshot_with_options, ScreenshotOptions, WindowIdentifier};
+
+#[derive(Default)]
+struct App {
+    window: Option<Window>,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let window = event_loop
+            .create_window(Window::default_attributes().with_title("wlscreenaccess"))
+            .expect("Failed to create the window");
+        self.window = Some(window);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            // Wait for the window to be mapped, backends ignore other parents.
+            WindowEvent::RedrawRequested => {
+                let Some(window) = &self.window else {
+                    return;
+                };
+                // SAFETY: the identifier is dropped at the end of this arm,
+                // while the window lives until the app is.
+                let identifier = match unsafe { WindowIdentifier::from_window(window) } {
+                    Ok(identifier) => identifier,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        return event_loop.exit();
+                    }
+                };
+                let options = ScreenshotOptions::new().modal(true).interactive(true);
+                match async_io::block_on(screenshot_with_options(&identifier, options)) {
+                    Ok(response) => println!("{}", response),
+                    Err(err) => eprintln!("{}", err),
+                }
+                event_loop.exit();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let event_loop = EventLoop::new()?;
+    event_loop.run_app(&mut App::default())?;
+    Ok(())
+}
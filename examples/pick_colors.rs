@@ -0,0 +1,20 @@
+use std::error::Error;
+
+use futures_lite::StreamExt;
+use wlscreenaccess::{Portal, RGB};
+
+// Picks up to five colors, stopping early when the dialog is dismissed.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let portal = Portal::new().await?;
+    let colors: Vec<RGB> = portal
+        .pick_colors()
+        .take(5)
+        .map(|color| color.map(RGB::from))
+        .try_collect()
+        .await?;
+    for color in colors {
+        println!("{} {}", color.preview_block(4), color);
+    }
+    Ok(())
+}
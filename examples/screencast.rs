@@ -0,0 +1,27 @@
+use std::error::Error;
+use std::os::fd::AsRawFd;
+
+use wlscreenaccess::{
+    screencast::{ScreenCast, SourceSelection},
+    WindowIdentifier,
+};
+
+// Shares a monitor and prints how to view it with gst-launch, the media
+// plumbing being left to GStreamer. The session lasts until Enter is pressed.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let portal = ScreenCast::new().await?;
+    let selection = SourceSelection::monitor().build()?;
+    let (session, streams) = portal.start(&WindowIdentifier::None, selection).await?;
+    let remote = session.open_pipewire_remote().await?;
+    println!("PipeWire remote fd: {}", remote.as_raw_fd());
+    for stream in &streams {
+        println!(
+            "gst-launch-1.0 pipewiresrc path={} ! videoconvert ! autovideosink",
+            stream.pipewire_node_id()
+        );
+    }
+    std::io::stdin().read_line(&mut String::new())?;
+    session.close().await?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Prints the spans and events of a screenshot and a color pick as a tree,
+//! with the `tracing` feature:
+//!
+//! ```sh
+//! cargo run --example tracing --features tracing
+//! ```
+//!
+//! Applications would install `tracing_subscriber::fmt()` or the like
+//! instead; the small subscriber here only shows what's sent, e.g.
+//!
+//! ```text
+//! screenshot token="wlsa_1bzVsmcPqa_0"
+//!   DEBUG request path predicted token="wlsa_1bzVsmcPqa_0" path="/org/freedesktop/portal/desktop/request/1_87/wlsa_1bzVsmcPqa_0"
+//!   DEBUG signal subscribed token="wlsa_1bzVsmcPqa_0"
+//!   DEBUG request sent token="wlsa_1bzVsmcPqa_0" path="/org/freedesktop/portal/desktop/request/1_87/wlsa_1bzVsmcPqa_0" predicted=true
+//!   DEBUG response received token="wlsa_1bzVsmcPqa_0" code=0
+//! ```
+use std::{
+    error::Error,
+    fmt::{Debug, Write},
+    sync::Mutex,
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use wlscreenaccess::{color_pick, screenshot};
+
+/// Writes the fields as `name=value`, the message first.
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Prints each span when it's made and each event, indented under their
+/// parents.
+#[derive(Default)]
+struct Tree {
+    /// The depth of each span, by id.
+    depths: Mutex<Vec<usize>>,
+    /// The spans entered, innermost last.
+    entered: Mutex<Vec<Id>>,
+}
+
+impl Tree {
+    fn depth_under(&self, parent: Option<&Id>) -> usize {
+        let entered = self.entered.lock().unwrap();
+        match parent.or(entered.last()) {
+            Some(parent) => self.depths.lock().unwrap()[parent.into_u64() as usize - 1] + 1,
+            None => 0,
+        }
+    }
+}
+
+impl Subscriber for Tree {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // zbus traces every message too.
+        metadata.target().starts_with("wlscreenaccess")
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let depth = self.depth_under(span.parent());
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        println!(
+            "{:indent$}{}{}",
+            "",
+            span.metadata().name(),
+            fields.0,
+            indent = depth * 2
+        );
+        let mut depths = self.depths.lock().unwrap();
+        depths.push(depth);
+        Id::from_u64(depths.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let depth = self.depth_under(event.parent());
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let level = event.metadata().level();
+        println!("{:indent$}{} {}", "", level, fields.0, indent = depth * 2);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().unwrap().push(span.clone());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    tracing::subscriber::set_global_default(Tree::default())?;
+    async_io::block_on(async {
+        println!("{}", screenshot().await?);
+        println!("{}", color_pick().await?.to_rgb());
+        Ok(())
+    })
+}
@@ -0,0 +1,125 @@
+//! A synchronous API, for programs without an async runtime.
+//!
+//! It wraps the async one, blocking on zbus' own executor, and shares its
+//! options and responses.
+//!
+//! ```no_run
+//! # fn run() -> Result<(), wlscreenaccess::Error> {
+//! let response = wlscreenaccess::blocking::screenshot()?;
+//! println!("{}", response);
+//! let color = wlscreenaccess::blocking::color_pick()?;
+//! println!("{}", color.to_rgb());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use zbus::{block_on, blocking::Connection};
+
+use crate::{ColorResponse, Error, ScreenshotOptions, ScreenshotResponse, WindowIdentifier};
+
+/// A blocking client for the Screenshot portal, see [`crate::Portal`].
+#[derive(Clone, Debug)]
+pub struct Portal {
+    connection: Connection,
+    portal: crate::Portal,
+}
+
+impl Portal {
+    /// Connects to the session bus and creates the portal proxy.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_connection(&Connection::session()?)
+    }
+
+    /// Creates the portal proxy on an existing connection.
+    pub fn with_connection(connection: &Connection) -> Result<Self, Error> {
+        let portal = block_on(crate::Portal::with_connection(connection.inner()))?;
+        Ok(Self {
+            connection: connection.clone(),
+            portal,
+        })
+    }
+
+    /// The connection the requests are made on.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The version of the Screenshot interface the portal implements.
+    pub fn version(&self) -> Result<u32, Error> {
+        block_on(self.portal.version())
+    }
+
+    pub fn pick_color(&self) -> Result<ColorResponse, Error> {
+        block_on(self.portal.pick_color())
+    }
+
+    /// Picks a color with the dialog parented to the given window.
+    pub fn pick_color_with_parent(
+        &self,
+        identifier: &WindowIdentifier,
+    ) -> Result<ColorResponse, Error> {
+        block_on(self.portal.pick_color_with_parent(identifier))
+    }
+
+    pub fn screenshot(&self) -> Result<ScreenshotResponse, Error> {
+        block_on(self.portal.screenshot())
+    }
+
+    pub fn screenshot_with_options(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+    ) -> Result<ScreenshotResponse, Error> {
+        block_on(self.portal.screenshot_with_options(identifier, options))
+    }
+
+    /// Takes a screenshot, giving up if the portal hasn't answered within
+    /// `timeout`. The request is then closed and this is [`Error::Timeout`].
+    pub fn screenshot_with_timeout(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+        timeout: Duration,
+    ) -> Result<ScreenshotResponse, Error> {
+        block_on(
+            self.portal
+                .screenshot_with_timeout(identifier, options, timeout),
+        )
+    }
+
+    /// Takes a screenshot through the interactive dialog, see
+    /// [`crate::Portal::screenshot_interactive`].
+    pub fn screenshot_interactive(&self) -> Result<ScreenshotResponse, Error> {
+        block_on(self.portal.screenshot_interactive())
+    }
+
+    /// Reads the whole screenshot, see [`crate::Portal::read_screenshot`].
+    pub fn read_screenshot(&self, response: &ScreenshotResponse) -> Result<Vec<u8>, Error> {
+        block_on(self.portal.read_screenshot(response))
+    }
+}
+
+/// Lets the user pick a color on screen, see [`crate::color_pick`].
+pub fn color_pick() -> Result<ColorResponse, Error> {
+    block_on(crate::color_pick())
+}
+
+/// Takes a screenshot, see [`crate::screenshot`].
+pub fn screenshot() -> Result<ScreenshotResponse, Error> {
+    block_on(crate::screenshot())
+}
+
+/// Takes a screenshot, giving up after `timeout`, see
+/// [`crate::screenshot_with_timeout`].
+pub fn screenshot_with_timeout(timeout: Duration) -> Result<ScreenshotResponse, Error> {
+    block_on(crate::screenshot_with_timeout(timeout))
+}
+
+pub fn screenshot_with_options(
+    identifier: &WindowIdentifier,
+    options: ScreenshotOptions,
+) -> Result<ScreenshotResponse, Error> {
+    block_on(crate::screenshot_with_options(identifier, options))
+}
@@ -0,0 +1,211 @@
+//! Sharing the clipboard over remote desktop sessions, through the Clipboard
+//! portal.
+//!
+//! The clipboard is requested before the session starts. Offering data then
+//! goes: [`Session::set_selection`] with the MIME types on offer, a
+//! [`TransferRequest`] when the other side pastes, and the data written to
+//! the file descriptor of [`Session::selection_write`].
+
+use std::collections::HashMap;
+
+use futures_lite::{Stream, StreamExt};
+use zbus::{
+    dbus_proxy,
+    zvariant::{ObjectPath, OwnedFd, OwnedObjectPath, OwnedValue, Value},
+    Connection,
+};
+
+use crate::{
+    connection,
+    screencast::{owned_fd, Session},
+    Error, PORTAL_SERVICE,
+};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Clipboard",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Clipboard {
+    fn request_clipboard(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<()>;
+    fn set_selection(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<()>;
+    fn selection_write(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        serial: u32,
+    ) -> zbus::Result<OwnedFd>;
+    fn selection_write_done(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        serial: u32,
+        success: bool,
+    ) -> zbus::Result<()>;
+    fn selection_read(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        mime_type: &str,
+    ) -> zbus::Result<OwnedFd>;
+    #[dbus_proxy(signal)]
+    fn selection_owner_changed(
+        &self,
+        session_handle: OwnedObjectPath,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn selection_transfer(
+        &self,
+        session_handle: OwnedObjectPath,
+        mime_type: String,
+        serial: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// A change of the owner of the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionOwner {
+    /// The MIME types the clipboard holds.
+    pub mime_types: Vec<String>,
+    /// Whether this session set it.
+    pub session_is_owner: bool,
+}
+
+/// A request for the data this session offered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferRequest {
+    /// The type the data is wanted in.
+    pub mime_type: String,
+    /// Identifies the transfer to [`Session::selection_write`].
+    pub serial: u32,
+}
+
+async fn clipboard_proxy(connection: &Connection) -> Result<ClipboardProxy<'static>, Error> {
+    Ok(ClipboardProxy::new(connection).await?)
+}
+
+/// The proxy the clipboard's signals are received from, see
+/// [`connection::bind_to_portal`].
+async fn clipboard_signals(connection: &Connection) -> Result<ClipboardProxy<'static>, Error> {
+    let builder = ClipboardProxy::builder(connection);
+    Ok(connection::bind_to_portal(connection, builder)
+        .await?
+        .build()
+        .await?)
+}
+
+fn call_error(err: zbus::Error) -> Error {
+    Error::from_call(err, PORTAL_SERVICE)
+}
+
+impl Session {
+    /// Asks for access to the clipboard. Must be done before the remote
+    /// desktop session is started, and fails with
+    /// [`Error::NotRemoteDesktop`] on screencast sessions.
+    pub async fn request_clipboard(&self) -> Result<(), Error> {
+        if self.remote.is_none() {
+            return Err(Error::NotRemoteDesktop);
+        }
+        clipboard_proxy(&self.connection)
+            .await?
+            .request_clipboard(&self.path, HashMap::new())
+            .await
+            .map_err(call_error)
+    }
+
+    /// Offers data in the given MIME types on the clipboard. The data is only
+    /// sent when asked for, see [`Session::receive_selection_transfer`].
+    pub async fn set_selection(&self, mime_types: &[&str]) -> Result<(), Error> {
+        let mut options = HashMap::new();
+        options.insert("mime_types", Value::from(mime_types.to_vec()));
+        clipboard_proxy(&self.connection)
+            .await?
+            .set_selection(&self.path, options)
+            .await
+            .map_err(call_error)
+    }
+
+    /// Yields the changes of the clipboard's owner.
+    pub async fn receive_selection_owner_changed(
+        &self,
+    ) -> Result<impl Stream<Item = SelectionOwner>, Error> {
+        let path = self.path.clone();
+        let signals = clipboard_signals(&self.connection)
+            .await?
+            .receive_selection_owner_changed()
+            .await?;
+        Ok(signals.filter_map(move |signal| {
+            let args = signal.args().ok()?;
+            if args.session_handle != path {
+                return None;
+            }
+            let options = args.options;
+            let mime_types = options
+                .get("mime_types")
+                .and_then(|value| Vec::<String>::try_from(value.clone()).ok())
+                .unwrap_or_default();
+            let session_is_owner = options
+                .get("session_is_owner")
+                .and_then(|value| value.downcast_ref::<bool>().copied())
+                .unwrap_or_default();
+            Some(SelectionOwner {
+                mime_types,
+                session_is_owner,
+            })
+        }))
+    }
+
+    /// Yields the requests for the data this session offered.
+    pub async fn receive_selection_transfer(
+        &self,
+    ) -> Result<impl Stream<Item = TransferRequest>, Error> {
+        let path = self.path.clone();
+        let signals = clipboard_signals(&self.connection)
+            .await?
+            .receive_selection_transfer()
+            .await?;
+        Ok(signals.filter_map(move |signal| {
+            let args = signal.args().ok()?;
+            (args.session_handle == path).then_some(TransferRequest {
+                mime_type: args.mime_type,
+                serial: args.serial,
+            })
+        }))
+    }
+
+    /// The file to write the data of the transfer `serial` to. Once written
+    /// and closed, call [`Session::selection_write_done`].
+    pub async fn selection_write(&self, serial: u32) -> Result<std::os::fd::OwnedFd, Error> {
+        clipboard_proxy(&self.connection)
+            .await?
+            .selection_write(&self.path, serial)
+            .await
+            .map(owned_fd)
+            .map_err(call_error)
+    }
+
+    /// Tells whether the data of the transfer `serial` was written.
+    pub async fn selection_write_done(&self, serial: u32, success: bool) -> Result<(), Error> {
+        clipboard_proxy(&self.connection)
+            .await?
+            .selection_write_done(&self.path, serial, success)
+            .await
+            .map_err(call_error)
+    }
+
+    /// The file to read the clipboard's data in `mime_type` from.
+    pub async fn selection_read(&self, mime_type: &str) -> Result<std::os::fd::OwnedFd, Error> {
+        clipboard_proxy(&self.connection)
+            .await?
+            .selection_read(&self.path, mime_type)
+            .await
+            .map(owned_fd)
+            .map_err(call_error)
+    }
+}
@@ -0,0 +1,793 @@
+use std::fmt;
+
+use crate::RGB;
+
+/// Why a string isn't a hex color.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidHexColor {
+    /// Only 3, 6 or 8 digits are allowed, after an optional `#`.
+    #[error("Hex color has {0} digits, expected 3, 6 or 8")]
+    Length(usize),
+    /// A character isn't a hex digit.
+    #[error("Invalid hex digit {0}")]
+    Character(char),
+}
+
+impl RGB {
+    /// The color as `#rrggbb`, in lowercase.
+    ///
+    /// Components are clamped and rounded like [`to_u8`](Self::to_u8) does.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let orange = RGB { red: 1.0, green: 0.5, blue: 0.0 };
+    /// assert_eq!(orange.to_hex(), "#ff8000");
+    /// let off = RGB { red: 1.000_000_000_000_2, green: -0.01, blue: 0.2 };
+    /// assert_eq!(off.to_hex(), "#ff0033");
+    /// ```
+    pub fn to_hex(&self) -> String {
+        let [red, green, blue] = self.to_u8();
+        format!("#{:02x}{:02x}{:02x}", red, green, blue)
+    }
+
+    /// The color as `#rrggbbaa` with the given `alpha`, from 0.0 to 1.0.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let orange = RGB { red: 1.0, green: 0.5, blue: 0.0 };
+    /// assert_eq!(orange.to_hex_rgba(0.5), "#ff800080");
+    /// ```
+    pub fn to_hex_rgba(&self, alpha: f64) -> String {
+        format!(
+            "{}{:02x}",
+            self.to_hex(),
+            quantize(alpha, u8::MAX.into()) as u8
+        )
+    }
+
+    /// Parses a `#rgb`, `#rrggbb` or `#rrggbbaa` color, in any case and with
+    /// the `#` optional. The alpha of the last form is ignored.
+    ///
+    /// ```
+    /// # use wlscreenaccess::{InvalidHexColor, RGB};
+    /// let orange = RGB::from_hex("#ff8000")?;
+    /// assert_eq!((orange.red, orange.blue), (1.0, 0.0));
+    /// assert_eq!(RGB::from_hex("#F80")?.to_hex(), "#ff8800");
+    /// assert_eq!(RGB::from_hex("ff800080")?.to_hex(), "#ff8000");
+    /// assert_eq!(RGB::from_hex("#ff80"), Err(InvalidHexColor::Length(4)));
+    /// assert_eq!(RGB::from_hex("#ff800g"), Err(InvalidHexColor::Character('g')));
+    /// # Ok::<(), InvalidHexColor>(())
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Self, InvalidHexColor> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let digits = digits
+            .chars()
+            .map(|char| {
+                char.to_digit(16)
+                    .map(|digit| digit as u8)
+                    .ok_or(InvalidHexColor::Character(char))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [red, green, blue] = match digits[..] {
+            [red, green, blue] => [red, green, blue].map(|digit| digit * 17),
+            [r1, r2, g1, g2, b1, b2] | [r1, r2, g1, g2, b1, b2, _, _] => {
+                [(r1, r2), (g1, g2), (b1, b2)].map(|(high, low)| high << 4 | low)
+            }
+            _ => return Err(InvalidHexColor::Length(digits.len())),
+        };
+        Ok(Self::from_u8([red, green, blue]))
+    }
+
+    /// The components as 8-bit integers, 0.0 being 0 and 1.0 being 255.
+    ///
+    /// Components are clamped to 0.0..=1.0 first, since portals can be
+    /// slightly off, then rounded to the nearest integer, ties to even.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let color = RGB { red: 0.0, green: 1.0, blue: 1.000_000_000_000_000_2 };
+    /// assert_eq!(color.to_u8(), [0, 255, 255]);
+    /// let color = RGB { red: -0.000_1, green: 0.5, blue: 0.999 };
+    /// assert_eq!(color.to_u8(), [0, 128, 255]);
+    /// ```
+    pub fn to_u8(&self) -> [u8; 3] {
+        self.components()
+            .map(|component| quantize(component, u8::MAX.into()) as u8)
+    }
+
+    /// The components as 16-bit integers, 0.0 being 0 and 1.0 being 65535,
+    /// clamped and rounded like [`to_u8`](Self::to_u8).
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let color = RGB { red: 0.0, green: 1.0, blue: 1.000_000_000_000_000_2 };
+    /// assert_eq!(color.to_u16(), [0, 65535, 65535]);
+    /// let color = RGB { red: -0.000_1, green: 0.5, blue: 2.0 };
+    /// assert_eq!(color.to_u16(), [0, 32768, 65535]);
+    /// ```
+    pub fn to_u16(&self) -> [u16; 3] {
+        self.components()
+            .map(|component| quantize(component, u16::MAX.into()) as u16)
+    }
+
+    /// The color of 8-bit components, 255 being 1.0.
+    pub fn from_u8([red, green, blue]: [u8; 3]) -> Self {
+        let max = f64::from(u8::MAX);
+        Self {
+            red: f64::from(red) / max,
+            green: f64::from(green) / max,
+            blue: f64::from(blue) / max,
+        }
+    }
+
+    /// The color of 16-bit components, 65535 being 1.0.
+    pub fn from_u16([red, green, blue]: [u16; 3]) -> Self {
+        let max = f64::from(u16::MAX);
+        Self {
+            red: f64::from(red) / max,
+            green: f64::from(green) / max,
+            blue: f64::from(blue) / max,
+        }
+    }
+
+    /// Undoes the sRGB encoding of the color, as the portal gives it, for
+    /// math in linear light.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let gray = RGB { red: 0.5, green: 0.5, blue: 0.5 }.to_linear();
+    /// assert!((gray.red - 0.214_041).abs() < 1e-6);
+    /// ```
+    pub fn to_linear(&self) -> Self {
+        let [red, green, blue] = self.components().map(|component| {
+            if component <= 0.040_45 {
+                component / 12.92
+            } else {
+                ((component + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        Self { red, green, blue }
+    }
+
+    /// Encodes a color in linear light as sRGB, the reverse of
+    /// [`to_linear`](Self::to_linear).
+    pub fn to_srgb(&self) -> Self {
+        let [red, green, blue] = self.components().map(|component| {
+            if component <= 0.003_130_8 {
+                component * 12.92
+            } else {
+                1.055 * component.powf(1.0 / 2.4) - 0.055
+            }
+        });
+        Self { red, green, blue }
+    }
+
+    /// The relative luminance of the color, from 0.0 for black to 1.0 for
+    /// white, as defined by WCAG.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// assert_eq!(RGB { red: 1.0, green: 1.0, blue: 1.0 }.relative_luminance(), 1.0);
+    /// assert_eq!(RGB { red: 0.0, green: 1.0, blue: 0.0 }.relative_luminance(), 0.7152);
+    /// ```
+    pub fn relative_luminance(&self) -> f64 {
+        let linear = self.to_linear();
+        0.2126 * linear.red + 0.7152 * linear.green + 0.0722 * linear.blue
+    }
+
+    /// Mixes the colors in linear light, `t` from 0.0 for this color to 1.0
+    /// for `other`, e.g. for hover and pressed shades of a picked color.
+    ///
+    /// `t` is clamped to 0.0..=1.0. Its ends give either color exactly, as
+    /// does mixing a color with itself.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let red = RGB::from_hex("#ff0000")?;
+    /// let green = RGB::from_hex("#00ff00")?;
+    /// assert_eq!(red.mix(&green, 0.0), red);
+    /// assert_eq!(red.mix(&green, 1.0), green);
+    /// assert_eq!(red.mix(&green, 2.0), green);
+    /// // Halfway in linear light is lighter than halfway in sRGB, #808000.
+    /// assert_eq!(red.mix(&green, 0.5).to_hex(), "#bcbc00");
+    /// let pastel = RGB { red: 0.682, green: 0.776, blue: 0.812 };
+    /// assert_eq!(pastel.mix(&pastel, 0.3), pastel);
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn mix(&self, other: &RGB, t: f64) -> RGB {
+        let t = t.clamp(0.0, 1.0);
+        if t == 0.0 || self == other {
+            return *self;
+        }
+        if t == 1.0 {
+            return *other;
+        }
+        let (from, to) = (self.to_linear(), other.to_linear());
+        RGB {
+            red: from.red + (to.red - from.red) * t,
+            green: from.green + (to.green - from.green) * t,
+            blue: from.blue + (to.blue - from.blue) * t,
+        }
+        .to_srgb()
+    }
+
+    /// Mixes the color with white, see [`mix`](Self::mix).
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let blue = RGB::from_hex("#0000ff")?;
+    /// assert_eq!(blue.lighten(0.0), blue);
+    /// assert_eq!(blue.lighten(1.0).to_hex(), "#ffffff");
+    /// assert_eq!(blue.darken(1.0).to_hex(), "#000000");
+    /// assert_eq!(blue.darken(0.5).to_hex(), "#0000bc");
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn lighten(&self, amount: f64) -> RGB {
+        self.mix(&RGB::from_u8([255; 3]), amount)
+    }
+
+    /// Mixes the color with black, see [`mix`](Self::mix).
+    pub fn darken(&self, amount: f64) -> RGB {
+        self.mix(&RGB::from_u8([0; 3]), amount)
+    }
+
+    /// The WCAG 2.1 contrast ratio between the colors, from 1.0 for the same
+    /// luminance to 21.0 for black and white, whichever is lighter.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let white = RGB { red: 1.0, green: 1.0, blue: 1.0 };
+    /// let black = RGB { red: 0.0, green: 0.0, blue: 0.0 };
+    /// assert_eq!(black.contrast_ratio(&white), 21.0);
+    /// assert_eq!(white.contrast_ratio(&black), 21.0);
+    /// assert_eq!(white.contrast_ratio(&white), 1.0);
+    /// // The lightest gray meeting the 4.5:1 of level AA on white, and the next.
+    /// let ratio = RGB::from_hex("#767676")?.contrast_ratio(&white);
+    /// assert!((ratio - 4.54).abs() < 0.01, "{}", ratio);
+    /// let ratio = RGB::from_hex("#777777")?.contrast_ratio(&white);
+    /// assert!((ratio - 4.48).abs() < 0.01, "{}", ratio);
+    /// # let ratio = RGB::from_hex("#0000ff")?.contrast_ratio(&white);
+    /// # assert!((ratio - 8.59).abs() < 0.01, "{}", ratio);
+    /// # let ratio = RGB::from_hex("#ff0000")?.contrast_ratio(&black);
+    /// # assert!((ratio - 5.25).abs() < 0.01, "{}", ratio);
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn contrast_ratio(&self, other: &RGB) -> f64 {
+        let (first, second) = (self.relative_luminance(), other.relative_luminance());
+        (first.max(second) + 0.05) / (first.min(second) + 0.05)
+    }
+
+    /// Black or white, whichever contrasts more with the color, e.g. for
+    /// text on a background of this color.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// assert_eq!(RGB::from_hex("#ffff00")?.best_text_color().to_hex(), "#000000");
+    /// assert_eq!(RGB::from_hex("#0000cc")?.best_text_color().to_hex(), "#ffffff");
+    /// // Black still contrasts more with mid gray.
+    /// assert_eq!(RGB::from_hex("#808080")?.best_text_color().to_hex(), "#000000");
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn best_text_color(&self) -> RGB {
+        let black = RGB::from_u8([0; 3]);
+        let white = RGB::from_u8([255; 3]);
+        if self.contrast_ratio(&black) >= self.contrast_ratio(&white) {
+            black
+        } else {
+            white
+        }
+    }
+
+    /// The escape sequence setting the terminal's background to the color,
+    /// for terminals supporting 24-bit color.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let orange = RGB::from_hex("#ff8000")?;
+    /// assert_eq!(orange.to_ansi_truecolor_bg(), "\x1b[48;2;255;128;0m");
+    /// assert_eq!(orange.to_ansi_truecolor_fg(), "\x1b[38;2;255;128;0m");
+    /// assert_eq!(orange.preview_block(3), "\x1b[48;2;255;128;0m   \x1b[0m");
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn to_ansi_truecolor_bg(&self) -> String {
+        let [red, green, blue] = self.to_u8();
+        format!("\x1b[48;2;{};{};{}m", red, green, blue)
+    }
+
+    /// The escape sequence setting the terminal's text to the color, like
+    /// [`to_ansi_truecolor_bg`](Self::to_ansi_truecolor_bg).
+    pub fn to_ansi_truecolor_fg(&self) -> String {
+        let [red, green, blue] = self.to_u8();
+        format!("\x1b[38;2;{};{};{}m", red, green, blue)
+    }
+
+    /// `width` spaces in the color, followed by a reset, to show the color in
+    /// a terminal.
+    pub fn preview_block(&self, width: usize) -> String {
+        format!(
+            "{}{}\x1b[0m",
+            self.to_ansi_truecolor_bg(),
+            " ".repeat(width)
+        )
+    }
+
+    /// The closest color of the 256-color terminal palette, for terminals
+    /// without 24-bit color.
+    ///
+    /// Only the 6×6×6 color cube and the grayscale ramp, 16 to 255, are
+    /// matched, by distance in 8-bit sRGB. The 16 system colors before them
+    /// change with the terminal's theme.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// # let index = |hex| RGB::from_hex(hex).unwrap().to_xterm256();
+    /// // The system colors in xterm, and where they fall in the cube or ramp.
+    /// let system = [
+    ///     ("#000000", 16), ("#800000", 88), ("#008000", 28), ("#808000", 100),
+    ///     ("#000080", 18), ("#800080", 90), ("#008080", 30), ("#c0c0c0", 250),
+    ///     ("#808080", 244), ("#ff0000", 196), ("#00ff00", 46), ("#ffff00", 226),
+    ///     ("#0000ff", 21), ("#ff00ff", 201), ("#00ffff", 51), ("#ffffff", 231),
+    /// ];
+    /// for (i, (hex, closest)) in system.into_iter().enumerate() {
+    ///     assert_eq!(RGB::from_xterm256(i as u8).to_hex(), hex);
+    ///     assert_eq!(index(hex), closest, "{}", hex);
+    /// }
+    /// // The ends of the grayscale ramp, and the cube's black and white past
+    /// // them, ties going to the cube.
+    /// assert_eq!(RGB::from_xterm256(232).to_hex(), "#080808");
+    /// assert_eq!(RGB::from_xterm256(255).to_hex(), "#eeeeee");
+    /// assert_eq!(index("#040404"), 16);
+    /// assert_eq!(index("#050505"), 232);
+    /// assert_eq!(index("#f6f6f6"), 255);
+    /// assert_eq!(index("#f7f7f7"), 231);
+    /// ```
+    pub fn to_xterm256(&self) -> u8 {
+        let rgb = self.to_u8();
+        let distance = |other: [u8; 3]| {
+            rgb.iter()
+                .zip(other)
+                .map(|(&a, b)| (i32::from(a) - i32::from(b)).pow(2))
+                .sum::<i32>()
+        };
+        let level = |component: u8| {
+            (0..6)
+                .min_by_key(|&level| (i32::from(CUBE_LEVELS[level]) - i32::from(component)).abs())
+                .unwrap_or(0)
+        };
+        let [red, green, blue] = rgb.map(level);
+        let cube = [red, green, blue].map(|level| CUBE_LEVELS[level]);
+        let mean = rgb
+            .iter()
+            .map(|&component| u32::from(component))
+            .sum::<u32>()
+            / 3;
+        let step = (mean.saturating_sub(3) / 10).min(23) as u8;
+        let gray = 8 + 10 * step;
+        if distance([gray; 3]) < distance(cube) {
+            232 + step
+        } else {
+            16 + 36 * red as u8 + 6 * green as u8 + blue as u8
+        }
+    }
+
+    /// The color of the 256-color terminal palette at `index`, with xterm's
+    /// colors for the 16 system colors.
+    pub fn from_xterm256(index: u8) -> Self {
+        let rgb = match index {
+            0..=15 => XTERM_SYSTEM[usize::from(index)],
+            16..=231 => {
+                let cube = index - 16;
+                [cube / 36, cube / 6 % 6, cube % 6].map(|level| CUBE_LEVELS[usize::from(level)])
+            }
+            232..=255 => [8 + 10 * (index - 232); 3],
+        };
+        Self::from_u8(rgb)
+    }
+
+    /// The color in CIELAB, under the D65 white point of sRGB.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// # let close = |lab: wlscreenaccess::Lab, [l, a, b]: [f64; 3]| {
+    /// #     (lab.l - l).abs() < 1e-3 && (lab.a - a).abs() < 1e-3 && (lab.b - b).abs() < 1e-3
+    /// # };
+    /// let table = [
+    ///     ("#ffffff", [100.0, 0.0, 0.0]),
+    ///     ("#000000", [0.0, 0.0, 0.0]),
+    ///     ("#808080", [53.585, 0.0, 0.0]),
+    ///     ("#ff0000", [53.241, 80.092, 67.203]),
+    ///     ("#00ff00", [87.735, -86.183, 83.179]),
+    ///     ("#0000ff", [32.297, 79.188, -107.860]),
+    ///     ("#ffff00", [97.139, -21.554, 94.478]),
+    /// ];
+    /// for (hex, lab) in table {
+    ///     let converted = RGB::from_hex(hex)?.to_lab();
+    ///     assert!(close(converted, lab), "{}: {:?}", hex, converted);
+    /// }
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn to_lab(&self) -> Lab {
+        let linear = self.to_linear();
+        let [red, green, blue] = linear.components();
+        let x = 0.412_456_4 * red + 0.357_576_1 * green + 0.180_437_5 * blue;
+        let y = 0.212_672_9 * red + 0.715_152_2 * green + 0.072_175_0 * blue;
+        let z = 0.019_333_9 * red + 0.119_192_0 * green + 0.950_304_1 * blue;
+        let [x, y, z] = [x / D65[0], y / D65[1], z / D65[2]].map(|t| {
+            // Linear near black instead of the cube root's infinite slope.
+            const DELTA: f64 = 6.0 / 29.0;
+            if t > DELTA.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        });
+        Lab {
+            l: 116.0 * y - 16.0,
+            a: 500.0 * (x - y),
+            b: 200.0 * (y - z),
+        }
+    }
+
+    /// The CIE76 color difference, the distance between the colors in
+    /// CIELAB, see [`Lab::distance_cie76`].
+    pub fn distance_cie76(&self, other: &RGB) -> f64 {
+        self.to_lab().distance_cie76(&other.to_lab())
+    }
+
+    /// The CIEDE2000 color difference, see [`Lab::distance_ciede2000`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let picked = RGB::from_hex("#3478f6")?;
+    /// let palette = ["#ff3b30", "#007aff", "#34c759", "#5856d6"];
+    /// let closest = palette
+    ///     .iter()
+    ///     .min_by(|a, b| {
+    ///         let a = picked.distance_ciede2000(&RGB::from_hex(a).unwrap());
+    ///         let b = picked.distance_ciede2000(&RGB::from_hex(b).unwrap());
+    ///         a.total_cmp(&b)
+    ///     });
+    /// assert_eq!(closest, Some(&"#007aff"));
+    /// # Ok::<(), wlscreenaccess::InvalidHexColor>(())
+    /// ```
+    pub fn distance_ciede2000(&self, other: &RGB) -> f64 {
+        self.to_lab().distance_ciede2000(&other.to_lab())
+    }
+
+    /// Whether every component differs by at most `epsilon`, as colors picked
+    /// at different times are rarely exactly equal.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// let picked = RGB { red: 0.1, green: 0.2, blue: 0.3 };
+    /// let again = RGB { red: 0.1 + 1e-9, green: 0.2, blue: 0.3 - 1e-9 };
+    /// assert!(picked.approx_eq(&again, 1e-6));
+    /// assert!(!picked.approx_eq(&again, 1e-12));
+    /// ```
+    pub fn approx_eq(&self, other: &RGB, epsilon: f64) -> bool {
+        self.components()
+            .into_iter()
+            .zip(other.components())
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    }
+
+    /// The color in HSL, e.g. to make a palette around its hue.
+    ///
+    /// Grays have a hue and saturation of 0.
+    ///
+    /// ```
+    /// # use wlscreenaccess::RGB;
+    /// # let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+    /// let table = [
+    ///     // red, green, blue; hue, HSL saturation, lightness; HSV saturation, value
+    ///     ([1.0, 0.0, 0.0], [0.0, 1.0, 0.5], [1.0, 1.0]),
+    ///     ([0.0, 1.0, 0.0], [120.0, 1.0, 0.5], [1.0, 1.0]),
+    ///     ([0.0, 0.0, 1.0], [240.0, 1.0, 0.5], [1.0, 1.0]),
+    ///     ([1.0, 0.0, 1.0], [300.0, 1.0, 0.5], [1.0, 1.0]),
+    ///     ([0.5, 0.5, 0.5], [0.0, 0.0, 0.5], [0.0, 0.5]),
+    ///     ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+    ///     ([1.0, 1.0, 1.0], [0.0, 0.0, 1.0], [0.0, 1.0]),
+    ///     // A pastel, #aec6cf.
+    ///     ([0.682, 0.776, 0.812], [196.6, 0.257, 0.747], [0.160, 0.812]),
+    /// ];
+    /// for ([red, green, blue], [hue, saturation, lightness], [hsv_saturation, value]) in table {
+    ///     let rgb = RGB { red, green, blue };
+    ///     let hsl = rgb.to_hsl();
+    ///     assert!((hsl.hue - hue).abs() < 0.1, "{:?}", hsl);
+    ///     assert!(close(hsl.saturation, saturation) && close(hsl.lightness, lightness), "{:?}", hsl);
+    ///     let hsv = rgb.to_hsv();
+    ///     assert!(close(hsv.hue, hsl.hue), "{:?}", hsv);
+    ///     assert!(close(hsv.saturation, hsv_saturation) && close(hsv.value, value), "{:?}", hsv);
+    ///
+    ///     for back in [hsl.to_rgb(), hsv.to_rgb()] {
+    ///         assert!(close(back.red, red) && close(back.green, green) && close(back.blue, blue));
+    ///     }
+    /// }
+    /// ```
+    pub fn to_hsl(&self) -> Hsl {
+        let (max, min) = self.extremes();
+        let lightness = (max + min) / 2.0;
+        let chroma = max - min;
+        let saturation = if chroma == 0.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Hsl {
+            hue: self.hue(),
+            saturation,
+            lightness,
+        }
+    }
+
+    /// The color in HSV, like [`to_hsl`](Self::to_hsl).
+    pub fn to_hsv(&self) -> Hsv {
+        let (max, min) = self.extremes();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsv {
+            hue: self.hue(),
+            saturation,
+            value: max,
+        }
+    }
+
+    /// The largest and the smallest components.
+    fn extremes(&self) -> (f64, f64) {
+        let [red, green, blue] = self.components();
+        (red.max(green).max(blue), red.min(green).min(blue))
+    }
+
+    /// The hue in degrees, 0 for grays.
+    fn hue(&self) -> f64 {
+        let (max, min) = self.extremes();
+        let chroma = max - min;
+        let sector = if chroma == 0.0 {
+            return 0.0;
+        } else if max == self.red {
+            ((self.green - self.blue) / chroma).rem_euclid(6.0)
+        } else if max == self.green {
+            (self.blue - self.red) / chroma + 2.0
+        } else {
+            (self.red - self.green) / chroma + 4.0
+        };
+        60.0 * sector
+    }
+
+    /// The color of the given hue and chroma, with `offset` added to every
+    /// component.
+    fn from_hue(hue: f64, chroma: f64, offset: f64) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let second = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+        let [red, green, blue] = match sector as u8 {
+            0 => [chroma, second, 0.0],
+            1 => [second, chroma, 0.0],
+            2 => [0.0, chroma, second],
+            3 => [0.0, second, chroma],
+            4 => [second, 0.0, chroma],
+            _ => [chroma, 0.0, second],
+        };
+        Self {
+            red: red + offset,
+            green: green + offset,
+            blue: blue + offset,
+        }
+    }
+
+    fn components(&self) -> [f64; 3] {
+        [self.red, self.green, self.blue]
+    }
+}
+
+/// Formats the color as `#rrggbb`, see [`RGB::to_hex`].
+///
+/// ```
+/// # use wlscreenaccess::RGB;
+/// let color = RGB::from([0.2, 0.4, 1.0]);
+/// assert_eq!(color.to_string(), "#3366ff");
+/// assert_eq!(<[f64; 3]>::from(color), [0.2, 0.4, 1.0]);
+///
+/// let record = toml::to_string(&color)?;
+/// assert_eq!(record, "red = 0.2\ngreen = 0.4\nblue = 1.0\n");
+/// assert_eq!(toml::from_str::<RGB>(&record)?, color);
+/// # let color = RGB { red: 0.1 + 0.2, green: 1.0 / 3.0, blue: 1e-300 };
+/// # assert_eq!(toml::from_str::<RGB>(&toml::to_string(&color)?)?, color);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+impl fmt::Display for RGB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl From<[f64; 3]> for RGB {
+    fn from([red, green, blue]: [f64; 3]) -> Self {
+        Self { red, green, blue }
+    }
+}
+
+impl From<RGB> for [f64; 3] {
+    fn from(color: RGB) -> Self {
+        color.components()
+    }
+}
+
+/// The components of the 256-color palette's color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// xterm's 16 system colors.
+const XTERM_SYSTEM: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [128, 0, 0],
+    [0, 128, 0],
+    [128, 128, 0],
+    [0, 0, 128],
+    [128, 0, 128],
+    [0, 128, 128],
+    [192, 192, 192],
+    [128, 128, 128],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [0, 0, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// The XYZ of the D65 white point.
+const D65: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Scales a component to 0..=max, clamping it and rounding ties to even.
+fn quantize(component: f64, max: f64) -> f64 {
+    (component.clamp(0.0, 1.0) * max).round_ties_even()
+}
+
+/// A color as hue, saturation and lightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    /// In degrees, from 0.0 to 360.0.
+    pub hue: f64,
+    /// From 0.0 to 1.0.
+    pub saturation: f64,
+    /// From 0.0 for black to 1.0 for white.
+    pub lightness: f64,
+}
+
+impl Hsl {
+    /// The color in RGB, see [`RGB::to_hsl`].
+    pub fn to_rgb(&self) -> RGB {
+        let chroma = (1.0 - (2.0 * self.lightness - 1.0).abs()) * self.saturation;
+        RGB::from_hue(self.hue, chroma, self.lightness - chroma / 2.0)
+    }
+}
+
+/// A color as hue, saturation and value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    /// In degrees, from 0.0 to 360.0.
+    pub hue: f64,
+    /// From 0.0 to 1.0.
+    pub saturation: f64,
+    /// From 0.0 for black to 1.0 for the most intense.
+    pub value: f64,
+}
+
+impl Hsv {
+    /// The color in RGB, see [`RGB::to_hsv`].
+    pub fn to_rgb(&self) -> RGB {
+        let chroma = self.value * self.saturation;
+        RGB::from_hue(self.hue, chroma, self.value - chroma)
+    }
+}
+
+/// A color in CIELAB, where distances roughly match perceived differences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    /// The lightness, from 0.0 for black to 100.0 for white.
+    pub l: f64,
+    /// From green, negative, to red, positive.
+    pub a: f64,
+    /// From blue, negative, to yellow, positive.
+    pub b: f64,
+}
+
+impl Lab {
+    /// The CIE76 color difference, the Euclidean distance between the
+    /// colors. About 2.3 is just noticeable.
+    ///
+    /// ```
+    /// # use wlscreenaccess::Lab;
+    /// let first = Lab { l: 50.0, a: 0.0, b: 0.0 };
+    /// let second = Lab { l: 53.0, a: 4.0, b: 0.0 };
+    /// assert_eq!(first.distance_cie76(&second), 5.0);
+    /// ```
+    pub fn distance_cie76(&self, other: &Lab) -> f64 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2))
+            .sqrt()
+    }
+
+    /// The CIEDE2000 color difference, which corrects CIE76 for blues,
+    /// grays and saturated colors. About 1.0 is just noticeable.
+    ///
+    /// ```
+    /// # use wlscreenaccess::Lab;
+    /// // Pairs from Sharma, Wu and Dalal's test data for the formula.
+    /// let pairs = [
+    ///     ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+    ///     ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+    ///     ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+    ///     ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+    ///     ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0009], 7.1792),
+    ///     ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+    ///     ([50.0, 2.5, 0.0], [56.0, -27.0, -3.0], 31.9030),
+    ///     ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+    ///     ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630),
+    ///     ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+    ///     ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381),
+    ///     ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+    /// ];
+    /// for ([l1, a1, b1], [l2, a2, b2], expected) in pairs {
+    ///     let first = Lab { l: l1, a: a1, b: b1 };
+    ///     let second = Lab { l: l2, a: a2, b: b2 };
+    ///     let distance = first.distance_ciede2000(&second);
+    ///     assert!((distance - expected).abs() < 1e-4, "{:?} {:?}: {}", first, second, distance);
+    /// }
+    /// ```
+    pub fn distance_ciede2000(&self, other: &Lab) -> f64 {
+        // Following Sharma, Wu and Dalal, "The CIEDE2000 Color-Difference
+        // Formula: Implementation Notes", with angles in degrees.
+        let pow7 = |c: f64| c.powi(7);
+        let chroma_mean = (self.a.hypot(self.b) + other.a.hypot(other.b)) / 2.0;
+        let g = 0.5 * (1.0 - (pow7(chroma_mean) / (pow7(chroma_mean) + pow7(25.0))).sqrt());
+        let prime = |lab: &Lab| {
+            let a = (1.0 + g) * lab.a;
+            let chroma = a.hypot(lab.b);
+            let hue = if chroma == 0.0 {
+                0.0
+            } else {
+                lab.b.atan2(a).to_degrees().rem_euclid(360.0)
+            };
+            (chroma, hue)
+        };
+        let ((c1, h1), (c2, h2)) = (prime(self), prime(other));
+
+        let delta_l = other.l - self.l;
+        let delta_c = c2 - c1;
+        let delta_h = if c1 * c2 == 0.0 {
+            0.0
+        } else if (h2 - h1).abs() <= 180.0 {
+            h2 - h1
+        } else if h2 - h1 > 180.0 {
+            h2 - h1 - 360.0
+        } else {
+            h2 - h1 + 360.0
+        };
+        let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+        let l_mean = (self.l + other.l) / 2.0;
+        let c_mean = (c1 + c2) / 2.0;
+        let h_mean = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) / 2.0
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+        let cos = |degrees: f64| degrees.to_radians().cos();
+        let t = 1.0 - 0.17 * cos(h_mean - 30.0)
+            + 0.24 * cos(2.0 * h_mean)
+            + 0.32 * cos(3.0 * h_mean + 6.0)
+            - 0.20 * cos(4.0 * h_mean - 63.0);
+        let delta_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+        let r_c = 2.0 * (pow7(c_mean) / (pow7(c_mean) + pow7(25.0))).sqrt();
+        let s_l = 1.0 + 0.015 * (l_mean - 50.0).powi(2) / (20.0 + (l_mean - 50.0).powi(2)).sqrt();
+        let s_c = 1.0 + 0.045 * c_mean;
+        let s_h = 1.0 + 0.015 * c_mean * t;
+        let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+        let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+        (l * l + c * c + h * h + r_t * c * h).sqrt()
+    }
+}
@@ -0,0 +1,106 @@
+//! The session bus connection shared by the free functions.
+//!
+//! Connecting and saying Hello to the bus takes a while compared to a portal
+//! request, so the free functions reuse one connection instead of opening one
+//! per call.
+
+use std::future::Future;
+
+use async_lock::Mutex;
+use zbus::{
+    names::{BusName, OwnedUniqueName},
+    Connection, ProxyBuilder,
+};
+
+use crate::{Error, PORTAL_SERVICE};
+
+/// Empty until first used, and again once found disconnected.
+static SESSION: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// The shared connection to the session bus, connecting if there's none.
+async fn session() -> Result<Connection, Error> {
+    let mut session = SESSION.lock().await;
+    match &*session {
+        Some(connection) => Ok(connection.clone()),
+        None => {
+            let connection = Connection::session().await?;
+            *session = Some(connection.clone());
+            Ok(connection)
+        }
+    }
+}
+
+/// Runs `call` on the shared connection. If the connection turns out to be
+/// gone, it's dropped so the next call connects again.
+pub(crate) async fn with_session<T, F, Fut>(call: F) -> Result<T, Error>
+where
+    F: FnOnce(Connection) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let connection = session().await?;
+    let result = call(connection.clone()).await;
+    if matches!(&result, Err(err) if err.is_disconnected()) {
+        let mut session = SESSION.lock().await;
+        // Another call may have reconnected meanwhile.
+        if session
+            .as_ref()
+            .is_some_and(|cached| same(cached, &connection))
+        {
+            *session = None;
+        }
+    }
+    result
+}
+
+/// Whether `a` and `b` are the same connection, as zbus doesn't compare them.
+pub(crate) fn same(a: &Connection, b: &Connection) -> bool {
+    a.server_guid() == b.server_guid() && a.unique_name() == b.unique_name()
+}
+
+/// Binds `builder` to the unique name of the portal when it's running, for
+/// proxies receiving its signals.
+///
+/// Subscribing through a proxy of the well-known name makes zbus look the
+/// owner up itself, and it can miss the reply when it arrives before the
+/// subscription is listening, dropping every signal after. Before the portal
+/// is started, by the first call, zbus learns its name from the bus instead.
+/// Peer-to-peer connections have no names to bind to.
+pub(crate) async fn bind_to_portal<'a, T>(
+    connection: &Connection,
+    builder: ProxyBuilder<'a, T>,
+) -> zbus::Result<ProxyBuilder<'a, T>> {
+    if connection.unique_name().is_none() {
+        return Ok(builder);
+    }
+    let owner = connection
+        .call_method(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            Some("org.freedesktop.DBus"),
+            "GetNameOwner",
+            &PORTAL_SERVICE,
+        )
+        .await;
+    match owner {
+        Ok(reply) => {
+            builder.destination(BusName::from(reply.body::<OwnedUniqueName>()?.into_inner()))
+        }
+        Err(zbus::Error::MethodError(name, _, _))
+            if name == "org.freedesktop.DBus.Error.NameHasNoOwner" =>
+        {
+            Ok(builder)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Drops the connection shared by the free functions, like [`screenshot`],
+/// so the next call connects again.
+///
+/// They reconnect on their own when the bus goes away; this is for tests
+/// starting a new bus with a different `DBUS_SESSION_BUS_ADDRESS`.
+///
+/// [`screenshot`]: crate::screenshot
+pub async fn reset_connection() {
+    SESSION.lock().await.take();
+}
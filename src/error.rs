@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use zbus::zvariant::OwnedValue;
+
+use crate::response::ResponseError;
+
+/// The error type of this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The user cancelled the request.
+    #[error("The request was cancelled")]
+    Cancelled,
+    /// The request ended for another reason.
+    #[error("The request failed: {}", .message.as_deref().unwrap_or("no details given"))]
+    Other {
+        /// The portal's explanation, when it gave one.
+        message: Option<String>,
+        /// Everything the portal sent along with the failure.
+        details: HashMap<String, OwnedValue>,
+    },
+    /// Talking to the portal over D-Bus failed.
+    #[error("D-Bus error: {0}")]
+    Zbus(#[from] zbus::Error),
+    /// The portal answered with something we did not expect.
+    ///
+    /// Response signals which can't be parsed are [`Error::InvalidResponse`]
+    /// instead, and successes without results [`Error::EmptyResults`].
+    #[error("Unexpected portal response: {0}")]
+    UnexpectedResponse(String),
+    /// The portal answered with a response code newer than this crate.
+    #[error("Unknown portal response code {0}")]
+    UnknownResponseCode(u32),
+    /// The portal reported success without the results the request has, as
+    /// with a Response signal of a lone `u` body. Requests without required
+    /// results accept it, see
+    /// [`ResponseResults::empty`](crate::response::ResponseResults::empty).
+    #[error("The portal reported success without sending the results")]
+    EmptyResults,
+    /// The body of the portal's Response signal couldn't be parsed.
+    #[error("Invalid portal response with signature `{signature}`: {source}")]
+    InvalidResponse {
+        /// The signature of the body the portal sent.
+        signature: String,
+        /// The Response signal, for debugging.
+        message: Arc<zbus::Message>,
+        source: zbus::Error,
+    },
+    /// The results of a successful request lacked a key.
+    #[error("The portal response has no `{field}`, only: {}", .present.join(", "))]
+    MissingField {
+        field: &'static str,
+        /// The keys the results did have.
+        present: Vec<String>,
+    },
+    /// A key of the results of a successful request had a value of the wrong
+    /// type.
+    #[error("The portal response's `{field}` should be `{expected}`, found `{found}`")]
+    InvalidField {
+        field: &'static str,
+        /// The signatures the value may have.
+        expected: String,
+        /// The signature of the value.
+        found: String,
+    },
+    /// A window identifier couldn't be made for the given window.
+    #[error("{0}")]
+    WindowIdentifier(String),
+    /// No portal service is running or activatable on the bus.
+    #[error("The {service} service isn't available, is xdg-desktop-portal installed?")]
+    PortalUnavailable {
+        /// The bus name nobody owns.
+        service: String,
+    },
+    /// The portal's interface is too old for what was asked.
+    #[error("The portal implements version {found} of the interface, {required} is required")]
+    UnsupportedVersion {
+        /// The version the request needs.
+        required: u32,
+        /// The version the portal implements.
+        found: u32,
+    },
+    /// The screenshot's URI doesn't name a local file.
+    #[error("The screenshot URI {0} with the {} scheme doesn't name a local file", .0.scheme())]
+    NotAFile(url::Url),
+    /// The screenshot's file couldn't be read or written.
+    #[error("Couldn't access the screenshot at {}: {source}", .path.display())]
+    File {
+        /// The file the URI resolved to, or the one written to.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: std::io::Error,
+    },
+    /// The screenshot's file was deleted before it could be read.
+    #[error("The screenshot at {} is gone", .0.display())]
+    Vanished(PathBuf),
+    /// The screenshot was still incomplete after waiting for the backend to
+    /// finish writing it.
+    #[error("The screenshot at {} is incomplete", .0.display())]
+    IncompleteFile(PathBuf),
+    /// The screenshot is neither a PNG nor a JPEG, or is truncated.
+    #[error("Couldn't find the dimensions of {}, it isn't a complete PNG or JPEG", .0.display())]
+    UnknownDimensions(PathBuf),
+    /// Saving the screenshot would replace an existing file.
+    #[error("{} already exists", .0.display())]
+    AlreadyExists(PathBuf),
+    /// Saving the screenshot would replace it with itself, which would only
+    /// truncate it.
+    #[error("{} is the screenshot itself", .0.display())]
+    SameFile(PathBuf),
+    /// The screenshot is neither in the home directory nor in `/tmp`, so it
+    /// wasn't deleted.
+    #[error("Refusing to delete {} outside of the home directory and /tmp", .0.display())]
+    UnsafeRemoval(PathBuf),
+    /// The screenshot is outside of what this Flatpak sandbox can read.
+    #[error(
+        "The screenshot at {} isn't exported to the sandbox by the document portal: {source}",
+        .path.display()
+    )]
+    SandboxAccess {
+        /// The host path the portal sent.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: std::io::Error,
+    },
+    /// The screenshot couldn't be decoded.
+    #[cfg(feature = "image")]
+    #[error("Couldn't decode the screenshot{}: {source}", .format.map(|format| format!(" as {:?}", format)).unwrap_or_default())]
+    Decode {
+        /// The format guessed from the file, `None` if it wasn't recognized.
+        format: Option<image::ImageFormat>,
+        /// The decoder's error.
+        source: image::ImageError,
+    },
+    /// The cropped or converted image couldn't be written.
+    #[cfg(feature = "image")]
+    #[error("Couldn't write the image to {}: {source}", .path.display())]
+    Encode {
+        /// The file written to.
+        path: PathBuf,
+        /// The encoder's error.
+        source: image::ImageError,
+    },
+    /// A rectangle exceeds the image it's applied to.
+    #[cfg(feature = "image")]
+    #[error("{rect:?} exceeds the {width}x{height} image")]
+    OutOfBounds {
+        /// The rectangle asked for.
+        rect: crate::Rect,
+        /// The width of the image.
+        width: u32,
+        /// The height of the image.
+        height: u32,
+    },
+    /// A converted image doesn't fit in the size asked for, even at the lowest
+    /// quality.
+    #[cfg(feature = "image")]
+    #[error("The image takes {smallest} bytes at the lowest quality, more than {max_bytes}")]
+    TooLarge {
+        /// The limit asked for.
+        max_bytes: u64,
+        /// The size at the lowest quality.
+        smallest: u64,
+    },
+    /// None of the kinds of sources asked for can be shared by the portal.
+    #[error("The portal can share {available:?}, none of {requested:?}")]
+    SourceTypeUnavailable {
+        /// The kinds of sources asked for.
+        requested: crate::screencast::SourceType,
+        /// The kinds of sources the portal can share.
+        available: crate::screencast::SourceType,
+    },
+    /// A remote desktop call was made on a screencast session.
+    #[error("The session wasn't made by the RemoteDesktop portal")]
+    NotRemoteDesktop,
+    /// The connection to the portal was lost before it answered.
+    #[error("The portal went away before sending a response")]
+    PortalGone,
+    /// The portal didn't answer in time.
+    #[error("Timed out waiting for the portal response")]
+    Timeout,
+}
+
+impl Error {
+    /// Whether the user cancelled the request, which callers usually don't
+    /// want to treat as a failure.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled)
+    }
+
+    /// Whether the portal doesn't implement the method called, which backends
+    /// lacking a feature answer.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(
+            self,
+            Self::Zbus(zbus::Error::MethodError(name, _, _))
+                if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod"
+                    || name.as_str() == "org.freedesktop.DBus.Error.NotSupported"
+        )
+    }
+
+    /// Whether the bus connection the call was made on was lost.
+    pub(crate) fn is_disconnected(&self) -> bool {
+        matches!(self, Self::Zbus(zbus::Error::Io(_)) | Self::PortalGone)
+    }
+
+    /// Tells a portal missing from the bus apart from other failures of a
+    /// method call to `service`.
+    pub(crate) fn from_call(err: zbus::Error, service: &str) -> Self {
+        match &err {
+            zbus::Error::MethodError(name, _, _)
+                if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown"
+                    || name.as_str() == "org.freedesktop.DBus.Error.NameHasNoOwner" =>
+            {
+                Self::PortalUnavailable {
+                    service: service.to_owned(),
+                }
+            }
+            _ => Self::Zbus(err),
+        }
+    }
+}
+
+impl From<ResponseError> for Error {
+    fn from(err: ResponseError) -> Self {
+        let message = err.message().map(ToOwned::to_owned);
+        match err {
+            ResponseError::Cancelled => Self::Cancelled,
+            ResponseError::Other(details) => Self::Other { message, details },
+        }
+    }
+}
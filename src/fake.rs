@@ -0,0 +1,166 @@
+//! Canned portal answers, for testing applications where there's no portal.
+//!
+//! Headless CI rarely runs a session bus, let alone xdg-desktop-portal, so an
+//! application calling [`screenshot`](crate::screenshot) in its tests would
+//! only get errors. With the `fake` feature and an explicit opt-in, the
+//! screenshot and color picking requests answer with what the test
+//! registered here instead, without touching D-Bus.
+//!
+//! Both are needed on purpose: the feature alone changes nothing, so a
+//! release build that happens to enable it still talks to the portal. Opt in
+//! with [`enable`], or by setting `WLSCREENACCESS_FAKE=1` in the environment
+//! of the test run. The variable is enough on its own, so builds shipped to
+//! users shouldn't enable the feature.
+//!
+//! Every request for a screenshot or a color is answered, through the free
+//! functions or a [`Portal`](crate::Portal), including the ones sent with
+//! [`Portal::send_screenshot`](crate::Portal::send_screenshot). The free
+//! functions don't even connect to the bus.
+//!
+//! Enable the feature for tests only:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! wlscreenaccess = { version = "0.1", features = ["fake"] }
+//! ```
+//!
+//! A test of code taking a screenshot then looks like this:
+//!
+//! ```
+//! # use wlscreenaccess::fake;
+//! # futures_lite::future::block_on(async {
+//! fake::enable();
+//! fake::set_screenshot_path("/tmp/fixture.png");
+//! fake::set_color([0.2, 0.4, 1.0]);
+//!
+//! let response = wlscreenaccess::screenshot().await?;
+//! assert_eq!(response.url().path(), "/tmp/fixture.png");
+//! assert_eq!(wlscreenaccess::pick_color_hex().await?, "#3366ff");
+//! # Ok::<(), wlscreenaccess::Error>(())
+//! # }).unwrap();
+//! ```
+//!
+//! The registered answers are global to the process, like the opt-in.
+//! Requests which nothing was registered for fail with [`Error::Other`].
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use zbus::Connection;
+
+use crate::{request, ColorResponse, Error, HandleToken, Request, ScreenshotResponse, RGB};
+
+/// The variable opting in from the environment.
+const VARIABLE: &str = "WLSCREENACCESS_FAKE";
+
+struct Fake {
+    enabled: bool,
+    screenshot: Option<PathBuf>,
+    color: Option<[f64; 3]>,
+}
+
+static FAKE: Mutex<Fake> = Mutex::new(Fake {
+    enabled: false,
+    screenshot: None,
+    color: None,
+});
+
+fn fake() -> std::sync::MutexGuard<'static, Fake> {
+    // The state stays consistent whatever panicked while holding it.
+    FAKE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Answers the requests with the registered fixtures from now on, for the
+/// whole process.
+pub fn enable() {
+    fake().enabled = true;
+}
+
+/// Whether the requests are answered with the fixtures, through [`enable`]
+/// or `WLSCREENACCESS_FAKE=1`.
+pub fn is_enabled() -> bool {
+    fake().enabled || std::env::var_os(VARIABLE).is_some_and(|value| value == "1")
+}
+
+/// Answers screenshot requests with the file at `path`.
+///
+/// Relative paths are taken from the current directory. The file isn't read
+/// until the application reads the screenshot, so it needn't exist yet.
+pub fn set_screenshot_path(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let path = if path.is_absolute() {
+        path.to_owned()
+    } else {
+        std::env::current_dir()
+            .map(|directory| directory.join(path))
+            .unwrap_or_else(|_| path.to_owned())
+    };
+    fake().screenshot = Some(path);
+}
+
+/// Answers color picking requests with `color`.
+pub fn set_color(color: impl Into<RGB>) {
+    fake().color = Some(color.into().into());
+}
+
+/// Forgets the registered fixtures, so the requests fail again.
+pub fn clear() {
+    let mut fake = fake();
+    fake.screenshot = None;
+    fake.color = None;
+}
+
+/// The answer to a screenshot request, `None` unless faking.
+pub(crate) fn screenshot() -> Option<Result<ScreenshotResponse, Error>> {
+    if !is_enabled() {
+        return None;
+    }
+    let path = fake().screenshot.clone();
+    Some(match path {
+        Some(path) => url::Url::from_file_path(&path)
+            .map(ScreenshotResponse::new)
+            .map_err(|()| unregistered(&format!("{} isn't a valid path", path.display()))),
+        None => Err(unregistered("no fake screenshot was registered")),
+    })
+}
+
+/// The answer to a color picking request, `None` unless faking.
+pub(crate) fn color() -> Option<Result<ColorResponse, Error>> {
+    if !is_enabled() {
+        return None;
+    }
+    let color = fake().color;
+    Some(
+        color
+            .map(|color| ColorResponse { color })
+            .ok_or_else(|| unregistered("no fake color was registered")),
+    )
+}
+
+/// The screenshot request made with `token`, answered right away, `None`
+/// unless faking.
+pub(crate) fn screenshot_request(
+    connection: &Connection,
+    token: &HandleToken,
+) -> Option<Result<Request, Error>> {
+    Some(screenshot()?.and_then(|response| request::answered(connection, token, &response)))
+}
+
+/// The color picking request made with `token`, answered right away, `None`
+/// unless faking.
+pub(crate) fn color_request(
+    connection: &Connection,
+    token: &HandleToken,
+) -> Option<Result<Request, Error>> {
+    Some(color()?.and_then(|response| request::answered(connection, token, &response)))
+}
+
+fn unregistered(message: &str) -> Error {
+    Error::Other {
+        message: Some(message.to_owned()),
+        details: HashMap::new(),
+    }
+}
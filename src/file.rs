@@ -0,0 +1,1095 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_lite::AsyncRead;
+
+use crate::sha256::Sha256;
+#[cfg(feature = "image")]
+use crate::{splitmix::SplitMix64, Rect, RGB};
+use crate::{Error, ScreenshotResponse};
+
+impl ScreenshotResponse {
+    /// The path of the screenshot, with the URI's percent-encoding undone.
+    ///
+    /// Fails unless the URI is a `file://` one on this host.
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// let response =
+    ///     ScreenshotResponse::new("file:///home/me/Pictures/Screenshot%20from%20today.png".parse()?);
+    /// assert_eq!(
+    ///     response.to_path()?,
+    ///     std::path::Path::new("/home/me/Pictures/Screenshot from today.png")
+    /// );
+    /// let response = ScreenshotResponse::new("https://example.org/shot.png".parse()?);
+    /// assert!(response.to_path().is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_path(&self) -> Result<PathBuf, Error> {
+        let uri = self.url();
+        if uri.scheme() != "file" {
+            return Err(Error::NotAFile(uri.clone()));
+        }
+        uri.to_file_path()
+            .map_err(|()| Error::NotAFile(uri.clone()))
+    }
+
+    /// Opens the screenshot for reading, in the sandbox this process runs in
+    /// if any, see [`open_with`](Self::open_with).
+    pub fn open(&self) -> Result<File, Error> {
+        self.open_with(&Sandbox::detect())
+    }
+
+    /// Opens the screenshot for reading in `sandbox`.
+    ///
+    /// In a Flatpak sandbox the portal should hand out a path under the
+    /// document portal's `/run/user/UID/doc` mount. When it sends the host
+    /// path instead, the document portal's export of the file is opened if
+    /// there's one. A host path which can't be read in the sandbox is
+    /// [`Error::SandboxAccess`] rather than a plain [`Error::File`].
+    pub fn open_with(&self, sandbox: &Sandbox) -> Result<File, Error> {
+        let path = sandbox.locate(self.to_path()?);
+        File::open(&path).map_err(|source| sandbox.read_error(path, source))
+    }
+
+    /// Reads the whole screenshot.
+    ///
+    /// Some backends delete the file soon after, the error then names the path
+    /// it was looked for at.
+    ///
+    /// Some backends send the response before they're done writing the file,
+    /// so a missing file or a PNG without its end chunk is read again after
+    /// a moment, as [`ReadRetry::default`] says. A PNG still incomplete then
+    /// is [`Error::IncompleteFile`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// let path = std::env::temp_dir().join("wlscreenaccess doc.png");
+    /// let png = b"\x89PNG\r\n\x1a\n\0\0\0\0IEND\xaeB`\x82";
+    /// std::fs::write(&path, png)?;
+    /// let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+    /// assert_eq!(runtime.block_on(response.read_bytes())?, png);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn read_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.read_bytes_with(ReadRetry::default()).await
+    }
+
+    /// Reads the whole screenshot like [`read_bytes`](Self::read_bytes),
+    /// waiting for it to be written as `retry` says.
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// # use std::time::Duration;
+    /// # use wlscreenaccess::{Error, ReadRetry, ScreenshotResponse};
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-retry.png");
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// let png = b"\x89PNG\r\n\x1a\n\0\0\0\0IEND\xaeB`\x82";
+    /// let mut file = std::fs::File::create(&path)?;
+    /// file.write_all(&png[..10])?;
+    /// let writer = std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_millis(100));
+    ///     file.write_all(&png[10..])
+    /// });
+    /// # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+    /// let retry = ReadRetry { attempts: 20, delay: Duration::from_millis(20) };
+    /// assert_eq!(runtime.block_on(response.read_bytes_with(retry))?, png);
+    /// writer.join().unwrap()?;
+    ///
+    /// std::fs::write(&path, &png[..10])?;
+    /// assert!(matches!(
+    ///     runtime.block_on(response.read_bytes_with(retry)),
+    ///     Err(Error::IncompleteFile(_))
+    /// ));
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn read_bytes_with(&self, retry: ReadRetry) -> Result<Vec<u8>, Error> {
+        let path = self.to_path()?;
+        let mut attempt = 1;
+        loop {
+            let last = attempt >= retry.attempts;
+            match crate::runtime::read(&path).await {
+                Ok(bytes) if is_complete(&bytes) => return Ok(bytes),
+                Ok(_) if last => return Err(Error::IncompleteFile(path)),
+                Err(err) if last || err.kind() != ErrorKind::NotFound => {
+                    return Err(read_error(path, err))
+                }
+                _ => {}
+            }
+            crate::trace::warning!(
+                path = %path.display(),
+                attempt,
+                "screenshot not written yet, retrying"
+            );
+            crate::runtime::sleep(retry.delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Opens the screenshot for reading it asynchronously, e.g. to stream it
+    /// elsewhere without holding it all in memory.
+    pub async fn reader(&self) -> Result<impl AsyncRead + Unpin, Error> {
+        let path = self.to_path()?;
+        crate::runtime::open(&path)
+            .await
+            .map_err(|source| read_error(path, source))
+    }
+
+    /// Copies the screenshot to `dest`, returning where it was saved.
+    ///
+    /// Missing parent directories are created. If `dest` is a directory the
+    /// copy is named after the current UTC time, like
+    /// `screenshot-2024-05-01T12-30-00.png`. An existing file is only replaced
+    /// with `overwrite`, otherwise this is [`Error::AlreadyExists`]. Saving
+    /// the screenshot over itself is [`Error::SameFile`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// # let dir = std::env::temp_dir().join("wlscreenaccess-save-to");
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.join("shot.png");
+    /// # std::fs::write(&path, b"\x89PNG")?;
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// let saved = response.save_to(&dir.join("saved/shot.png"), false)?;
+    /// assert_eq!(std::fs::read(&saved)?, b"\x89PNG");
+    /// assert!(response.save_to(&saved, false).is_err());
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn save_to(&self, dest: &Path, overwrite: bool) -> Result<PathBuf, Error> {
+        let source = self.to_path()?;
+        let dest = destination(&source, dest)?;
+        copy(&source, &dest, overwrite)?;
+        Ok(dest)
+    }
+
+    /// Moves the screenshot to `dest` like [`save_to`](Self::save_to) copies
+    /// it, deleting the original.
+    ///
+    /// The file is renamed, or hard linked without `overwrite`, when `dest` is
+    /// on the same filesystem. Otherwise, and on filesystems without hard
+    /// links like vfat or the document portal's, it's copied then unlinked.
+    pub fn move_to(&self, dest: &Path, overwrite: bool) -> Result<PathBuf, Error> {
+        let source = self.to_path()?;
+        let dest = destination(&source, dest)?;
+        let moved = if overwrite {
+            fs::rename(&source, &dest)
+        } else {
+            // Unlike renaming, linking fails instead of replacing `dest`.
+            fs::hard_link(&source, &dest)
+        };
+        match moved {
+            Ok(()) if overwrite => return Ok(dest),
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                return Err(Error::AlreadyExists(dest))
+            }
+            Err(err) if !source.exists() => return Err(read_error(source, err)),
+            // EXDEV, or EPERM and ENOTSUP from filesystems without links.
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::CrossesDevices
+                        | ErrorKind::PermissionDenied
+                        | ErrorKind::Unsupported
+                ) =>
+            {
+                copy(&source, &dest, overwrite)?
+            }
+            Err(err) => {
+                return Err(Error::File {
+                    path: dest,
+                    source: err,
+                })
+            }
+        }
+        fs::remove_file(&source).map_err(|err| read_error(source, err))?;
+        Ok(dest)
+    }
+
+    /// Deletes the portal's file once it has been read or copied, so repeated
+    /// screenshots don't pile up.
+    ///
+    /// A file already gone is fine. As a guard, files outside of the home
+    /// directory and `/tmp` are never deleted, that's
+    /// [`Error::UnsafeRemoval`].
+    pub fn remove_source(&self) -> Result<(), Error> {
+        let path = self.to_path()?;
+        let resolved = match path.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(read_error(path, err)),
+        };
+        let allowed = [
+            std::env::var_os("HOME").map(PathBuf::from),
+            Some(PathBuf::from("/tmp")),
+        ];
+        let allowed = allowed
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .any(|dir| resolved.starts_with(dir));
+        if !allowed {
+            return Err(Error::UnsafeRemoval(path));
+        }
+        match fs::remove_file(&resolved) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(read_error(path, err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ScreenshotResponse {
+    /// The width and height of the screenshot, read from the PNG or JPEG
+    /// header alone instead of decoding the whole image.
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-dimensions.png");
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    /// png.extend_from_slice(&1920u32.to_be_bytes());
+    /// png.extend_from_slice(&1080u32.to_be_bytes());
+    /// std::fs::write(&path, &png)?;
+    /// assert_eq!(response.dimensions()?, (1920, 1080));
+    ///
+    /// std::fs::write(&path, b"\xff\xd8\xff\xc0\0\x11\x08\x02\xd0\x05\0")?;
+    /// assert_eq!(response.dimensions()?, (1280, 720));
+    ///
+    /// std::fs::write(&path, &png[..20])?;
+    /// assert!(response.dimensions().is_err());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dimensions(&self) -> Result<(u32, u32), Error> {
+        let mut file = self.open()?;
+        let path = self.to_path()?;
+        match dimensions(&mut file) {
+            Ok(Some(dimensions)) => Ok(dimensions),
+            // Truncated files are as unrecognizable as other formats.
+            Ok(None) => Err(Error::UnknownDimensions(path)),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                Err(Error::UnknownDimensions(path))
+            }
+            Err(err) => Err(read_error(path, err)),
+        }
+    }
+}
+
+impl ScreenshotResponse {
+    /// The screenshot as a `data:` URI, e.g. to show it in a web view, with
+    /// the MIME type guessed from the file's content.
+    ///
+    /// The file is encoded as it's read, so only the encoded copy is held in
+    /// memory.
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-data-uri.png");
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// std::fs::write(&path, b"\x89PNG\r\n\x1a\n")?;
+    /// assert_eq!(response.to_data_uri()?, "data:image/png;base64,iVBORw0KGgo=");
+    /// assert_eq!(response.to_base64()?, "iVBORw0KGgo=");
+    ///
+    /// std::fs::write(&path, b"\xff\xd8\xff")?;
+    /// assert_eq!(response.to_data_uri()?, "data:image/jpeg;base64,/9j/");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_data_uri(&self) -> Result<String, Error> {
+        self.encode_base64(|magic| format!("data:{};base64,", mime_type(magic)))
+    }
+
+    /// The screenshot encoded in base64, like the payload of
+    /// [`to_data_uri`](Self::to_data_uri).
+    pub fn to_base64(&self) -> Result<String, Error> {
+        self.encode_base64(|_| String::new())
+    }
+
+    /// Encodes the screenshot after the prefix made from its first bytes.
+    fn encode_base64(&self, prefix: impl FnOnce(&[u8]) -> String) -> Result<String, Error> {
+        let path = self.to_path()?;
+        let mut file = File::open(&path).map_err(|err| read_error(path.clone(), err))?;
+        let length = file
+            .metadata()
+            .map_err(|err| read_error(path.clone(), err))?
+            .len();
+        // A multiple of 3 bytes, so only the last chunk is padded.
+        let mut chunk = vec![0; 3 << 14];
+        let mut filled =
+            fill(&mut file, &mut chunk).map_err(|err| read_error(path.clone(), err))?;
+        let mut encoded = prefix(&chunk[..filled]);
+        encoded.reserve_exact(length.div_ceil(3) as usize * 4);
+        while filled > 0 {
+            base64(&chunk[..filled], &mut encoded);
+            if filled < chunk.len() {
+                break;
+            }
+            filled = fill(&mut file, &mut chunk).map_err(|err| read_error(path.clone(), err))?;
+        }
+        Ok(encoded)
+    }
+}
+
+impl ScreenshotResponse {
+    /// The SHA-256 of the screenshot, e.g. to skip identical captures.
+    ///
+    /// The file is hashed as it's read. It being gone, as some backends delete
+    /// it soon after, is [`Error::Vanished`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::ScreenshotResponse;
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-hash.png");
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// # let hex = |hash: [u8; 32]| hash.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    /// std::fs::write(&path, b"abc")?;
+    /// assert_eq!(
+    ///     hex(response.content_hash()?),
+    ///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    /// );
+    /// std::fs::remove_file(&path)?;
+    /// assert!(matches!(response.content_hash(), Err(wlscreenaccess::Error::Vanished(_))));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_hash(&self) -> Result<[u8; 32], Error> {
+        let path = self.to_path()?;
+        let hash_error = |err: io::Error| match err.kind() {
+            ErrorKind::NotFound => Error::Vanished(path.clone()),
+            _ => read_error(path.clone(), err),
+        };
+        let mut file = File::open(&path).map_err(hash_error)?;
+        let mut hasher = Sha256::new();
+        let mut chunk = vec![0; 1 << 16];
+        loop {
+            match file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => hasher.update(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(hash_error(err)),
+            }
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Whether both screenshots have the same content, by their
+    /// [`content_hash`](Self::content_hash).
+    pub fn is_identical_to(&self, other: &ScreenshotResponse) -> Result<bool, Error> {
+        Ok(self.content_hash()? == other.content_hash()?)
+    }
+}
+
+/// How long reading a screenshot waits for the backend to finish writing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadRetry {
+    /// How many times the file is read before giving up, at least once.
+    pub attempts: u32,
+    /// The time between two reads.
+    pub delay: Duration,
+}
+
+impl ReadRetry {
+    /// Reads the file once, as it is.
+    pub const NONE: Self = Self {
+        attempts: 1,
+        delay: Duration::ZERO,
+    };
+}
+
+impl Default for ReadRetry {
+    /// Five reads 50 ms apart.
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            delay: Duration::from_millis(50),
+        }
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// The empty IEND chunk ending every PNG, with its CRC.
+const PNG_END: &[u8] = b"\0\0\0\0IEND\xaeB`\x82";
+
+/// Whether a screenshot read as `bytes` was completely written, as far as can
+/// be told: it's not empty, and a PNG has its end chunk.
+fn is_complete(bytes: &[u8]) -> bool {
+    if bytes.len() < PNG_SIGNATURE.len() {
+        !PNG_SIGNATURE.starts_with(bytes)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        bytes.ends_with(PNG_END)
+    } else {
+        true
+    }
+}
+
+/// Reads into `buf` until it's full or the end of `reader`, returning how much
+/// was read.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Appends `bytes` to `out` in standard, padded base64.
+fn base64(bytes: &[u8], out: &mut String) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for group in bytes.chunks(3) {
+        let word = group.iter().enumerate().fold(0u32, |word, (i, &byte)| {
+            word | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(char::from(ALPHABET[(word >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+/// The MIME type of a file starting with `magic`.
+fn mime_type(magic: &[u8]) -> &'static str {
+    match magic {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'B', b'M', ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Reads the dimensions from a PNG's IHDR chunk or a JPEG's SOF segment.
+fn dimensions(file: &mut (impl Read + Seek)) -> io::Result<Option<(u32, u32)>> {
+    let mut magic = [0; 2];
+    file.read_exact(&mut magic)?;
+    match magic {
+        [0x89, b'P'] => {
+            // Signature, chunk length and type, then width and height.
+            let mut header = [0; 22];
+            file.read_exact(&mut header)?;
+            if &header[..6] != b"NG\r\n\x1a\n" || &header[10..14] != b"IHDR" {
+                return Ok(None);
+            }
+            let width = u32::from_be_bytes([header[14], header[15], header[16], header[17]]);
+            let height = u32::from_be_bytes([header[18], header[19], header[20], header[21]]);
+            Ok(Some((width, height)))
+        }
+        [0xff, 0xd8] => loop {
+            let mut marker = [0; 1];
+            file.read_exact(&mut marker)?;
+            if marker[0] != 0xff {
+                return Ok(None);
+            }
+            // Markers may be padded with any number of 0xff bytes.
+            while marker[0] == 0xff {
+                file.read_exact(&mut marker)?;
+            }
+            match marker[0] {
+                // Restart markers and TEM stand alone.
+                0xd0..=0xd7 | 0x01 => continue,
+                // Every SOF but DHT, JPG and DAC, which share the range.
+                0xc0..=0xcf if !matches!(marker[0], 0xc4 | 0xc8 | 0xcc) => {
+                    // Length and precision, then height and width.
+                    let mut segment = [0; 7];
+                    file.read_exact(&mut segment)?;
+                    let height = u16::from_be_bytes([segment[3], segment[4]]);
+                    let width = u16::from_be_bytes([segment[5], segment[6]]);
+                    return Ok(Some((width.into(), height.into())));
+                }
+                // Start of scan, the frame header should have come before.
+                0xda | 0xd9 => return Ok(None),
+                _ => {
+                    let mut length = [0; 2];
+                    file.read_exact(&mut length)?;
+                    let length = u16::from_be_bytes(length);
+                    file.seek(SeekFrom::Current(i64::from(length) - 2))?;
+                }
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Resolves where to save the screenshot at `source` when asked for `dest`,
+/// creating the directories on the way.
+fn destination(source: &Path, dest: &Path) -> Result<PathBuf, Error> {
+    let dest = if dest.is_dir() {
+        let extension = source
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png");
+        dest.join(format!(
+            "screenshot-{}.{}",
+            timestamp(SystemTime::now()),
+            extension
+        ))
+    } else {
+        dest.to_owned()
+    };
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|source| Error::File {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+    Ok(dest)
+}
+
+fn copy(source: &Path, dest: &Path, overwrite: bool) -> Result<(), Error> {
+    let mut reader = File::open(source).map_err(|err| read_error(source.to_owned(), err))?;
+    // Opening `dest` would truncate the very file about to be read, whichever
+    // path or link it's reached by.
+    if let (Ok(read), Ok(written)) = (reader.metadata(), fs::metadata(dest)) {
+        if (read.dev(), read.ino()) == (written.dev(), written.ino()) {
+            return Err(Error::SameFile(dest.to_owned()));
+        }
+    }
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!overwrite)
+        .open(dest)
+        .map_err(|err| match err.kind() {
+            ErrorKind::AlreadyExists => Error::AlreadyExists(dest.to_owned()),
+            _ => Error::File {
+                path: dest.to_owned(),
+                source: err,
+            },
+        })?;
+    io::copy(&mut reader, &mut writer).map_err(|source| Error::File {
+        path: dest.to_owned(),
+        source,
+    })?;
+    Ok(())
+}
+
+/// Formats `time` as `YYYY-MM-DDTHH-MM-SS` in UTC, without colons so it works
+/// in file names everywhere.
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+    // Days to a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(feature = "image")]
+impl ScreenshotResponse {
+    /// Reads and decodes the screenshot, guessing its format from its content
+    /// since backends don't all save PNGs.
+    pub async fn decode(&self) -> Result<image::DynamicImage, Error> {
+        self.decode_with(ReadRetry::default()).await
+    }
+
+    /// Reads and decodes the screenshot like [`decode`](Self::decode),
+    /// waiting for it to be written as `retry` says.
+    pub async fn decode_with(&self, retry: ReadRetry) -> Result<image::DynamicImage, Error> {
+        let bytes = self.read_bytes_with(retry).await?;
+        let format = image::guess_format(&bytes).map_err(|source| Error::Decode {
+            format: None,
+            source,
+        })?;
+        image::load_from_memory_with_format(&bytes, format).map_err(|source| Error::Decode {
+            format: Some(format),
+            source,
+        })
+    }
+
+    /// Decodes the screenshot and crops it to `rect`, e.g. to select a region
+    /// when the backend's interactive mode isn't available.
+    ///
+    /// A `rect` exceeding the image is [`Error::OutOfBounds`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::{Rect, ScreenshotResponse};
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-crop.png");
+    /// let gradient = image::RgbImage::from_fn(64, 32, |x, y| image::Rgb([x as u8, y as u8, 0]));
+    /// gradient.save(&path)?;
+    /// # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+    /// let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    ///
+    /// let rect = Rect { x: 10, y: 5, width: 20, height: 8 };
+    /// let cropped = runtime.block_on(response.crop(rect))?.to_rgb8();
+    /// assert_eq!(cropped.dimensions(), (20, 8));
+    /// assert_eq!(cropped.get_pixel(0, 0), &image::Rgb([10, 5, 0]));
+    /// assert_eq!(cropped.get_pixel(19, 7), &image::Rgb([29, 12, 0]));
+    ///
+    /// let rect = Rect { x: 60, y: 0, width: 5, height: 1 };
+    /// assert!(runtime.block_on(response.crop(rect)).is_err());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn crop(&self, rect: Rect) -> Result<image::DynamicImage, Error> {
+        let image = self.decode().await?;
+        check_bounds(&image, rect)?;
+        Ok(image.crop_imm(rect.x, rect.y, rect.width, rect.height))
+    }
+
+    /// Decodes the screenshot and scales it down so its longest edge is at
+    /// most `max_edge`, keeping its aspect ratio, with a Lanczos filter.
+    ///
+    /// Images already small enough are returned as they are.
+    ///
+    /// ```
+    /// # use wlscreenaccess::{ScreenshotResponse, ThumbnailFilter};
+    /// # let path = std::env::temp_dir().join("wlscreenaccess-thumbnail.png");
+    /// # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+    /// # let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    /// # let thumbnail = |width, height, max_edge| {
+    /// #     image::RgbImage::new(width, height).save(&path).unwrap();
+    /// #     let image = runtime.block_on(response.thumbnail_with(max_edge, ThumbnailFilter::Triangle));
+    /// #     image.map(|image| (image.width(), image.height()))
+    /// # };
+    /// assert_eq!(thumbnail(1920, 1080, 256)?, (256, 144));
+    /// assert_eq!(thumbnail(1080, 1920, 256)?, (144, 256));
+    /// assert_eq!(thumbnail(40, 20, 256)?, (40, 20));
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn thumbnail(&self, max_edge: u32) -> Result<image::DynamicImage, Error> {
+        self.thumbnail_with(max_edge, ThumbnailFilter::default())
+            .await
+    }
+
+    /// Makes a thumbnail like [`thumbnail`](Self::thumbnail) with the given
+    /// filter.
+    pub async fn thumbnail_with(
+        &self,
+        max_edge: u32,
+        filter: ThumbnailFilter,
+    ) -> Result<image::DynamicImage, Error> {
+        let image = self.decode().await?;
+        if image.width().max(image.height()) <= max_edge {
+            return Ok(image);
+        }
+        let filter = match filter {
+            ThumbnailFilter::Triangle => image::imageops::FilterType::Triangle,
+            ThumbnailFilter::Lanczos3 => image::imageops::FilterType::Lanczos3,
+        };
+        Ok(image.resize(max_edge, max_edge, filter))
+    }
+
+    /// Crops the screenshot like [`crop`](Self::crop) and writes the result
+    /// to `dest` as a PNG.
+    pub async fn crop_to_file(&self, rect: Rect, dest: &Path) -> Result<(), Error> {
+        self.crop(rect)
+            .await?
+            .save_with_format(dest, image::ImageFormat::Png)
+            .map_err(|source| Error::Encode {
+                path: dest.to_owned(),
+                source,
+            })
+    }
+
+    /// Decodes the screenshot and writes it to `dest` in another format, e.g.
+    /// as a JPEG where backends only save PNGs.
+    ///
+    /// With a `max_bytes` limit, JPEGs are encoded again at lower and lower
+    /// quality until they fit, a limit they can't be brought under is
+    /// [`Error::TooLarge`]. Failing to read the screenshot is
+    /// [`Error::Decode`], failing to write the result [`Error::Encode`].
+    ///
+    /// ```
+    /// # use wlscreenaccess::{ConvertFormat, ScreenshotResponse};
+    /// # let dir = std::env::temp_dir().join("wlscreenaccess-convert");
+    /// # std::fs::create_dir_all(&dir)?;
+    /// # let path = dir.join("shot.png");
+    /// let noise = image::RgbImage::from_fn(256, 256, |x, y| image::Rgb([(x * y) as u8, (x ^ y) as u8, 0]));
+    /// noise.save(&path)?;
+    /// # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+    /// let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    ///
+    /// let jpeg = dir.join("shot.jpg");
+    /// let format = ConvertFormat::Jpeg { quality: 95, max_bytes: Some(20_000) };
+    /// runtime.block_on(response.convert_to(format, &jpeg))?;
+    /// assert!(std::fs::metadata(&jpeg)?.len() <= 20_000);
+    /// assert_eq!(image::open(&jpeg)?.width(), 256);
+    ///
+    /// let format = ConvertFormat::Jpeg { quality: 95, max_bytes: Some(100) };
+    /// assert!(runtime.block_on(response.convert_to(format, &jpeg)).is_err());
+    ///
+    /// let webp = dir.join("shot.webp");
+    /// runtime.block_on(response.convert_to(ConvertFormat::WebP, &webp))?;
+    /// assert_eq!(image::open(&webp)?.to_rgb8(), noise);
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn convert_to(&self, format: ConvertFormat, dest: &Path) -> Result<(), Error> {
+        let image = self.decode().await?;
+        let encode_error = |source| Error::Encode {
+            path: dest.to_owned(),
+            source,
+        };
+        let bytes = match format {
+            ConvertFormat::Jpeg { quality, max_bytes } => {
+                // JPEGs have no alpha channel.
+                let image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+                let mut quality = quality.clamp(1, 100);
+                loop {
+                    let mut bytes = Vec::new();
+                    image
+                        .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(
+                            &mut bytes, quality,
+                        ))
+                        .map_err(encode_error)?;
+                    match max_bytes {
+                        Some(max_bytes) if bytes.len() as u64 > max_bytes => {
+                            if quality == 1 {
+                                return Err(Error::TooLarge {
+                                    max_bytes,
+                                    smallest: bytes.len() as u64,
+                                });
+                            }
+                            quality = quality.saturating_sub(JPEG_QUALITY_STEP).max(1);
+                        }
+                        _ => break bytes,
+                    }
+                }
+            }
+            ConvertFormat::WebP => {
+                let mut bytes = Vec::new();
+                image
+                    .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut bytes))
+                    .map_err(encode_error)?;
+                bytes
+            }
+        };
+        fs::write(dest, bytes).map_err(|source| encode_error(image::ImageError::IoError(source)))
+    }
+}
+
+/// The average color of `rect` in `image`, e.g. to pick a color from a
+/// screenshot where the portal can't.
+///
+/// The pixels are averaged in linear light, their alpha is ignored. An empty
+/// `rect` or one exceeding the image is [`Error::OutOfBounds`].
+///
+/// ```
+/// # use wlscreenaccess::{average_color, Rect};
+/// let stripes = image::RgbImage::from_fn(4, 2, |x, _| {
+///     if x % 2 == 0 { image::Rgb([0, 0, 0]) } else { image::Rgb([255, 255, 255]) }
+/// });
+/// let stripes = image::DynamicImage::ImageRgb8(stripes);
+///
+/// let all = Rect { x: 0, y: 0, width: 4, height: 2 };
+/// // Half of white's light, not the #808080 of averaging sRGB values.
+/// assert_eq!(average_color(&stripes, all)?.to_hex(), "#bcbcbc");
+/// let white = Rect { x: 1, y: 0, width: 1, height: 2 };
+/// assert_eq!(average_color(&stripes, white)?.to_hex(), "#ffffff");
+///
+/// let orange = image::RgbImage::from_pixel(8, 8, image::Rgb([255, 128, 0]));
+/// let orange = image::DynamicImage::ImageRgb8(orange);
+/// let corner = Rect { x: 6, y: 6, width: 2, height: 2 };
+/// assert_eq!(average_color(&orange, corner)?.to_hex(), "#ff8000");
+///
+/// assert!(average_color(&orange, Rect { x: 6, y: 6, width: 3, height: 1 }).is_err());
+/// assert!(average_color(&orange, Rect { x: 0, y: 0, width: 0, height: 1 }).is_err());
+/// # Ok::<(), wlscreenaccess::Error>(())
+/// ```
+#[cfg(feature = "image")]
+pub fn average_color(image: &image::DynamicImage, rect: Rect) -> Result<RGB, Error> {
+    check_bounds(image, rect)?;
+    if rect.width == 0 || rect.height == 0 {
+        return Err(Error::OutOfBounds {
+            rect,
+            width: image.width(),
+            height: image.height(),
+        });
+    }
+    let linear: Vec<f64> = (0..=u8::MAX)
+        .map(|value| RGB::from_u8([value; 3]).to_linear().red)
+        .collect();
+    let region = image
+        .crop_imm(rect.x, rect.y, rect.width, rect.height)
+        .to_rgb8();
+    let mut sums = [0.0; 3];
+    for pixel in region.pixels() {
+        for (sum, &value) in sums.iter_mut().zip(&pixel.0) {
+            *sum += linear[usize::from(value)];
+        }
+    }
+    let count = f64::from(rect.width) * f64::from(rect.height);
+    Ok(RGB::from(sums.map(|sum| sum / count)).to_srgb())
+}
+
+/// The `k` main colors of `image` by k-means clustering, with the fraction of
+/// the pixels near each, most common first, e.g. to derive a theme from the
+/// screen.
+///
+/// At most 65536 pixels, evenly spread, are clustered so large captures stay
+/// fast. The initial centers are chosen by k-means++ with a fixed seed, so
+/// the same image always gives the same colors. Fewer than `k` colors are
+/// returned for images with fewer distinct ones.
+///
+/// ```
+/// # use wlscreenaccess::dominant_colors;
+/// let flag = image::RgbImage::from_fn(40, 40, |x, _| {
+///     if x < 30 { image::Rgb([255, 0, 0]) } else { image::Rgb([0, 0, 255]) }
+/// });
+/// let flag = image::DynamicImage::ImageRgb8(flag);
+/// let colors = dominant_colors(&flag, 5);
+/// let colors: Vec<_> = colors.iter().map(|(color, weight)| (color.to_hex(), *weight)).collect();
+/// assert_eq!(colors, [("#ff0000".to_owned(), 0.75), ("#0000ff".to_owned(), 0.25)]);
+/// ```
+#[cfg(feature = "image")]
+pub fn dominant_colors(image: &image::DynamicImage, k: usize) -> Vec<(RGB, f32)> {
+    const MAX_SAMPLES: usize = 1 << 16;
+    const ITERATIONS: usize = 20;
+
+    let pixels = image.to_rgb8();
+    let total = pixels.pixels().len();
+    let samples: Vec<[f32; 3]> = pixels
+        .pixels()
+        .step_by(total.div_ceil(MAX_SAMPLES).max(1))
+        .map(|pixel| pixel.0.map(f32::from))
+        .collect();
+    let distance = |a: &[f32; 3], b: &[f32; 3]| -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+    };
+    let nearest = |centers: &[[f32; 3]], sample: &[f32; 3]| {
+        (0..centers.len())
+            .min_by(|&a, &b| {
+                distance(&centers[a], sample).total_cmp(&distance(&centers[b], sample))
+            })
+            .unwrap_or(0)
+    };
+
+    // k-means++: each center is picked with a probability growing with the
+    // squared distance to the closest one so far.
+    if k == 0 || samples.is_empty() {
+        return Vec::new();
+    }
+    let mut random = SplitMix64(0x5eed);
+    let mut centers = vec![samples[random.below(samples.len())]];
+    while centers.len() < k {
+        let distances: Vec<f64> = samples
+            .iter()
+            .map(|sample| f64::from(distance(&centers[nearest(&centers, sample)], sample)))
+            .collect();
+        let sum: f64 = distances.iter().sum();
+        if sum == 0.0 {
+            // Every sample is a center already.
+            break;
+        }
+        let mut target = random.unit() * sum;
+        let picked = distances
+            .iter()
+            .position(|distance| {
+                target -= distance;
+                target < 0.0
+            })
+            .unwrap_or(samples.len() - 1);
+        centers.push(samples[picked]);
+    }
+
+    let mut assignments = vec![usize::MAX; samples.len()];
+    for _ in 0..ITERATIONS {
+        let mut changed = false;
+        for (assignment, sample) in assignments.iter_mut().zip(&samples) {
+            let closest = nearest(&centers, sample);
+            changed |= *assignment != closest;
+            *assignment = closest;
+        }
+        if !changed {
+            break;
+        }
+        let mut sums = vec![([0.0f64; 3], 0usize); centers.len()];
+        for (&assignment, sample) in assignments.iter().zip(&samples) {
+            let (sum, count) = &mut sums[assignment];
+            for (sum, &component) in sum.iter_mut().zip(sample) {
+                *sum += f64::from(component);
+            }
+            *count += 1;
+        }
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum.map(|sum| (sum / count as f64) as f32);
+            }
+        }
+    }
+
+    let mut counts = vec![0usize; centers.len()];
+    for &assignment in &assignments {
+        counts[assignment] += 1;
+    }
+    let mut colors: Vec<(RGB, f32)> = centers
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(center, count)| {
+            let color = RGB::from(center.map(|component| f64::from(component) / 255.0));
+            (color, count as f32 / samples.len() as f32)
+        })
+        .collect();
+    colors.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    colors
+}
+
+/// Fails unless `rect` is within `image`.
+#[cfg(feature = "image")]
+fn check_bounds(image: &image::DynamicImage, rect: Rect) -> Result<(), Error> {
+    let (width, height) = (image.width(), image.height());
+    let fits = |start: u32, length: u32, limit: u32| {
+        start.checked_add(length).is_some_and(|end| end <= limit)
+    };
+    if !fits(rect.x, rect.width, width) || !fits(rect.y, rect.height, height) {
+        return Err(Error::OutOfBounds {
+            rect,
+            width,
+            height,
+        });
+    }
+    Ok(())
+}
+
+/// How much [`ScreenshotResponse::convert_to`] lowers the JPEG quality each
+/// time the result is too large.
+#[cfg(feature = "image")]
+const JPEG_QUALITY_STEP: u8 = 5;
+
+/// The format [`ScreenshotResponse::convert_to`] writes.
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFormat {
+    /// Lossy JPEG.
+    Jpeg {
+        /// From 1 to 100, the encoder's default is 75.
+        quality: u8,
+        /// The size the file must not exceed, the quality is lowered as needed.
+        max_bytes: Option<u64>,
+    },
+    /// Lossless WebP.
+    WebP,
+}
+
+/// How [`ScreenshotResponse::thumbnail_with`] resamples the image.
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThumbnailFilter {
+    /// Bilinear, faster.
+    Triangle,
+    /// Sharper, slower.
+    #[default]
+    Lanczos3,
+}
+
+fn read_error(path: PathBuf, source: std::io::Error) -> Error {
+    Sandbox::detect().read_error(path, source)
+}
+
+/// The Flatpak sandbox screenshots are read in, see
+/// [`ScreenshotResponse::open_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Where the document portal exports files, `None` outside of a sandbox.
+    documents: Option<PathBuf>,
+}
+
+impl Sandbox {
+    /// Outside of any sandbox, files are read where the portal says.
+    pub const NONE: Self = Self { documents: None };
+
+    /// The sandbox this process runs in, told by the `/.flatpak-info` file
+    /// Flatpak puts in each, with the document portal in the runtime
+    /// directory.
+    pub fn detect() -> Self {
+        if !Path::new("/.flatpak-info").exists() {
+            return Self::NONE;
+        }
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .or_else(|| {
+                let uid = fs::metadata("/proc/self").ok()?.uid();
+                Some(PathBuf::from(format!("/run/user/{}", uid)))
+            });
+        Self {
+            documents: runtime.map(|runtime| runtime.join("doc")),
+        }
+    }
+
+    /// A Flatpak sandbox with the document portal mounted at `documents`,
+    /// usually `/run/user/UID/doc`.
+    pub fn flatpak(documents: impl Into<PathBuf>) -> Self {
+        Self {
+            documents: Some(documents.into()),
+        }
+    }
+
+    /// Whether this is a sandbox.
+    pub fn is_sandboxed(&self) -> bool {
+        self.documents.is_some()
+    }
+
+    /// Whether `path` is exported by the document portal.
+    fn is_document(&self, path: &Path) -> bool {
+        self.documents
+            .as_ref()
+            .is_some_and(|documents| path.starts_with(documents))
+    }
+
+    /// Where the file at the host path `path` is read from: the document
+    /// portal's export of it when there's one, `path` itself otherwise.
+    ///
+    /// Each exported file is in a directory of its own, named by its
+    /// document ID, under its own name.
+    fn locate(&self, path: PathBuf) -> PathBuf {
+        let (Some(documents), Some(name)) = (&self.documents, path.file_name()) else {
+            return path;
+        };
+        if self.is_document(&path) || path.exists() {
+            return path;
+        }
+        let Ok(entries) = fs::read_dir(documents) else {
+            return path;
+        };
+        entries
+            .filter_map(|entry| Some(entry.ok()?.path().join(name)))
+            .find(|exported| exported.is_file())
+            .unwrap_or(path)
+    }
+
+    fn read_error(&self, path: PathBuf, source: std::io::Error) -> Error {
+        if self.is_sandboxed() && !self.is_document(&path) {
+            Error::SandboxAccess { path, source }
+        } else {
+            Error::File { path, source }
+        }
+    }
+}
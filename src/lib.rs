@@ -1,53 +1,197 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod clipboard;
+mod color;
+mod connection;
+mod error;
+#[cfg(feature = "fake")]
+pub mod fake;
+mod file;
+mod record;
+pub mod remotedesktop;
+pub mod request;
 pub mod response;
+mod runtime;
+pub mod screencast;
+pub mod settings;
+mod sha256;
+#[cfg(any(feature = "image", not(feature = "rand")))]
+mod splitmix;
+mod trace;
+pub mod wallpaper;
+#[cfg(feature = "wayland")]
+mod wayland;
+mod window_identifier;
+pub use color::{Hsl, Hsv, InvalidHexColor, Lab};
+pub use connection::reset_connection;
+pub use error::Error;
+#[cfg(feature = "image")]
+pub use file::{average_color, dominant_colors, ConvertFormat, ThumbnailFilter};
+pub use file::{ReadRetry, Sandbox};
+pub use request::{Request, RequestHandle};
+pub use response::ResponseError;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+pub use window_identifier::{WaylandHandle, WindowIdentifier};
 use zbus::{
-    export::futures_util::StreamExt,
-    zvariant::{DeserializeDict, OwnedObjectPath, SerializeDict, Type},
+    export::futures_util::future::{self, Either},
+    zvariant::{OwnedObjectPath, OwnedValue, SerializeDict, Type, Value},
 };
 
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use futures_lite::Stream;
+
 use serde::{Deserialize, Serialize};
-use zbus::{dbus_proxy, names::OwnedMemberName, Connection};
+use zbus::{
+    dbus_proxy,
+    names::{MemberName, OwnedMemberName},
+    Connection,
+};
 
-#[derive(Serialize, Deserialize, Type, Debug)]
+/// The token a request is made with, which the portal puts in the path of its
+/// request object.
+#[derive(Serialize, Deserialize, Type, Debug, Clone, PartialEq, Eq)]
 pub struct HandleToken(OwnedMemberName);
 impl Default for HandleToken {
     fn default() -> Self {
-        let mut rng = thread_rng();
-        let token: String = (&mut rng)
-            .sample_iter(Alphanumeric)
-            .take(10)
-            .map(char::from)
-            .collect();
-        HandleToken::try_from(format!("ashpd_{}", token)).unwrap()
+        HandleToken::unique()
     }
 }
-#[derive(Debug)]
-pub struct HandleInvalidCharacter(char);
 
-impl std::fmt::Display for HandleInvalidCharacter {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("Invalid Character {}", self.0))
+/// Counts the tokens made by this process, so none is made twice.
+static TOKEN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Why a string can't be used as a [`HandleToken`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidHandleToken {
+    /// Only ASCII letters, digits and `_` are allowed.
+    #[error("Invalid Character {0}")]
+    Character(char),
+    /// Tokens can't be empty.
+    #[error("Handle token is empty")]
+    Empty,
+    /// Tokens can't start with a digit.
+    #[error("Handle token starts with the digit {0}")]
+    LeadingDigit(char),
+    /// Tokens are bus member names, limited to 255 bytes.
+    #[error("Handle token is {0} bytes long, at most 255 are allowed")]
+    TooLong(usize),
+}
+
+impl HandleToken {
+    /// Makes a token of `prefix` followed by random characters and the number
+    /// of tokens made so far, which makes it unique within this process.
+    ///
+    /// ```
+    /// # use wlscreenaccess::{HandleToken, InvalidHandleToken};
+    /// assert!(HandleToken::new("myapp_").unwrap().as_str().starts_with("myapp_"));
+    /// assert!(HandleToken::new("").is_ok());
+    /// assert_ne!(HandleToken::new("myapp_"), HandleToken::new("myapp_"));
+    /// assert_eq!(HandleToken::new("my-app"), Err(InvalidHandleToken::Character('-')));
+    /// assert!(matches!(
+    ///     HandleToken::new(&"a".repeat(250)),
+    ///     Err(InvalidHandleToken::TooLong(_))
+    /// ));
+    /// ```
+    pub fn new(prefix: &str) -> Result<Self, InvalidHandleToken> {
+        let count = TOKEN_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let random = random_chars(count);
+        HandleToken::try_from(format!("{}{}_{}", prefix, random, count))
+    }
+
+    /// Makes a token unique within this process, with the crate's `wlsa_`
+    /// prefix. This is what `default()` does.
+    pub fn unique() -> Self {
+        HandleToken::new("wlsa_").expect("The wlsa_ prefix is valid")
     }
+
+    /// The token as sent to the portal.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Ten random characters for a token, starting with a letter so the token is
+/// valid when the prefix is empty.
+#[cfg(feature = "rand")]
+fn random_chars(_count: u64) -> String {
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+    let mut rng = thread_rng();
+    let first = char::from(rng.gen_range(b'a'..=b'z'));
+    let rest = (&mut rng).sample_iter(Alphanumeric).take(9).map(char::from);
+    std::iter::once(first).chain(rest).collect()
+}
+
+/// Ten characters for a token, starting with a letter so the token is valid
+/// when the prefix is empty.
+///
+/// Without `rand`, they're mixed from the time, the process id and `count`.
+/// The count already makes tokens unique within the process, this is only to
+/// tell processes apart.
+#[cfg(not(feature = "rand"))]
+fn random_chars(count: u64) -> String {
+    const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    let seed = nanos ^ (u64::from(std::process::id()) << 32) ^ count;
+    let mut random = splitmix::SplitMix64(seed);
+    let first = char::from(b'a' + (random.next() % 26) as u8);
+    let rest = (0..9).map(|_| {
+        let index = random.next() % ALPHANUMERIC.len() as u64;
+        char::from(ALPHANUMERIC[index as usize])
+    });
+    std::iter::once(first).chain(rest).collect()
 }
 
-impl std::error::Error for HandleInvalidCharacter {}
+impl std::fmt::Display for HandleToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 impl TryFrom<&str> for HandleToken {
-    type Error = HandleInvalidCharacter;
+    type Error = InvalidHandleToken;
+
+    /// Checks `value` is a valid bus member name, which tokens have to be.
+    ///
+    /// ```
+    /// # use wlscreenaccess::{HandleToken, InvalidHandleToken};
+    /// assert_eq!(
+    ///     HandleToken::try_from("9abc"),
+    ///     Err(InvalidHandleToken::LeadingDigit('9'))
+    /// );
+    /// ```
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let first = value.chars().next().ok_or(InvalidHandleToken::Empty)?;
         for char in value.chars() {
             if !char.is_ascii_alphanumeric() && char != '_' {
-                return Err(HandleInvalidCharacter(char));
+                return Err(InvalidHandleToken::Character(char));
             }
         }
+        if first.is_ascii_digit() {
+            return Err(InvalidHandleToken::LeadingDigit(first));
+        }
+        if value.len() > 255 {
+            return Err(InvalidHandleToken::TooLong(value.len()));
+        }
+        // The checks above are the rules of member names.
         Ok(Self(
-            OwnedMemberName::try_from(value).expect("Invalid handle token"),
+            MemberName::from_string_unchecked(value.to_owned()).into(),
         ))
     }
 }
 
 impl TryFrom<String> for HandleToken {
-    type Error = HandleInvalidCharacter;
+    type Error = InvalidHandleToken;
     fn try_from(value: String) -> Result<Self, Self::Error> {
         HandleToken::try_from(value.as_str())
     }
@@ -57,36 +201,176 @@ impl TryFrom<String> for HandleToken {
 pub struct ColorOptions {
     handle_token: HandleToken,
 }
-#[derive(Debug, Clone, Copy)]
+/// A rectangle of pixels, from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct RGB {
     pub red: f64,
     pub green: f64,
     pub blue: f64,
 }
 
-#[derive(DeserializeDict, Clone, Copy, PartialEq, Type, Debug)]
+/// The results of a PickColor request.
+///
+/// Keys other than `color` are skipped, so extensions by newer portals or
+/// particular backends don't break deserialization. The color is accepted as
+/// a struct of three doubles as the spec says, or as an array of them as some
+/// backends send it, but its components have to be finite.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use wlscreenaccess::{response::Response, ColorResponse};
+/// # use zbus::zvariant::{Structure, Value};
+/// # let response = |color: Value<'static>| {
+/// #     let results = HashMap::from([("color", color)]);
+/// #     let message = zbus::Message::signal(
+/// #         None::<&str>,
+/// #         None::<&str>,
+/// #         "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+/// #         "org.freedesktop.portal.Request",
+/// #         "Response",
+/// #         &(0u32, results),
+/// #     )
+/// #     .unwrap();
+/// #     Response::<ColorResponse>::from_message(&message).map(|response| response.ok().unwrap())
+/// # };
+/// let color = response(Value::from(Structure::from((0.1, 0.5, 1.0))))?;
+/// assert_eq!(color.to_rgb().green, 0.5);
+/// let color = response(Value::from(vec![0.1, 0.5, 1.0]))?;
+/// assert_eq!(color.to_rgb().blue, 1.0);
+/// assert!(response(Value::from(vec![f64::NAN, 0.5, 1.0])).is_err());
+/// # Ok::<(), wlscreenaccess::Error>(())
+/// ```
+///
+/// It serializes back to the vardict the portal sends, with the color as
+/// `(ddd)`, or in human-readable formats to a plain map with the `color` as an
+/// array.
+///
+/// ```
+/// # use wlscreenaccess::{response::Response, ColorResponse, RGB};
+/// let picked: ColorResponse = toml::from_str("color = [0.1, 0.5, 1.0]")?;
+/// assert_eq!(picked.as_array(), [0.1, 0.5, 1.0]);
+/// assert_eq!(RGB::from(picked), picked.to_rgb());
+/// assert_eq!(toml::to_string(&picked)?, "color = [0.1, 0.5, 1.0]\n");
+/// assert!(toml::from_str::<ColorResponse>("color = [0.1, nan, 1.0]").is_err());
+///
+/// let message = zbus::Message::signal(
+///     None::<&str>,
+///     None::<&str>,
+///     "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+///     "org.freedesktop.portal.Request",
+///     "Response",
+///     &(0u32, picked),
+/// )?;
+/// assert_eq!(message.body_signature()?, "ua{sv}");
+/// let response = Response::<ColorResponse>::from_message(&message)?;
+/// assert_eq!(response.ok(), Some(picked));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Copy, PartialEq, Type, Debug)]
 #[zvariant(signature = "dict")]
+#[non_exhaustive]
 pub struct ColorResponse {
     color: [f64; 3],
 }
 
-impl ColorResponse {
-    pub fn to_rgb(&self) -> RGB {
-        RGB {
-            red: self.color[0],
-            green: self.color[1],
-            blue: self.color[2],
+/// A color response as the portal sends it.
+#[derive(SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct ColorResults {
+    color: (f64, f64, f64),
+}
+
+/// A color response in human-readable formats.
+#[derive(Serialize, Deserialize)]
+struct ColorRecord {
+    color: [f64; 3],
+}
+
+impl Serialize for ColorResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let color = self.color;
+        if serializer.is_human_readable() {
+            ColorRecord { color }.serialize(serializer)
+        } else {
+            let [red, green, blue] = color;
+            ColorResults {
+                color: (red, green, blue),
+            }
+            .serialize(serializer)
         }
     }
 }
-#[derive(Type, Deserialize, Serialize)]
-#[zvariant(signature = "s")]
-pub enum WindowIdentifier {
-    None,
+
+impl<'de> Deserialize<'de> for ColorResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error as _;
+
+        let color = if deserializer.is_human_readable() {
+            ColorRecord::deserialize(deserializer)?.color
+        } else {
+            let results = HashMap::<String, OwnedValue>::deserialize(deserializer)?;
+            let color = results
+                .get("color")
+                .ok_or_else(|| D::Error::missing_field("color"))?;
+            let components = match &**color {
+                Value::Structure(color) => color.fields(),
+                Value::Array(color) => color.get(),
+                _ => &[],
+            };
+            match components {
+                [Value::F64(red), Value::F64(green), Value::F64(blue)] => [*red, *green, *blue],
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "Expected the color as (ddd) or ad, found {}",
+                        color.value_signature()
+                    )))
+                }
+            }
+        };
+        if color.iter().any(|component| !component.is_finite()) {
+            return Err(D::Error::custom(format!(
+                "The color {:?} has non-finite components",
+                color
+            )));
+        }
+        Ok(Self { color })
+    }
 }
-impl Default for WindowIdentifier {
-    fn default() -> Self {
-        Self::None
+
+impl response::ResponseResults for ColorResponse {
+    fn check(results: &HashMap<String, OwnedValue>) -> Result<(), Error> {
+        response::require_field(results, "color", &["(ddd)", "ad"])
+    }
+}
+
+impl ColorResponse {
+    pub fn to_rgb(&self) -> RGB {
+        RGB::from(self.color)
+    }
+
+    /// The red, green and blue components, from 0.0 to 1.0.
+    pub fn as_array(&self) -> [f64; 3] {
+        self.color
+    }
+}
+
+impl From<ColorResponse> for RGB {
+    fn from(response: ColorResponse) -> Self {
+        response.to_rgb()
     }
 }
 #[dbus_proxy(
@@ -105,7 +389,13 @@ trait Screenshot {
         identifier: &WindowIdentifier,
         options: ScreenshotOptions,
     ) -> zbus::Result<OwnedObjectPath>;
+    #[dbus_proxy(property, name = "version")]
+    fn version(&self) -> zbus::Result<u32>;
 }
+/// Options passed to the Screenshot portal.
+///
+/// Built fluently, e.g. `ScreenshotOptions::new().modal(true).interactive(true)`.
+/// Unset options are left out of the request so the portal uses its own defaults.
 #[derive(SerializeDict, Type, Debug, Default)]
 #[zvariant(signature = "dict")]
 pub struct ScreenshotOptions {
@@ -114,52 +404,703 @@ pub struct ScreenshotOptions {
     interactive: Option<bool>,
 }
 
-#[derive(DeserializeDict, Clone, Type, Debug)]
+impl ScreenshotOptions {
+    /// Creates options with a random handle token and nothing else set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the given handle token instead of a random one.
+    pub fn handle_token(mut self, handle_token: HandleToken) -> Self {
+        self.handle_token = handle_token;
+        self
+    }
+
+    /// Sets whether the dialog should be modal.
+    pub fn modal(mut self, modal: bool) -> Self {
+        self.modal = Some(modal);
+        self
+    }
+
+    /// Sets whether the dialog should offer customization before taking a screenshot.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = Some(interactive);
+        self
+    }
+
+    /// The handle token the request will be made with.
+    pub fn token(&self) -> &HandleToken {
+        &self.handle_token
+    }
+
+    /// Whether the dialog was asked to be modal, if set.
+    pub fn is_modal(&self) -> Option<bool> {
+        self.modal
+    }
+
+    /// Whether the dialog was asked to be interactive, if set.
+    pub fn is_interactive(&self) -> Option<bool> {
+        self.interactive
+    }
+}
+
+/// The results of a Screenshot request.
+///
+/// Keys other than `uri`, such as the ones KDE adds, are skipped rather than
+/// rejected. Human-readable serde formats get a plain map with the `uri`, for
+/// keeping records of screenshots, others the vardict the portal sends.
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use wlscreenaccess::ScreenshotResponse;
+/// let response = ScreenshotResponse::new("file:///tmp/Screenshot%201.png".parse()?);
+/// assert_eq!(response.to_string(), "file:///tmp/Screenshot%201.png");
+/// assert_eq!(response.url().path(), "/tmp/Screenshot%201.png");
+/// assert_eq!(
+///     PathBuf::try_from(response.clone())?,
+///     PathBuf::from("/tmp/Screenshot 1.png")
+/// );
+///
+/// let record = toml::to_string(&response)?;
+/// assert_eq!(record, "uri = \"file:///tmp/Screenshot%201.png\"\n");
+/// let response: ScreenshotResponse = toml::from_str(&record)?;
+/// assert_eq!(response.into_url().as_str(), "file:///tmp/Screenshot%201.png");
+///
+/// let response = ScreenshotResponse::from(url::Url::parse("https://example.org/shot.png")?);
+/// assert!(PathBuf::try_from(response).is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Type, Debug, PartialEq, Eq)]
 #[zvariant(signature = "dict")]
+#[non_exhaustive]
 pub struct ScreenshotResponse {
+    #[deprecated(note = "use `url()` or `into_url()`, the field will become private")]
     pub uri: url::Url,
 }
 
-pub async fn color_pick() -> zbus::Result<ColorResponse> {
-    let connection = Connection::session().await?;
-    let poxy = ScreenshotProxy::new(&connection).await?;
-    let reply = poxy
-        .pick_color(&WindowIdentifier::None, ColorOptions::default())
-        .await?;
-    let proxy: zbus::Proxy = zbus::ProxyBuilder::new_bare(&connection)
-        .interface("org.freedesktop.portal.Request")?
-        .path(reply)?
-        .destination("org.freedesktop.portal.Desktop")?
-        .build()
-        .await?;
-    let mut request = proxy.receive_signal("Response").await?;
-    let message = request.next().await.unwrap();
-    //println!("{:?}", message);
-    let color: response::Response<ColorResponse> = message.body().unwrap();
-    match color {
-        response::Response::Ok(response) => Ok(response),
-        response::Response::Err(_) => Err(zbus::Error::Unsupported),
-    }
-}
-pub async fn screenshot() -> zbus::Result<ScreenshotResponse> {
-    let connection = Connection::session().await?;
-    let poxy = ScreenshotProxy::new(&connection).await?;
-    let reply = poxy
-        .screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
-        .await?;
-    let proxy: zbus::Proxy = zbus::ProxyBuilder::new_bare(&connection)
-        .interface("org.freedesktop.portal.Request")?
-        .path(reply)?
-        .destination("org.freedesktop.portal.Desktop")?
-        .build()
-        .await?;
-    let mut request = proxy.receive_signal("Response").await?;
-    let message = request.next().await.unwrap();
-    //println!("{:?}", message);
-    //let color: response::Response<ColorResponse> = message.body().unwrap();
-    let color: response::Response<ScreenshotResponse> = message.body().unwrap();
-    match color {
-        response::Response::Ok(response) => Ok(response),
-        response::Response::Err(_) => Err(zbus::Error::Unsupported),
+// Accessors for the deprecated field, until it's made private.
+#[allow(deprecated)]
+impl ScreenshotResponse {
+    /// A response for the screenshot at `uri`, e.g. one kept from an earlier
+    /// capture.
+    pub fn new(uri: url::Url) -> Self {
+        Self { uri }
+    }
+
+    /// The URI the portal saved the screenshot at.
+    pub fn url(&self) -> &url::Url {
+        &self.uri
+    }
+
+    /// Takes the URI the portal saved the screenshot at.
+    pub fn into_url(self) -> url::Url {
+        self.uri
+    }
+}
+
+impl From<url::Url> for ScreenshotResponse {
+    fn from(uri: url::Url) -> Self {
+        Self::new(uri)
+    }
+}
+
+/// A screenshot response as the portal sends it.
+#[derive(SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct ScreenshotResults {
+    uri: url::Url,
+}
+
+/// A screenshot response in human-readable formats.
+#[derive(Serialize, Deserialize)]
+struct ScreenshotRecord {
+    uri: url::Url,
+}
+
+impl Serialize for ScreenshotResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let uri = self.url().clone();
+        if serializer.is_human_readable() {
+            ScreenshotRecord { uri }.serialize(serializer)
+        } else {
+            ScreenshotResults { uri }.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScreenshotResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            ScreenshotRecord::deserialize(deserializer).map(|record| Self::new(record.uri))
+        } else {
+            use serde::de::Error as _;
+
+            // Read through values rather than `ScreenshotResults`, as zvariant
+            // panics on a `uri` of another type instead of failing.
+            let results = HashMap::<String, OwnedValue>::deserialize(deserializer)?;
+            let uri = results
+                .get("uri")
+                .ok_or_else(|| D::Error::missing_field("uri"))?;
+            match &**uri {
+                Value::Str(uri) => url::Url::parse(uri)
+                    .map(Self::new)
+                    .map_err(D::Error::custom),
+                _ => Err(D::Error::custom(format!(
+                    "Expected the uri as s, found {}",
+                    uri.value_signature()
+                ))),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ScreenshotResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self.url(), f)
+    }
+}
+
+impl TryFrom<ScreenshotResponse> for std::path::PathBuf {
+    type Error = Error;
+
+    /// The path of the screenshot, see [`ScreenshotResponse::to_path`].
+    fn try_from(response: ScreenshotResponse) -> Result<Self, Self::Error> {
+        response.to_path()
+    }
+}
+
+impl response::ResponseResults for ScreenshotResponse {
+    fn check(results: &HashMap<String, OwnedValue>) -> Result<(), Error> {
+        response::require_field(results, "uri", &["s"])
+    }
+}
+
+/// A client for the Screenshot portal.
+///
+/// It owns the session bus connection and the portal proxy, so repeated
+/// requests don't reconnect each time. Cloning is cheap and clones share the
+/// same connection, so it can be handed to several tasks.
+#[derive(Clone, Debug)]
+pub struct Portal {
+    connection: Connection,
+    proxy: ScreenshotProxy<'static>,
+    /// The interface version once read, 0 before.
+    version: Arc<AtomicU32>,
+    /// How reading the screenshots waits for them to be written.
+    read_retry: ReadRetry,
+}
+
+impl Portal {
+    /// Connects to the session bus and creates the portal proxy.
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Connects to the bus at `address`, like `unix:path=/run/user/1000/bus`,
+    /// rather than the session bus the environment names. Sandboxes passing
+    /// the bus address explicitly need this, as do tests on a private bus.
+    pub async fn connect_to_address(address: &str) -> Result<Self, Error> {
+        let connection = zbus::ConnectionBuilder::address(address)?.build().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Creates the portal proxy on an existing connection, so an application
+    /// already talking to other portals doesn't need a second one.
+    pub async fn with_connection(connection: &Connection) -> Result<Self, Error> {
+        let proxy = ScreenshotProxy::new(connection).await?;
+        Ok(Self {
+            connection: connection.clone(),
+            proxy,
+            version: Arc::new(AtomicU32::new(0)),
+            read_retry: ReadRetry::default(),
+        })
+    }
+
+    /// Sets how [`read_screenshot`](Self::read_screenshot) waits for a
+    /// screenshot to be written, in case the backend answers before it's done.
+    pub fn with_read_retry(mut self, read_retry: ReadRetry) -> Self {
+        self.read_retry = read_retry;
+        self
+    }
+
+    /// How reading the screenshots waits for them to be written.
+    pub fn read_retry(&self) -> ReadRetry {
+        self.read_retry
+    }
+
+    /// The connection the requests are made on.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The version of the Screenshot interface the portal implements. It's
+    /// only read once, clones of this `Portal` share it too.
+    pub async fn version(&self) -> Result<u32, Error> {
+        match self.version.load(Ordering::Relaxed) {
+            0 => {
+                let version = self
+                    .proxy
+                    .version()
+                    .await
+                    .map_err(|err| Error::from_call(err, PORTAL_SERVICE))?;
+                self.version.store(version, Ordering::Relaxed);
+                Ok(version)
+            }
+            version => Ok(version),
+        }
+    }
+
+    /// Sends a PickColor request without waiting for its response, see
+    /// [`Request::receive_response`].
+    pub async fn send_pick_color(&self, identifier: &WindowIdentifier) -> Result<Request, Error> {
+        #[cfg(feature = "fake")]
+        if let Some(request) = fake::color_request(&self.connection, &HandleToken::unique()) {
+            return request;
+        }
+        let (token, call) = self.pick_color_call(identifier);
+        request::send(&self.connection, &token, call).await
+    }
+
+    /// Sends a Screenshot request without waiting for its response, see
+    /// [`Request::receive_response`].
+    pub async fn send_screenshot(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+    ) -> Result<Request, Error> {
+        #[cfg(feature = "fake")]
+        if let Some(request) = fake::screenshot_request(&self.connection, options.token()) {
+            return request;
+        }
+        let (token, call) = self.screenshot_call(identifier, options).await?;
+        request::send(&self.connection, &token, call).await
+    }
+
+    /// The PickColor call, recorded, with the token it's made with.
+    fn pick_color_call<'a>(
+        &'a self,
+        identifier: &'a WindowIdentifier,
+    ) -> (
+        HandleToken,
+        impl Future<Output = zbus::Result<OwnedObjectPath>> + 'a,
+    ) {
+        let options = ColorOptions::default();
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "PickColor", &token, &options);
+        (token, self.proxy.pick_color(identifier, options))
+    }
+
+    /// The Screenshot call, recorded, with the token it's made with. Fails if
+    /// the portal is too old for `options`.
+    async fn screenshot_call<'a>(
+        &'a self,
+        identifier: &'a WindowIdentifier,
+        options: ScreenshotOptions,
+    ) -> Result<
+        (
+            HandleToken,
+            impl Future<Output = zbus::Result<OwnedObjectPath>> + 'a,
+        ),
+        Error,
+    > {
+        // Older portals would ignore the option and skip the dialog.
+        if options.interactive == Some(true) {
+            let found = self.version().await?;
+            if found < 2 {
+                return Err(Error::UnsupportedVersion { required: 2, found });
+            }
+        }
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "Screenshot", &token, &options);
+        Ok((token, self.proxy.screenshot(identifier, options)))
+    }
+
+    /// Picks a color from anywhere on screen, with the dialog floating on its
+    /// own.
+    ///
+    /// Dismissing the dialog is [`Error::Cancelled`].
+    pub async fn pick_color(&self) -> Result<ColorResponse, Error> {
+        self.pick_color_with_parent(&WindowIdentifier::None).await
+    }
+
+    /// Picks a color with the dialog parented to the given window.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "color_pick", skip_all)
+    )]
+    pub async fn pick_color_with_parent(
+        &self,
+        identifier: &WindowIdentifier,
+    ) -> Result<ColorResponse, Error> {
+        self.send_pick_color(identifier)
+            .await?
+            .receive_response()
+            .await
+    }
+
+    /// Picks a color, falling back to averaging `rect` of a screenshot when
+    /// the portal doesn't implement picking, as with some wlroots backends.
+    ///
+    /// See [`average_color`].
+    #[cfg(feature = "image")]
+    pub async fn pick_color_fallback(&self, rect: Rect) -> Result<RGB, Error> {
+        match self.pick_color().await {
+            Ok(color) => Ok(color.to_rgb()),
+            Err(err) if err.is_unsupported() => {
+                trace::warning!(error = %err, "color picking unsupported, averaging a screenshot");
+                let screenshot = self.screenshot().await?;
+                let image = self.decode_screenshot(&screenshot).await?;
+                average_color(&image, rect)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Lets the user pick colors one after the other, e.g. to build a
+    /// palette, until they cancel.
+    ///
+    /// Each pick is a new request. Cancelling ends the stream, other errors
+    /// are yielded before ending it. Dropping the stream during a pick closes
+    /// its request.
+    pub fn pick_colors(&self) -> impl Stream<Item = Result<ColorResponse, Error>> {
+        self.pick_colors_with_parent(&WindowIdentifier::None)
+    }
+
+    /// Picks colors like [`pick_colors`](Self::pick_colors), with the dialogs
+    /// parented to the given window.
+    pub fn pick_colors_with_parent(
+        &self,
+        identifier: &WindowIdentifier,
+    ) -> impl Stream<Item = Result<ColorResponse, Error>> {
+        let state = Some((self.clone(), identifier.clone()));
+        futures_lite::stream::unfold(state, |state| async move {
+            let (portal, identifier) = state?;
+            match portal.pick_color_with_parent(&identifier).await {
+                Ok(color) => Some((Ok(color), Some((portal, identifier)))),
+                Err(Error::Cancelled) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+
+    /// Starts picking a color without waiting for the result.
+    ///
+    /// The returned [`RequestHandle`] can close the request while the
+    /// returned future is still pending.
+    pub async fn start_pick_color(
+        &self,
+        identifier: &WindowIdentifier,
+    ) -> Result<
+        (
+            RequestHandle,
+            impl Future<Output = Result<ColorResponse, Error>>,
+        ),
+        Error,
+    > {
+        Ok(request::track(self.send_pick_color(identifier).await?))
+    }
+
+    /// Starts taking a screenshot without waiting for the result.
+    ///
+    /// The returned [`RequestHandle`] can close the request while the
+    /// returned future is still pending.
+    pub async fn start_screenshot(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+    ) -> Result<
+        (
+            RequestHandle,
+            impl Future<Output = Result<ScreenshotResponse, Error>>,
+        ),
+        Error,
+    > {
+        Ok(request::track(
+            self.send_screenshot(identifier, options).await?,
+        ))
+    }
+
+    /// Takes a screenshot, with the dialog floating on its own if the portal
+    /// shows one.
+    ///
+    /// Dismissing the dialog is [`Error::Cancelled`].
+    pub async fn screenshot(&self) -> Result<ScreenshotResponse, Error> {
+        self.screenshot_with_parent(&WindowIdentifier::None).await
+    }
+
+    /// Takes a screenshot with the dialog parented to the given window.
+    pub async fn screenshot_with_parent(
+        &self,
+        identifier: &WindowIdentifier,
+    ) -> Result<ScreenshotResponse, Error> {
+        self.screenshot_with_options(identifier, ScreenshotOptions::default())
+            .await
+    }
+
+    /// Takes a screenshot with the dialog parented to the given window, as
+    /// `options` say.
+    ///
+    /// Asking for the interactive dialog fails with
+    /// [`Error::UnsupportedVersion`] on portals older than version 2, which
+    /// would skip it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "screenshot", skip_all, fields(token = options.token().as_str()))
+    )]
+    pub async fn screenshot_with_options(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+    ) -> Result<ScreenshotResponse, Error> {
+        self.send_screenshot(identifier, options)
+            .await?
+            .receive_response()
+            .await
+    }
+
+    /// Takes a screenshot, giving up if the portal hasn't answered within
+    /// `timeout`.
+    ///
+    /// On timeout the request is closed so its dialog doesn't linger, and
+    /// [`Error::Timeout`] is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "screenshot", skip_all, fields(token = options.token().as_str()))
+    )]
+    pub async fn screenshot_with_timeout(
+        &self,
+        identifier: &WindowIdentifier,
+        options: ScreenshotOptions,
+        timeout: Duration,
+    ) -> Result<ScreenshotResponse, Error> {
+        let response = Box::pin(async {
+            self.send_screenshot(identifier, options)
+                .await?
+                .receive_response()
+                .await
+        });
+        let raced = future::select(response, Box::pin(runtime::sleep(timeout))).await;
+        match raced {
+            Either::Left((response, _)) => response,
+            Either::Right((_, response)) => {
+                trace::warning!(
+                    ?timeout,
+                    "timed out waiting for the response, closing the request"
+                );
+                // Dropping the pending response closes the request.
+                drop(response);
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Reads the whole screenshot, waiting for it to be written as
+    /// [`read_retry`](Self::read_retry) says.
+    pub async fn read_screenshot(&self, response: &ScreenshotResponse) -> Result<Vec<u8>, Error> {
+        response.read_bytes_with(self.read_retry).await
+    }
+
+    /// Reads and decodes the screenshot, waiting for it to be written as
+    /// [`read_retry`](Self::read_retry) says.
+    #[cfg(feature = "image")]
+    pub async fn decode_screenshot(
+        &self,
+        response: &ScreenshotResponse,
+    ) -> Result<image::DynamicImage, Error> {
+        response.decode_with(self.read_retry).await
+    }
+
+    /// Takes a screenshot through the interactive dialog, letting the user
+    /// pick what to capture.
+    ///
+    /// A dismissed dialog is reported as [`Error::Cancelled`]. Portals older
+    /// than version 2 have no interactive mode, which is
+    /// [`Error::UnsupportedVersion`].
+    pub async fn screenshot_interactive(&self) -> Result<ScreenshotResponse, Error> {
+        let options = ScreenshotOptions::new().modal(true).interactive(true);
+        self.screenshot_with_options(&WindowIdentifier::None, options)
+            .await
+    }
+}
+
+/// The bus name of the xdg-desktop-portal frontend serving every portal.
+pub(crate) const PORTAL_SERVICE: &str = "org.freedesktop.portal.Desktop";
+
+/// Whether the portal service is running or can be started on the bus of
+/// `connection`.
+///
+/// Without xdg-desktop-portal, as on some minimal compositor setups, requests
+/// only fail after the bus gave up activating it. Checking first avoids the
+/// wait, for any of the portal interfaces.
+pub async fn is_available(connection: &Connection) -> Result<bool, Error> {
+    let dbus = zbus::fdo::DBusProxy::new(connection).await?;
+    let name = zbus::names::WellKnownName::from_static_str_unchecked(PORTAL_SERVICE);
+    if dbus
+        .name_has_owner(name.into())
+        .await
+        .map_err(zbus::Error::from)?
+    {
+        return Ok(true);
     }
+    let activatable = dbus
+        .list_activatable_names()
+        .await
+        .map_err(zbus::Error::from)?;
+    Ok(activatable
+        .iter()
+        .any(|name| name.as_str() == PORTAL_SERVICE))
+}
+
+/// The [`Portal`] the free functions share, on the shared connection.
+static PORTAL: async_lock::Mutex<Option<Portal>> = async_lock::Mutex::new(None);
+
+/// What the free functions answer when faking, see [`fake`].
+trait Faked: Sized {
+    /// The answer, `None` unless faking.
+    fn faked() -> Option<Result<Self, Error>> {
+        None
+    }
+}
+
+impl Faked for ScreenshotResponse {
+    #[cfg(feature = "fake")]
+    fn faked() -> Option<Result<Self, Error>> {
+        fake::screenshot()
+    }
+}
+
+impl Faked for ColorResponse {
+    #[cfg(feature = "fake")]
+    fn faked() -> Option<Result<Self, Error>> {
+        fake::color()
+    }
+}
+
+impl Faked for RGB {
+    #[cfg(feature = "fake")]
+    fn faked() -> Option<Result<Self, Error>> {
+        Some(fake::color()?.map(|color| color.to_rgb()))
+    }
+}
+
+/// Runs `call` on a [`Portal`] over the session bus connection the free
+/// functions share, see [`reset_connection`].
+///
+/// When faking, the fixture answers without connecting, as there may be no
+/// bus to connect to.
+async fn with_portal<T, F, Fut>(call: F) -> Result<T, Error>
+where
+    T: Faked,
+    F: FnOnce(Portal) -> Fut,
+    Fut: std::future::Future<Output = Result<T, Error>>,
+{
+    if let Some(answer) = T::faked() {
+        return answer;
+    }
+    connection::with_session(|connection| async move {
+        let portal = {
+            let mut shared = PORTAL.lock().await;
+            match &*shared {
+                Some(portal) if connection::same(portal.connection(), &connection) => {
+                    portal.clone()
+                }
+                _ => {
+                    let portal = Portal::with_connection(&connection).await?;
+                    *shared = Some(portal.clone());
+                    portal
+                }
+            }
+        };
+        call(portal).await
+    })
+    .await
+}
+
+/// Lets the user pick a color on screen.
+///
+/// Dismissing the dialog is [`Error::Cancelled`], so it can be told apart from
+/// the portal failing, which is [`Error::Other`], with [`Error::is_cancelled`].
+pub async fn color_pick() -> Result<ColorResponse, Error> {
+    with_portal(|portal| async move { portal.pick_color().await }).await
+}
+/// Lets the user pick a color on screen, as a `#rrggbb` string.
+///
+/// Dismissing the dialog is [`Error::Cancelled`], like with [`color_pick`].
+pub async fn pick_color_hex() -> Result<String, Error> {
+    Ok(color_pick().await?.to_rgb().to_hex())
+}
+/// Picks a color, or averages `rect` of a screenshot where the portal can't,
+/// see [`Portal::pick_color_fallback`].
+#[cfg(feature = "image")]
+pub async fn pick_color_fallback(rect: Rect) -> Result<RGB, Error> {
+    with_portal(|portal| async move { portal.pick_color_fallback(rect).await }).await
+}
+/// Picks a color with the dialog parented to the given window.
+pub async fn color_pick_with_parent(identifier: &WindowIdentifier) -> Result<ColorResponse, Error> {
+    with_portal(|portal| async move { portal.pick_color_with_parent(identifier).await }).await
+}
+/// Picks a color on the given connection instead of opening a new one.
+pub async fn color_pick_on(connection: &Connection) -> Result<ColorResponse, Error> {
+    Portal::with_connection(connection)
+        .await?
+        .pick_color()
+        .await
+}
+/// Takes a screenshot.
+///
+/// Dismissing the dialog is [`Error::Cancelled`], so it can be told apart from
+/// the portal failing, which is [`Error::Other`], with [`Error::is_cancelled`].
+pub async fn screenshot() -> Result<ScreenshotResponse, Error> {
+    with_portal(|portal| async move { portal.screenshot().await }).await
+}
+/// Takes a screenshot with the dialog parented to the given window.
+pub async fn screenshot_with_parent(
+    identifier: &WindowIdentifier,
+) -> Result<ScreenshotResponse, Error> {
+    with_portal(|portal| async move { portal.screenshot_with_parent(identifier).await }).await
+}
+/// Takes a screenshot, giving up after `timeout`, see
+/// [`Portal::screenshot_with_timeout`].
+pub async fn screenshot_with_timeout(timeout: Duration) -> Result<ScreenshotResponse, Error> {
+    with_portal(|portal| async move {
+        portal
+            .screenshot_with_timeout(
+                &WindowIdentifier::None,
+                ScreenshotOptions::default(),
+                timeout,
+            )
+            .await
+    })
+    .await
+}
+/// Takes a screenshot on the given connection instead of opening a new one.
+pub async fn screenshot_on(
+    connection: &Connection,
+    options: ScreenshotOptions,
+) -> Result<ScreenshotResponse, Error> {
+    Portal::with_connection(connection)
+        .await?
+        .screenshot_with_options(&WindowIdentifier::None, options)
+        .await
+}
+/// Takes a screenshot with the dialog parented to the given window, as
+/// `options` say, see [`Portal::screenshot_with_options`].
+pub async fn screenshot_with_options(
+    identifier: &WindowIdentifier,
+    options: ScreenshotOptions,
+) -> Result<ScreenshotResponse, Error> {
+    with_portal(|portal| async move { portal.screenshot_with_options(identifier, options).await })
+        .await
+}
+/// Takes a screenshot through the interactive dialog, see
+/// [`Portal::screenshot_interactive`].
+pub async fn screenshot_interactive() -> Result<ScreenshotResponse, Error> {
+    with_portal(|portal| async move { portal.screenshot_interactive().await }).await
 }
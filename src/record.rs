@@ -0,0 +1,151 @@
+//! Recording the portal traffic, for bug reports about backends we can't run.
+//!
+//! With `WLSCREENACCESS_RECORD=path` set, every request sent and every
+//! Response signal received are appended to `path`, one JSON object per line:
+//!
+//! ```json
+//! {"time_ms":1714566600000,"kind":"call","interface":"org.freedesktop.portal.Screenshot","method":"Screenshot","token":"wlsa_x","endian":"l","signature":"a{sv}","body":"01000000..."}
+//! {"time_ms":1714566601250,"kind":"response","path":"/org/freedesktop/portal/desktop/request/1_42/wlsa_x","endian":"l","signature":"ua{sv}","body":"00000000..."}
+//! ```
+//!
+//! Bodies are in the D-Bus wire format, hex encoded, so a response can be
+//! fed back through [`Response::from_message`](crate::response::Response::from_message)
+//! exactly as it was received. Their byte order is the `endian` byte of the
+//! message header, `l` or `B`, as the sender may not share ours. The options
+//! of a call are recorded without the other arguments, which are the parent
+//! window and session handles.
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::Write as _,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use zbus::{zvariant::DynamicType, Message, Proxy};
+
+use crate::{trace, HandleToken};
+
+/// The variable naming the file to record to.
+const VARIABLE: &str = "WLSCREENACCESS_RECORD";
+
+/// The file recorded to, opened on first use. `None` when not recording.
+static RECORDING: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+fn recording() -> Option<&'static Mutex<File>> {
+    RECORDING
+        .get_or_init(|| {
+            let path = std::env::var_os(VARIABLE)?;
+            match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(_err) => {
+                    // Asked for explicitly, so not silently ignored.
+                    trace::warning!(
+                        path = %std::path::Path::new(&path).display(),
+                        error = %_err,
+                        "not recording"
+                    );
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// Records the request `method` of `proxy` made with `token` and `options`.
+pub(crate) fn call<O>(proxy: &Proxy<'_>, method: &str, token: &HandleToken, options: &O)
+where
+    O: Serialize + DynamicType,
+{
+    let Some(recording) = recording() else {
+        return;
+    };
+    let interface = proxy.interface().as_str();
+    // Serialized as the body of a call of its own, to get the wire format.
+    let Ok(message) = Message::method(
+        None::<&str>,
+        None::<&str>,
+        proxy.path().as_str(),
+        Some(interface),
+        method,
+        options,
+    ) else {
+        return;
+    };
+    write(
+        recording,
+        &[
+            ("kind", "call"),
+            ("interface", interface),
+            ("method", method),
+            ("token", token.as_str()),
+        ],
+        &message,
+    );
+}
+
+/// Records a received Response signal.
+pub(crate) fn response(message: &Message) {
+    let Some(recording) = recording() else {
+        return;
+    };
+    let path = message
+        .path()
+        .map(|path| path.to_string())
+        .unwrap_or_default();
+    write(recording, &[("kind", "response"), ("path", &path)], message);
+}
+
+/// Appends a line with `fields`, then the byte order, signature and body of
+/// `message`.
+fn write(recording: &Mutex<File>, fields: &[(&str, &str)], message: &Message) {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let signature = message
+        .body_signature()
+        .map(|signature| signature.to_string())
+        .unwrap_or_default();
+    let endian = char::from(message.primary_header().endian_sig() as u8).to_string();
+    let body = message.body_as_bytes().unwrap_or_default();
+    let mut line = format!("{{\"time_ms\":{}", time);
+    let header = [
+        ("endian", endian.as_str()),
+        ("signature", signature.as_str()),
+    ];
+    for (key, value) in fields.iter().chain(&header) {
+        let _ = write!(line, ",\"{}\":{}", key, json_string(value));
+    }
+    line.push_str(",\"body\":\"");
+    for byte in body {
+        let _ = write!(line, "{:02x}", byte);
+    }
+    line.push_str("\"}\n");
+    // Losing a line of a debugging aid isn't worth failing the request.
+    let mut file = recording
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let _ = file.write_all(line.as_bytes());
+}
+
+/// `value` as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c < ' ' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
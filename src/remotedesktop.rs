@@ -0,0 +1,568 @@
+//! Controlling the desktop through the RemoteDesktop portal.
+//!
+//! Remote desktop sessions are [`Session`]s like screencast ones. Sources can
+//! be selected on them too, so a single session both shares the screen and
+//! injects input.
+//!
+//! ```no_run
+//! # use wlscreenaccess::{
+//! #     remotedesktop::{DeviceType, KeyState, RemoteDesktop, SelectDevicesOptions},
+//! #     screencast::SelectSourcesOptions,
+//! #     WindowIdentifier,
+//! # };
+//! # async fn run() -> Result<(), wlscreenaccess::Error> {
+//! let portal = RemoteDesktop::new().await?;
+//! let devices = SelectDevicesOptions::new().types(DeviceType::KEYBOARD | DeviceType::POINTER);
+//! let (session, streams) = portal
+//!     .start(&WindowIdentifier::None, devices, Some(SelectSourcesOptions::new()))
+//!     .await?;
+//! session.notify_pointer_motion(10.0, 0.0).await?;
+//! // The A key, as an evdev keycode.
+//! session.notify_keyboard_keycode(30, KeyState::Pressed).await?;
+//! session.notify_keyboard_keycode(30, KeyState::Released).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zbus::{
+    dbus_proxy,
+    zvariant::{
+        DeserializeDict, ObjectPath, OwnedObjectPath, SerializeDict, Signature, Type, Value,
+    },
+    Connection,
+};
+
+use crate::{
+    record, response,
+    screencast::{
+        flags_as_u32, CreateSessionOptions, CreateSessionResults, PersistMode,
+        SelectSourcesOptions, Session, StartOptions, Stream,
+    },
+    trace, Error, HandleToken, WindowIdentifier, PORTAL_SERVICE,
+};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.RemoteDesktop",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+pub(crate) trait RemoteDesktop {
+    fn create_session(&self, options: CreateSessionOptions) -> zbus::Result<OwnedObjectPath>;
+    fn select_devices(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: SelectDevicesOptions,
+    ) -> zbus::Result<OwnedObjectPath>;
+    fn start(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        parent_window: &WindowIdentifier,
+        options: StartOptions,
+    ) -> zbus::Result<OwnedObjectPath>;
+    fn notify_pointer_motion(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        dx: f64,
+        dy: f64,
+    ) -> zbus::Result<()>;
+    fn notify_pointer_motion_absolute(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        stream: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::Result<()>;
+    fn notify_pointer_button(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        button: i32,
+        state: KeyState,
+    ) -> zbus::Result<()>;
+    fn notify_pointer_axis(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        dx: f64,
+        dy: f64,
+    ) -> zbus::Result<()>;
+    fn notify_pointer_axis_discrete(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        axis: Axis,
+        steps: i32,
+    ) -> zbus::Result<()>;
+    fn notify_keyboard_keycode(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        keycode: i32,
+        state: KeyState,
+    ) -> zbus::Result<()>;
+    fn notify_keyboard_keysym(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        keysym: i32,
+        state: KeyState,
+    ) -> zbus::Result<()>;
+    fn notify_touch_down(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::Result<()>;
+    fn notify_touch_motion(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::Result<()>;
+    fn notify_touch_up(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+        slot: u32,
+    ) -> zbus::Result<()>;
+    #[dbus_proxy(property)]
+    fn available_device_types(&self) -> zbus::Result<u32>;
+    #[dbus_proxy(property, name = "version")]
+    fn version(&self) -> zbus::Result<u32>;
+}
+
+bitflags::bitflags! {
+    /// The kinds of input devices to control.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct DeviceType: u32 {
+        /// Keyboards.
+        const KEYBOARD = 1;
+        /// Mice and touchpads.
+        const POINTER = 2;
+        /// Touchscreens.
+        const TOUCHSCREEN = 4;
+    }
+}
+
+flags_as_u32!(DeviceType);
+
+/// Whether a key or a button goes down or up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyState {
+    Released,
+    Pressed,
+}
+
+/// A scroll axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+// Both go over the bus as a plain `u`.
+macro_rules! enum_as_u32 {
+    ($enum:ty, $($variant:ident = $value:literal),+) => {
+        impl Type for $enum {
+            fn signature() -> Signature<'static> {
+                u32::signature()
+            }
+        }
+
+        impl Serialize for $enum {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_u32(match self {
+                    $(Self::$variant => $value,)+
+                })
+            }
+        }
+    };
+}
+
+enum_as_u32!(KeyState, Released = 0, Pressed = 1);
+enum_as_u32!(Axis, Vertical = 0, Horizontal = 1);
+
+/// A pointer button, by its evdev code from `linux/input-event-codes.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Button(pub i32);
+
+impl Button {
+    pub const LEFT: Self = Self(0x110);
+    pub const RIGHT: Self = Self(0x111);
+    pub const MIDDLE: Self = Self(0x112);
+    pub const SIDE: Self = Self(0x113);
+    pub const EXTRA: Self = Self(0x114);
+}
+
+/// Options of the SelectDevices request, choosing which devices the user is
+/// asked to let the application control.
+///
+/// Built fluently like [`SelectSourcesOptions`]. Unset options are left out
+/// of the request so the portal uses its own defaults.
+#[derive(SerializeDict, Type, Debug, Default)]
+#[zvariant(signature = "dict")]
+pub struct SelectDevicesOptions {
+    handle_token: HandleToken,
+    types: Option<DeviceType>,
+    persist_mode: Option<PersistMode>,
+    restore_token: Option<String>,
+}
+
+impl SelectDevicesOptions {
+    /// Creates options with a random handle token and nothing else set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the kinds of devices to control.
+    pub fn types(mut self, types: DeviceType) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    /// Sets how long the portal remembers the selection, of both devices and
+    /// sources. Left out for portals older than version 2 of the interface.
+    pub fn persist_mode(mut self, persist_mode: PersistMode) -> Self {
+        self.persist_mode = Some(persist_mode);
+        self
+    }
+
+    /// Restores the selection of an earlier session. Left out for portals
+    /// older than version 2 of the interface.
+    pub fn restore_token(mut self, restore_token: impl Into<String>) -> Self {
+        self.restore_token = Some(restore_token.into());
+        self
+    }
+
+    /// The kinds of devices to control, if set.
+    pub fn device_types(&self) -> Option<DeviceType> {
+        self.types
+    }
+}
+
+/// The results of a Start request on a remote desktop session.
+#[derive(DeserializeDict, Type, Debug)]
+#[zvariant(signature = "dict")]
+struct StartResults {
+    devices: Option<DeviceType>,
+    streams: Option<Vec<Stream>>,
+    restore_token: Option<String>,
+}
+
+impl crate::response::ResponseResults for StartResults {}
+
+/// A client for the RemoteDesktop portal.
+///
+/// Like [`ScreenCast`](crate::screencast::ScreenCast) it owns the bus
+/// connection and the proxy, and clones share them.
+#[derive(Clone, Debug)]
+pub struct RemoteDesktop {
+    connection: Connection,
+    proxy: RemoteDesktopProxy<'static>,
+}
+
+impl RemoteDesktop {
+    /// Connects to the session bus and creates the portal proxy.
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Creates the portal proxy on an existing connection.
+    pub async fn with_connection(connection: &Connection) -> Result<Self, Error> {
+        let proxy = RemoteDesktopProxy::new(connection).await?;
+        Ok(Self {
+            connection: connection.clone(),
+            proxy,
+        })
+    }
+
+    /// The connection the requests are made on.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The version of the RemoteDesktop interface the portal implements.
+    pub async fn version(&self) -> Result<u32, Error> {
+        version(&self.proxy).await
+    }
+
+    /// The kinds of devices the portal can let the application control.
+    pub async fn available_device_types(&self) -> Result<DeviceType, Error> {
+        self.proxy
+            .available_device_types()
+            .await
+            .map(DeviceType::from_bits_retain)
+            .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+    }
+
+    /// Creates a remote desktop session, the first step before selecting
+    /// devices, maybe sources, and starting it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn create_session(&self) -> Result<Session, Error> {
+        let options = CreateSessionOptions::default();
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "CreateSession", &token, &options);
+        let results: CreateSessionResults = response::wait_for_response(
+            &self.connection,
+            &token,
+            self.proxy.create_session(options),
+        )
+        .await?;
+        Session::new(
+            &self.connection,
+            results.session_handle,
+            Some(self.proxy.clone()),
+        )
+        .await
+    }
+
+    /// Runs the whole handshake: creates a session, lets the user select the
+    /// devices and, if `sources` is set, what to share too, and starts the
+    /// session, with the dialogs parented to the given window.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "remote_desktop", skip_all)
+    )]
+    pub async fn start(
+        &self,
+        identifier: &WindowIdentifier,
+        devices: SelectDevicesOptions,
+        sources: Option<SelectSourcesOptions>,
+    ) -> Result<(Session, Vec<Stream>), Error> {
+        let session = self.create_session().await?;
+        session.select_devices(devices).await?;
+        if let Some(sources) = sources {
+            session.select_sources(sources).await?;
+        }
+        let streams = session.start(identifier).await?;
+        Ok((session, streams))
+    }
+}
+
+impl Session {
+    fn remote(&self) -> Result<&RemoteDesktopProxy<'static>, Error> {
+        self.remote.as_ref().ok_or(Error::NotRemoteDesktop)
+    }
+
+    /// The devices the user allowed to control, once the session is started.
+    /// Always empty for screencast sessions.
+    pub fn devices(&self) -> DeviceType {
+        *self.devices.lock().unwrap()
+    }
+
+    /// Lets the user choose which devices to control, through the portal's
+    /// dialog.
+    ///
+    /// Fails with [`Error::NotRemoteDesktop`] on screencast sessions. The
+    /// persist mode and restore token are dropped for portals too old for
+    /// them.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn select_devices(&self, mut options: SelectDevicesOptions) -> Result<(), Error> {
+        let remote = self.remote()?;
+        if (options.persist_mode.is_some() || options.restore_token.is_some())
+            && version(remote).await? < 2
+        {
+            trace::warning!("portal too old to persist sessions");
+            options.persist_mode = None;
+            options.restore_token = None;
+        }
+        *self.selected_devices.lock().unwrap() = options.types;
+        let token = options.handle_token.clone();
+        record::call(remote.inner(), "SelectDevices", &token, &options);
+        response::wait_for_response::<response::BasicResponse>(
+            &self.connection,
+            &token,
+            remote.select_devices(&self.path, options),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn start_remote(
+        &self,
+        remote: &RemoteDesktopProxy<'static>,
+        identifier: &WindowIdentifier,
+    ) -> Result<Vec<Stream>, Error> {
+        let options = StartOptions::default();
+        let token = options.handle_token.clone();
+        record::call(remote.inner(), "Start", &token, &options);
+        let results: StartResults = response::wait_for_response(
+            &self.connection,
+            &token,
+            remote.start(&self.path, identifier, options),
+        )
+        .await?;
+        *self.devices.lock().unwrap() = results.devices.unwrap_or_default();
+        *self.restore_token.lock().unwrap() = results.restore_token;
+        Ok(results.streams.unwrap_or_default())
+    }
+
+    /// Makes a new remote desktop session like this one. Restoring goes
+    /// through the devices on these sessions.
+    pub(crate) async fn restart_remote(
+        &self,
+        identifier: &WindowIdentifier,
+        mut sources: SelectSourcesOptions,
+    ) -> Result<(Session, Vec<Stream>), Error> {
+        let mut devices = SelectDevicesOptions::new();
+        devices.types = *self.selected_devices.lock().unwrap();
+        devices.persist_mode = sources.persist_mode.take();
+        devices.restore_token = sources.restore_token.take();
+        let portal = RemoteDesktop {
+            connection: self.connection.clone(),
+            proxy: self.remote()?.clone(),
+        };
+        portal.start(identifier, devices, Some(sources)).await
+    }
+
+    /// Moves the pointer by `dx` and `dy` logical pixels.
+    pub async fn notify_pointer_motion(&self, dx: f64, dy: f64) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_pointer_motion(&self.path, HashMap::new(), dx, dy),
+        )
+        .await
+    }
+
+    /// Moves the pointer to `x` and `y` in the space of the stream with the
+    /// given PipeWire node.
+    pub async fn notify_pointer_motion_absolute(
+        &self,
+        stream: u32,
+        x: f64,
+        y: f64,
+    ) -> Result<(), Error> {
+        notify(self.remote()?.notify_pointer_motion_absolute(
+            &self.path,
+            HashMap::new(),
+            stream,
+            x,
+            y,
+        ))
+        .await
+    }
+
+    /// Presses or releases a pointer button.
+    pub async fn notify_pointer_button(
+        &self,
+        button: Button,
+        state: KeyState,
+    ) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_pointer_button(&self.path, HashMap::new(), button.0, state),
+        )
+        .await
+    }
+
+    /// Scrolls smoothly by `dx` and `dy`. `finish` tells the scroll is over,
+    /// for kinetic scrolling.
+    pub async fn notify_pointer_axis(&self, dx: f64, dy: f64, finish: bool) -> Result<(), Error> {
+        let mut options = HashMap::new();
+        if finish {
+            options.insert("finish", Value::from(true));
+        }
+        notify(
+            self.remote()?
+                .notify_pointer_axis(&self.path, options, dx, dy),
+        )
+        .await
+    }
+
+    /// Scrolls by `steps` clicks of a wheel along `axis`.
+    pub async fn notify_pointer_axis_discrete(&self, axis: Axis, steps: i32) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_pointer_axis_discrete(&self.path, HashMap::new(), axis, steps),
+        )
+        .await
+    }
+
+    /// Presses or releases the key with the given evdev keycode.
+    pub async fn notify_keyboard_keycode(
+        &self,
+        keycode: i32,
+        state: KeyState,
+    ) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_keyboard_keycode(&self.path, HashMap::new(), keycode, state),
+        )
+        .await
+    }
+
+    /// Presses or releases the key with the given X11 keysym.
+    pub async fn notify_keyboard_keysym(&self, keysym: i32, state: KeyState) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_keyboard_keysym(&self.path, HashMap::new(), keysym, state),
+        )
+        .await
+    }
+
+    /// Puts the finger `slot` down at `x` and `y` in the space of the stream
+    /// with the given PipeWire node.
+    pub async fn notify_touch_down(
+        &self,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_touch_down(&self.path, HashMap::new(), stream, slot, x, y),
+        )
+        .await
+    }
+
+    /// Moves the finger `slot` to `x` and `y` in the space of the stream with
+    /// the given PipeWire node.
+    pub async fn notify_touch_motion(
+        &self,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_touch_motion(&self.path, HashMap::new(), stream, slot, x, y),
+        )
+        .await
+    }
+
+    /// Lifts the finger `slot`.
+    pub async fn notify_touch_up(&self, slot: u32) -> Result<(), Error> {
+        notify(
+            self.remote()?
+                .notify_touch_up(&self.path, HashMap::new(), slot),
+        )
+        .await
+    }
+}
+
+/// Sends a Notify call, which has no request object to wait on.
+async fn notify(call: impl std::future::Future<Output = zbus::Result<()>>) -> Result<(), Error> {
+    call.await
+        .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+}
+
+async fn version(proxy: &RemoteDesktopProxy<'_>) -> Result<u32, Error> {
+    proxy
+        .version()
+        .await
+        .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+}
@@ -0,0 +1,350 @@
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use futures_lite::StreamExt;
+use zbus::{
+    export::futures_util::future::{abortable, AbortHandle},
+    zvariant::OwnedObjectPath,
+    CacheProperties, Connection, Message, Task,
+};
+
+use crate::{
+    connection, record,
+    response::{Response, ResponseResults},
+    runtime, trace, Error, HandleToken, PORTAL_SERVICE,
+};
+
+mod proxy {
+    use std::collections::HashMap;
+
+    use zbus::{dbus_proxy, zvariant::OwnedValue};
+
+    #[dbus_proxy(
+        interface = "org.freedesktop.portal.Request",
+        default_service = "org.freedesktop.portal.Desktop"
+    )]
+    trait Request {
+        /// Closes the portal request to which this object refers and ends all
+        /// related user interaction (dialogs, etc).
+        fn close(&self) -> zbus::Result<()>;
+
+        /// Emitted when the user interaction for a portal request is over.
+        #[dbus_proxy(signal)]
+        fn response(&self, response: u32, results: HashMap<String, OwnedValue>)
+            -> zbus::Result<()>;
+    }
+}
+
+use proxy::RequestProxy;
+
+/// The proxy of the request at `path`.
+async fn request_proxy(
+    connection: &Connection,
+    path: OwnedObjectPath,
+) -> zbus::Result<RequestProxy<'static>> {
+    RequestProxy::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+/// Where the portal creates the request objects.
+const REQUEST_PATH: &str = "/org/freedesktop/portal/desktop/request";
+
+/// Closes the request at `path`.
+async fn close(connection: &Connection, path: &OwnedObjectPath) -> zbus::Result<()> {
+    request_proxy(connection, path.clone()).await?.close().await
+}
+
+/// The proxy of the request at `path` the response is received from.
+///
+/// Its signals only come through from the portal, one pretending to answer
+/// from elsewhere is ignored.
+async fn response_proxy(
+    connection: &Connection,
+    path: OwnedObjectPath,
+) -> zbus::Result<RequestProxy<'static>> {
+    let builder = RequestProxy::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No);
+    connection::bind_to_portal(connection, builder)
+        .await?
+        .build()
+        .await
+}
+
+/// Subscribes to the Response signal of the request at `path`.
+///
+/// The first response is awaited on the connection's executor, so the
+/// subscription is read from even while nobody awaits the request: zbus stops
+/// receiving on the whole connection once a subscriber falls behind. The
+/// subscription, and its match rule, go away with the task, once the response
+/// arrived or the task is dropped.
+async fn subscribe(
+    connection: &Connection,
+    path: OwnedObjectPath,
+) -> zbus::Result<Task<Option<Arc<Message>>>> {
+    let proxy = response_proxy(connection, path).await?;
+    let mut responses = proxy.receive_response().await?;
+    Ok(connection.executor().spawn(async move {
+        let response = responses.next().await?;
+        Some(Arc::clone(response.as_ref()))
+    }))
+}
+
+/// The path the portal creates the request object at, derived from our unique
+/// bus name and the handle token. `None` on peer-to-peer connections.
+fn expected_path(connection: &Connection, token: &HandleToken) -> Option<OwnedObjectPath> {
+    let sender = connection
+        .unique_name()?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    OwnedObjectPath::try_from(format!("{}/{}/{}", REQUEST_PATH, sender, token.as_str())).ok()
+}
+
+/// Sends a request made with `token`, listening for its response beforehand.
+///
+/// The response can be emitted as soon as the method call returns, so the
+/// path predicted from the token is subscribed to first. Portals too old to
+/// use that path, and peer-to-peer connections, are only subscribed to once
+/// the call returned the actual one.
+pub(crate) async fn send(
+    connection: &Connection,
+    token: &HandleToken,
+    call: impl Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> Result<Request, Error> {
+    let expected = expected_path(connection, token);
+    trace::debug!(
+        token = token.as_str(),
+        path = expected.as_ref().map(|path| path.as_str()),
+        "request path predicted"
+    );
+    let subscribed = match &expected {
+        Some(path) => Some(subscribe(connection, path.clone()).await?),
+        None => None,
+    };
+    trace::debug!(token = token.as_str(), "signal subscribed");
+    let path = call
+        .await
+        .map_err(|err| Error::from_call(err, PORTAL_SERVICE))?;
+    let predicted = expected.as_ref() == Some(&path);
+    trace::debug!(
+        token = token.as_str(),
+        path = path.as_str(),
+        predicted,
+        "request sent"
+    );
+    let response = match subscribed {
+        Some(response) if predicted => response,
+        _ => subscribe(connection, path.clone()).await?,
+    };
+    Ok(Request {
+        guard: CloseGuard::new(connection, path),
+        expected,
+        response: Answer::Pending(response),
+    })
+}
+
+/// A request made with `token` which succeeded with `results` right away,
+/// without sending anything.
+#[cfg(feature = "fake")]
+pub(crate) fn answered<R>(
+    connection: &Connection,
+    token: &HandleToken,
+    results: &R,
+) -> Result<Request, Error>
+where
+    R: serde::Serialize + zbus::zvariant::Type,
+{
+    let path = OwnedObjectPath::try_from(format!("{}/fake/{}", REQUEST_PATH, token.as_str()))
+        .map_err(zbus::Error::from)?;
+    let message =
+        zbus::MessageBuilder::signal(&path, "org.freedesktop.portal.Request", "Response")?
+            .build(&(0u32, results))?;
+    let guard = CloseGuard::new(connection, path);
+    // Nothing to close.
+    guard.disarm();
+    Ok(Request {
+        guard,
+        expected: None,
+        response: Answer::Faked(Arc::new(message)),
+    })
+}
+
+/// A request which was sent, with the subscription to its response.
+///
+/// The subscription is made before sending, so a response emitted right away
+/// isn't missed.
+#[derive(Debug)]
+pub struct Request {
+    guard: CloseGuard,
+    expected: Option<OwnedObjectPath>,
+    response: Answer,
+}
+
+/// Where the response of a [`Request`] comes from.
+#[derive(Debug)]
+enum Answer {
+    /// The subscription awaiting the portal's, `None` once the connection is
+    /// gone.
+    Pending(Task<Option<Arc<Message>>>),
+    /// Made up without asking the portal, see [`fake`](crate::fake). It's
+    /// there already, closing the request doesn't take it back.
+    #[cfg(feature = "fake")]
+    Faked(Arc<Message>),
+}
+
+impl Request {
+    /// The path of the request object, as returned by the portal. The response
+    /// is awaited there.
+    pub fn path(&self) -> &OwnedObjectPath {
+        &self.guard.path
+    }
+
+    /// The path predicted from the handle token, `None` on peer-to-peer
+    /// connections.
+    pub fn expected_path(&self) -> Option<&OwnedObjectPath> {
+        self.expected.as_ref()
+    }
+
+    /// Whether the portal used the predicted path. Old portals make up their
+    /// own.
+    pub fn is_expected_path(&self) -> bool {
+        self.expected.as_ref() == Some(self.path())
+    }
+
+    /// Closes the request, dismissing its dialog. The portal sends no
+    /// response then, [`receive_response`](Self::receive_response) returns
+    /// [`Error::Cancelled`] instead.
+    pub async fn close(&self) -> Result<(), Error> {
+        if self.guard.done.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        Ok(close(&self.guard.connection, &self.guard.path).await?)
+    }
+
+    /// Waits for the response, closing the request if dropped before.
+    ///
+    /// Only the first Response signal counts, the subscription is dropped
+    /// once it arrived so duplicates some backends emit are ignored.
+    pub async fn receive_response<T>(self) -> Result<T, Error>
+    where
+        T: ResponseResults,
+    {
+        let Self {
+            guard, response, ..
+        } = self;
+        let message = match response {
+            // Closed by the caller, nothing will answer.
+            Answer::Pending(_) if guard.done.load(Ordering::SeqCst) => {
+                return Err(Error::Cancelled)
+            }
+            // The stream only ends when the connection is gone.
+            Answer::Pending(response) => response.await,
+            #[cfg(feature = "fake")]
+            Answer::Faked(message) => Some(message),
+        };
+        guard.disarm();
+        let message = message.ok_or(Error::PortalGone)?;
+        record::response(&message);
+        let response = Response::<T>::from_message(&message);
+        #[cfg(feature = "tracing")]
+        trace::received(&guard.path, &response);
+        Ok(response?.into_result()?)
+    }
+}
+
+/// Closes the request if dropped before its response arrived, so aborting the
+/// task awaiting a request doesn't leave its dialog on screen.
+#[derive(Debug)]
+struct CloseGuard {
+    connection: Connection,
+    path: OwnedObjectPath,
+    done: Arc<AtomicBool>,
+}
+
+impl CloseGuard {
+    fn new(connection: &Connection, path: OwnedObjectPath) -> Self {
+        Self {
+            connection: connection.clone(),
+            path,
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Marks the request as finished, it won't be closed on drop.
+    fn disarm(&self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for CloseGuard {
+    fn drop(&mut self) {
+        if self.done.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let (connection, path) = (self.connection.clone(), self.path.clone());
+        runtime::detach(async move {
+            let _ = close(&connection, &path).await;
+        });
+    }
+}
+
+/// A handle on an in-flight portal request, used to abort it.
+///
+/// It's returned next to the future resolving to the request's response, see
+/// [`Portal::start_screenshot`](crate::Portal::start_screenshot).
+#[derive(Debug, Clone)]
+pub struct RequestHandle {
+    connection: Connection,
+    path: OwnedObjectPath,
+    abort: AbortHandle,
+    done: Arc<AtomicBool>,
+}
+
+impl RequestHandle {
+    /// The object path of the `org.freedesktop.portal.Request`.
+    pub fn path(&self) -> &OwnedObjectPath {
+        &self.path
+    }
+
+    /// Closes the request, dismissing its dialog.
+    ///
+    /// The pending response future resolves to a cancelled error. Once the
+    /// response has arrived this does nothing.
+    pub async fn close(&self) -> Result<(), Error> {
+        if self.done.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.abort.abort();
+        Ok(close(&self.connection, &self.path).await?)
+    }
+}
+
+/// Starts waiting for the response of a pending request.
+pub(crate) fn track<T>(request: Request) -> (RequestHandle, impl Future<Output = Result<T, Error>>)
+where
+    T: ResponseResults,
+{
+    let connection = request.guard.connection.clone();
+    let path = request.guard.path.clone();
+    let done = request.guard.done.clone();
+    let (response, abort) = abortable(request.receive_response::<T>());
+    let handle = RequestHandle {
+        connection,
+        path,
+        abort,
+        done,
+    };
+    let response = async move {
+        match response.await {
+            Ok(response) => response,
+            Err(_) => Err(Error::Cancelled),
+        }
+    };
+    (handle, response)
+}
@@ -5,25 +5,47 @@ use serde::{
 };
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::future::Future;
 use std::marker::PhantomData;
-use zbus::zvariant::{OwnedValue, Signature, Type};
-#[derive(Debug, Copy, PartialEq, Eq, Hash, Clone)]
+use std::sync::Arc;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Signature, Type, Value};
+use zbus::{Connection, Message};
+
+use crate::{request, Error, HandleToken};
+#[derive(Debug, PartialEq, Clone)]
 /// An error returned a portal request caused by either the user cancelling the
 /// request or something else.
 pub enum ResponseError {
     /// The user canceled the request.
     Cancelled,
-    /// Something else happened.
-    Other,
+    /// Something else happened. Holds whatever details the portal sent along,
+    /// often an `error` or `message` string.
+    Other(HashMap<String, OwnedValue>),
+}
+
+impl ResponseError {
+    /// The explanation the portal gave for a failure, if any.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Cancelled => None,
+            Self::Other(details) => ["error", "message"].iter().find_map(|key| {
+                match details.get(*key).map(|value| &**value) {
+                    Some(Value::Str(message)) => Some(message.as_str()),
+                    _ => None,
+                }
+            }),
+        }
+    }
 }
 
 impl std::error::Error for ResponseError {}
 
 impl std::fmt::Display for ResponseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Cancelled => f.write_str("Cancelled"),
-            Self::Other => f.write_str("Other"),
+        match (self, self.message()) {
+            (Self::Cancelled, _) => f.write_str("Cancelled"),
+            (Self::Other(_), Some(message)) => write!(f, "Other: {}", message),
+            (Self::Other(_), None) => f.write_str("Other"),
         }
     }
 }
@@ -38,19 +60,38 @@ enum ResponseType {
     Other = 2,
 }
 
+impl ResponseType {
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Success),
+            1 => Some(Self::Cancelled),
+            2 => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
 #[doc(hidden)]
-impl From<ResponseError> for ResponseType {
-    fn from(err: ResponseError) -> Self {
+impl From<&ResponseError> for ResponseType {
+    fn from(err: &ResponseError) -> Self {
         match err {
-            ResponseError::Other => Self::Other,
+            ResponseError::Other(_) => Self::Other,
             ResponseError::Cancelled => Self::Cancelled,
         }
     }
 }
 
-
-#[derive(Debug)]
-pub(crate) enum Response<T>
+/// The outcome of a portal request, as sent in its Response signal.
+///
+/// ```
+/// # use wlscreenaccess::{response::Response, ResponseError};
+/// let response: Response<u32> = Response::Err(ResponseError::Cancelled);
+/// assert!(response.is_cancelled());
+/// assert_eq!(response.ok(), None);
+/// assert_eq!(Response::Ok(7u32).into_result(), Ok(7));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response<T>
 where
     T: for<'de> Deserialize<'de> + Type,
 {
@@ -60,6 +101,29 @@ where
     Err(ResponseError),
 }
 
+impl<T> Response<T>
+where
+    T: for<'de> Deserialize<'de> + Type,
+{
+    /// The results, or why the request failed.
+    pub fn into_result(self) -> Result<T, ResponseError> {
+        match self {
+            Self::Ok(response) => Ok(response),
+            Self::Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the user cancelled the request.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Err(ResponseError::Cancelled))
+    }
+
+    /// The results, discarding why the request failed.
+    pub fn ok(self) -> Option<T> {
+        self.into_result().ok()
+    }
+}
+
 impl<T> Type for Response<T>
 where
     T: for<'de> Deserialize<'de> + Type,
@@ -83,7 +147,7 @@ where
         where
             T: Deserialize<'de>,
         {
-            type Value = (ResponseType, Option<T>);
+            type Value = (ResponseType, Option<T>, HashMap<String, OwnedValue>);
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                 write!(
@@ -96,23 +160,34 @@ where
             where
                 A: de::SeqAccess<'de>,
             {
-                let type_: ResponseType = seq.next_element()?.ok_or_else(|| A::Error::custom(
+                let code: u32 = seq.next_element()?.ok_or_else(|| A::Error::custom(
                     "Failed to deserialize the response. Expected a numeric (u) value as the first item of the returned tuple",
                 ))?;
+                let type_ = match ResponseType::from_code(code) {
+                    Some(type_) => type_,
+                    None => {
+                        // Still read the results, so the tuple is consumed.
+                        let _ = seq.next_element::<HashMap<String, OwnedValue>>()?;
+                        return Err(A::Error::custom(format!("Unknown response code {}", code)));
+                    }
+                };
                 if type_ == ResponseType::Success {
                     let data: T = seq.next_element()?.ok_or_else(|| A::Error::custom(
                         "Failed to deserialize the response. Expected a vardict (a{sv}) with the returned results",
                     ))?;
-                    Ok((type_, Some(data)))
+                    Ok((type_, Some(data), HashMap::new()))
                 } else {
-                    Ok((type_, None))
+                    // Failures may still explain themselves in the results.
+                    let details = seq.next_element()?.unwrap_or_default();
+                    Ok((type_, None, details))
                 }
             }
         }
 
         let visitor = ResponseVisitor::<T>(PhantomData);
-        let response: (ResponseType, Option<T>) = deserializer.deserialize_tuple(2, visitor)?;
-        Ok(response.into())
+        let response: (ResponseType, Option<T>, HashMap<String, OwnedValue>) =
+            deserializer.deserialize_tuple(2, visitor)?;
+        response.try_into().map_err(D::Error::custom)
     }
 }
 
@@ -127,8 +202,11 @@ where
         let mut map = serializer.serialize_tuple(2)?;
         match self {
             Self::Err(err) => {
-                map.serialize_element(&ResponseType::from(*err))?;
-                map.serialize_element(&BasicResponse::default())?;
+                map.serialize_element(&ResponseType::from(err))?;
+                match err {
+                    ResponseError::Cancelled => map.serialize_element(&BasicResponse::default())?,
+                    ResponseError::Other(details) => map.serialize_element(details)?,
+                }
             }
             Self::Ok(response) => {
                 map.serialize_element(&ResponseType::Success)?;
@@ -140,25 +218,249 @@ where
 }
 
 #[doc(hidden)]
-impl<T> From<(ResponseType, Option<T>)> for Response<T>
+impl<T> TryFrom<(ResponseType, Option<T>, HashMap<String, OwnedValue>)> for Response<T>
 where
     T: for<'de> Deserialize<'de> + Type,
 {
-    fn from(f: (ResponseType, Option<T>)) -> Self {
+    type Error = &'static str;
+
+    fn try_from(
+        f: (ResponseType, Option<T>, HashMap<String, OwnedValue>),
+    ) -> Result<Self, Self::Error> {
         match f.0 {
             ResponseType::Success => {
-                Response::Ok(f.1.expect("Expected a valid response, found nothing."))
+                f.1.map(Response::Ok)
+                    .ok_or("The portal reported success without sending the results")
+            }
+            ResponseType::Cancelled => Ok(Response::Err(ResponseError::Cancelled)),
+            ResponseType::Other => Ok(Response::Err(ResponseError::Other(f.2))),
+        }
+    }
+}
+
+impl<T> Response<T>
+where
+    T: ResponseResults,
+{
+    /// Parses the body of a Response signal, for wrapping portal requests by
+    /// hand.
+    ///
+    /// Bodies not of the `ua{sv}` signature are rejected before being
+    /// deserialized, as [`Error::InvalidResponse`] naming their signature.
+    /// Successes can only leave out the results if [`ResponseResults::empty`]
+    /// allows it, otherwise they're [`Error::EmptyResults`] rather than a
+    /// panic.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use wlscreenaccess::{response::Response, ScreenshotResponse};
+    /// # use zbus::zvariant::Value;
+    /// let mut results = HashMap::new();
+    /// results.insert("uri", Value::from("file:///tmp/Screenshot.png"));
+    /// let message = zbus::Message::signal(
+    ///     None::<&str>,
+    ///     None::<&str>,
+    ///     "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+    ///     "org.freedesktop.portal.Request",
+    ///     "Response",
+    ///     &(0u32, results),
+    /// )?;
+    /// let response = Response::<ScreenshotResponse>::from_message(&message)?;
+    /// assert_eq!(response.ok().unwrap().url().path(), "/tmp/Screenshot.png");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_message(message: &Message) -> Result<Self, Error> {
+        let invalid = |source| Error::InvalidResponse {
+            signature: message
+                .body_signature()
+                .map(|signature| signature.to_string())
+                .unwrap_or_default(),
+            message: Arc::new(message.clone()),
+            source,
+        };
+        // Header signatures leave out the parentheses of the body's struct.
+        const EXPECTED: &str = "ua{sv}";
+        match message.body_signature() {
+            Ok(signature) if signature == EXPECTED => (),
+            // Some portals leave out the results, which is fine for failures
+            // and for requests without required results.
+            Ok(signature) if signature == "u" => {
+                let code = message.body::<u32>().map_err(invalid)?;
+                let type_ =
+                    ResponseType::from_code(code).ok_or(Error::UnknownResponseCode(code))?;
+                return Self::try_from((type_, T::empty(), HashMap::new()))
+                    .map_err(|_| Error::EmptyResults);
             }
-            ResponseType::Cancelled => Response::Err(ResponseError::Cancelled),
-            ResponseType::Other => Response::Err(ResponseError::Other),
+            Ok(signature) => {
+                return Err(invalid(zbus::Error::Variant(
+                    zbus::zvariant::Error::SignatureMismatch(
+                        signature.to_owned(),
+                        EXPECTED.to_owned(),
+                    ),
+                )))
+            }
+            Err(source) => return Err(invalid(source)),
         }
+        message.body().map_err(|source| {
+            match message.body::<(u32, HashMap<String, OwnedValue>)>() {
+                // Explain a successful response with unusable results precisely.
+                Ok((0, results)) => {
+                    if let Err(err) = T::check(&results) {
+                        return err;
+                    }
+                }
+                Ok((code, _)) if ResponseType::from_code(code).is_none() => {
+                    return Error::UnknownResponseCode(code)
+                }
+                _ => (),
+            }
+            invalid(source)
+        })
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Type)]
+/// Sends a request made with `token` and waits for its response, for crates
+/// wrapping other portals.
+///
+/// `call` is the method call creating the request, returning its path, and
+/// `token` the handle token passed in its options. It takes the call not yet
+/// awaited, rather than the path it returns, because the portal may emit the
+/// Response signal as soon as the call returns: subscribing only then would
+/// race with it and could miss the response. Instead the path the portal
+/// derives from `token` is subscribed to first, and the call sent afterwards.
+/// Only a response coming from the portal is taken. The request is closed if
+/// the future is dropped before the response came, e.g. on a timeout.
+///
+/// ```no_run
+/// # use std::collections::HashMap;
+/// # use wlscreenaccess::{response::{wait_for_response, BasicResponse}, HandleToken};
+/// # use zbus::zvariant::{OwnedObjectPath, Value};
+/// # futures_lite::future::block_on(async {
+/// let connection = zbus::Connection::session().await?;
+/// let account = zbus::Proxy::new(
+///     &connection,
+///     "org.freedesktop.portal.Desktop",
+///     "/org/freedesktop/portal/desktop",
+///     "org.freedesktop.portal.Account",
+/// )
+/// .await?;
+/// let token = HandleToken::unique();
+/// let body = ("", HashMap::from([("handle_token", Value::from(token.as_str()))]));
+/// let call = account.call::<_, _, OwnedObjectPath>("GetUserInformation", &body);
+/// let user: BasicResponse = wait_for_response(&connection, &token, call).await?;
+/// # Ok::<(), wlscreenaccess::Error>(())
+/// # });
+/// ```
+pub async fn wait_for_response<T>(
+    connection: &Connection,
+    token: &HandleToken,
+    call: impl Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> Result<T, Error>
+where
+    T: ResponseResults,
+{
+    request::send(connection, token, call)
+        .await?
+        .receive_response()
+        .await
+}
+
+/// The results of a successful request, deserialized from the vardict of its
+/// Response signal.
+///
+/// Portals add fields to their results over time, so types implementing this
+/// must ignore the ones they don't know: never derive them with
+/// `deny_unknown_fields`.
+pub trait ResponseResults: for<'de> Deserialize<'de> + Type {
+    /// Looks for what's wrong with `results` once deserializing them failed,
+    /// e.g. with [`require_field`].
+    fn check(results: &HashMap<String, OwnedValue>) -> Result<(), Error> {
+        let _ = results;
+        Ok(())
+    }
+
+    /// The results of a success reported without any, for requests whose
+    /// results are all optional. `None` makes such a response an error.
+    fn empty() -> Option<Self> {
+        None
+    }
+}
+
+/// Fails if `results` has no `field`, or if its value's signature isn't one of
+/// `expected`.
+pub fn require_field(
+    results: &HashMap<String, OwnedValue>,
+    field: &'static str,
+    expected: &[&str],
+) -> Result<(), Error> {
+    let value = results.get(field).ok_or_else(|| {
+        let mut present: Vec<String> = results.keys().cloned().collect();
+        present.sort();
+        Error::MissingField { field, present }
+    })?;
+    let found = value.value_signature();
+    if expected.iter().any(|expected| found == *expected) {
+        Ok(())
+    } else {
+        Err(Error::InvalidField {
+            field,
+            expected: expected.join(" or "),
+            found: found.to_string(),
+        })
+    }
+}
+
+impl ResponseResults for BasicResponse {
+    fn empty() -> Option<Self> {
+        Some(Self::default())
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, Type)]
 /// The most basic response. Used when only the status of the request is what we
 /// receive as a response.
-pub(crate) struct BasicResponse(HashMap<String, OwnedValue>);
+///
+/// Await data-less requests with `wait_for_response::<BasicResponse>`. Any
+/// results sent anyway are kept.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use wlscreenaccess::response::BasicResponse;
+/// # use zbus::zvariant::{OwnedValue, Value};
+/// let mut results = HashMap::new();
+/// results.insert("restore_token".to_owned(), OwnedValue::from(Value::from("abc")));
+/// let response = BasicResponse::from(results);
+///
+/// let message = zbus::Message::signal(
+///     None::<&str>,
+///     None::<&str>,
+///     "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+///     "org.freedesktop.portal.Request",
+///     "Response",
+///     &response,
+/// )?;
+/// assert_eq!(message.body::<BasicResponse>()?, response);
+/// # Ok::<(), zbus::Error>(())
+/// ```
+pub struct BasicResponse(HashMap<String, OwnedValue>);
+
+impl BasicResponse {
+    /// The results the portal sent.
+    pub fn results(&self) -> &HashMap<String, OwnedValue> {
+        &self.0
+    }
+
+    /// Takes the results the portal sent.
+    pub fn into_results(self) -> HashMap<String, OwnedValue> {
+        self.0
+    }
+}
+
+impl From<HashMap<String, OwnedValue>> for BasicResponse {
+    fn from(results: HashMap<String, OwnedValue>) -> Self {
+        Self(results)
+    }
+}
 
 impl Debug for BasicResponse {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
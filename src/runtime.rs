@@ -0,0 +1,94 @@
+//! What the crate needs from the async runtime zbus runs on, selected by the
+//! `async-io` or `tokio` feature.
+
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{mpsc, OnceLock};
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[cfg(all(feature = "async-io", feature = "tokio"))]
+compile_error!(
+    "The `async-io` and `tokio` features are exclusive, disable the default features to use `tokio`"
+);
+
+#[cfg(not(any(feature = "async-io", feature = "tokio")))]
+compile_error!("Either the `async-io` or the `tokio` feature is required");
+
+/// Waits for `duration` on the selected runtime's timers.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(feature = "async-io")]
+    async_io::Timer::after(duration).await;
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+}
+
+/// Reads the whole file at `path`, off the executor's threads.
+pub(crate) async fn read(path: &Path) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "async-io")]
+    return async_fs::read(path).await;
+    #[cfg(feature = "tokio")]
+    return tokio::fs::read(path).await;
+}
+
+/// A file opened for reading asynchronously.
+#[cfg(feature = "async-io")]
+pub(crate) type File = async_fs::File;
+
+/// A file opened for reading asynchronously.
+///
+/// tokio's files implement its own `AsyncRead`, so this adapts them to the
+/// one of `futures`.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub(crate) struct File(tokio::fs::File);
+
+#[cfg(feature = "tokio")]
+impl futures_lite::AsyncRead for File {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        let read = tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, &mut buf);
+        read.map_ok(|()| buf.filled().len())
+    }
+}
+
+/// Opens the file at `path` for reading.
+pub(crate) async fn open(path: &Path) -> io::Result<File> {
+    #[cfg(feature = "async-io")]
+    return async_fs::File::open(path).await;
+    #[cfg(feature = "tokio")]
+    return tokio::fs::File::open(path).await.map(File);
+}
+
+/// A future run by [`detach`].
+type Detached = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs `future` in the background, for the cleanups of `Drop` impls which
+/// can't await.
+///
+/// zbus gives no way to detach a task on the connection's executor, and
+/// dropping its handle would cancel it, so one thread started on first use
+/// runs them all, one after the other.
+pub(crate) fn detach(future: impl Future<Output = ()> + Send + 'static) {
+    static DETACHED: OnceLock<mpsc::Sender<Detached>> = OnceLock::new();
+    let sender = DETACHED.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Detached>();
+        // Without the thread the futures are dropped, as they'd be on exit.
+        let _ = std::thread::Builder::new()
+            .name("wlscreenaccess-detached".to_owned())
+            .spawn(move || {
+                for future in receiver {
+                    futures_lite::future::block_on(future);
+                }
+            });
+        sender
+    });
+    let _ = sender.send(Box::pin(future));
+}
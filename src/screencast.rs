@@ -0,0 +1,1003 @@
+//! Sharing screens and windows through the ScreenCast portal.
+//!
+//! ```no_run
+//! # use wlscreenaccess::{screencast::{ScreenCast, SelectSourcesOptions}, WindowIdentifier};
+//! # async fn run() -> Result<(), wlscreenaccess::Error> {
+//! let portal = ScreenCast::new().await?;
+//! let (session, streams) = portal
+//!     .start(&WindowIdentifier::None, SelectSourcesOptions::new().multiple(true))
+//!     .await?;
+//! for stream in &streams {
+//!     println!("{}: node {}", session.path().as_str(), stream.pipewire_node_id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod frame;
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::os::fd::{FromRawFd, IntoRawFd};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zbus::{
+    dbus_proxy,
+    export::futures_util::StreamExt,
+    zvariant::{
+        DeserializeDict, ObjectPath, OwnedFd, OwnedObjectPath, OwnedValue, SerializeDict,
+        Signature, Structure, Type, Value,
+    },
+    CacheProperties, Connection,
+};
+
+use crate::{
+    connection, record,
+    remotedesktop::{DeviceType, RemoteDesktopProxy},
+    response, runtime, trace, Error, HandleToken, WindowIdentifier, PORTAL_SERVICE,
+};
+
+pub use frame::{Frame, PixelFormat};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.ScreenCast",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait ScreenCast {
+    fn create_session(&self, options: CreateSessionOptions) -> zbus::Result<OwnedObjectPath>;
+    fn select_sources(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: SelectSourcesOptions,
+    ) -> zbus::Result<OwnedObjectPath>;
+    fn start(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        parent_window: &WindowIdentifier,
+        options: StartOptions,
+    ) -> zbus::Result<OwnedObjectPath>;
+    #[dbus_proxy(name = "OpenPipeWireRemote")]
+    fn open_pipewire_remote(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedFd>;
+    #[dbus_proxy(property)]
+    fn available_source_types(&self) -> zbus::Result<u32>;
+    #[dbus_proxy(property)]
+    fn available_cursor_modes(&self) -> zbus::Result<u32>;
+    #[dbus_proxy(property, name = "version")]
+    fn version(&self) -> zbus::Result<u32>;
+}
+
+bitflags::bitflags! {
+    /// The kinds of sources to share.
+    ///
+    /// ```
+    /// # use wlscreenaccess::screencast::SourceType;
+    /// let types = SourceType::MONITOR | SourceType::WINDOW;
+    /// assert_eq!(types.bits(), 3);
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct SourceType: u32 {
+        /// Whole monitors.
+        const MONITOR = 1;
+        /// Single windows.
+        const WINDOW = 2;
+        /// Virtual monitors, extending the desktop.
+        const VIRTUAL = 4;
+    }
+}
+
+bitflags::bitflags! {
+    /// How the cursor appears in the streams.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct CursorMode: u32 {
+        /// Not shown.
+        const HIDDEN = 1;
+        /// Drawn into the frames.
+        const EMBEDDED = 2;
+        /// Sent as metadata of the frames.
+        const METADATA = 4;
+    }
+}
+
+// Both go over the bus as their bits, a plain `u`, keeping bits this crate
+// doesn't know about.
+macro_rules! flags_as_u32 {
+    ($flags:ty) => {
+        impl Type for $flags {
+            fn signature() -> Signature<'static> {
+                u32::signature()
+            }
+        }
+
+        impl Serialize for $flags {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_u32(self.bits())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $flags {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                u32::deserialize(deserializer).map(Self::from_bits_retain)
+            }
+        }
+    };
+}
+
+pub(crate) use flags_as_u32;
+
+flags_as_u32!(SourceType);
+flags_as_u32!(CursorMode);
+
+/// How long the portal remembers what the user chose to share, through the
+/// restore token of [`Session::restore_token`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PersistMode {
+    /// Don't remember anything.
+    #[default]
+    None,
+    /// Remember while the application is running.
+    Transient,
+    /// Remember until the user revokes it.
+    Persistent,
+}
+
+impl Type for PersistMode {
+    fn signature() -> Signature<'static> {
+        u32::signature()
+    }
+}
+
+impl Serialize for PersistMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(match self {
+            Self::None => 0,
+            Self::Transient => 1,
+            Self::Persistent => 2,
+        })
+    }
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Session",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Session {
+    fn close(&self) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn closed(&self, details: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
+
+// The RemoteDesktop portal creates and starts its sessions with the same
+// options and results.
+#[derive(SerializeDict, Type, Debug, Default)]
+#[zvariant(signature = "dict")]
+pub(crate) struct CreateSessionOptions {
+    pub(crate) handle_token: HandleToken,
+    session_handle_token: HandleToken,
+}
+
+/// The results of a CreateSession request.
+#[derive(DeserializeDict, Type, Debug)]
+#[zvariant(signature = "dict")]
+pub(crate) struct CreateSessionResults {
+    pub(crate) session_handle: String,
+}
+
+impl response::ResponseResults for CreateSessionResults {
+    fn check(results: &HashMap<String, OwnedValue>) -> Result<(), Error> {
+        response::require_field(results, "session_handle", &["s", "o"])
+    }
+}
+
+/// Options of the SelectSources request, choosing what the user is offered to
+/// share.
+///
+/// Built fluently like [`ScreenshotOptions`](crate::ScreenshotOptions). Unset
+/// options are left out of the request so the portal uses its own defaults.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use wlscreenaccess::screencast::{CursorMode, SelectSourcesOptions, SourceType};
+/// # use zbus::zvariant::OwnedValue;
+/// let options = SelectSourcesOptions::new()
+///     .types(SourceType::MONITOR | SourceType::WINDOW)
+///     .cursor_mode(CursorMode::EMBEDDED)
+///     .multiple(true);
+///
+/// let message = zbus::Message::method(
+///     None::<&str>,
+///     None::<&str>,
+///     "/org/freedesktop/portal/desktop",
+///     Some("org.freedesktop.portal.ScreenCast"),
+///     "SelectSources",
+///     &options,
+/// )?;
+/// let dict: HashMap<String, OwnedValue> = message.body()?;
+/// assert_eq!(dict["types"].downcast_ref::<u32>(), Some(&3));
+/// assert_eq!(dict["cursor_mode"].downcast_ref::<u32>(), Some(&2));
+/// assert_eq!(dict["multiple"].downcast_ref::<bool>(), Some(&true));
+/// # Ok::<(), zbus::Error>(())
+/// ```
+#[derive(SerializeDict, Type, Debug, Default)]
+#[zvariant(signature = "dict")]
+pub struct SelectSourcesOptions {
+    handle_token: HandleToken,
+    types: Option<SourceType>,
+    cursor_mode: Option<CursorMode>,
+    multiple: Option<bool>,
+    pub(crate) persist_mode: Option<PersistMode>,
+    pub(crate) restore_token: Option<String>,
+}
+
+impl SelectSourcesOptions {
+    /// Creates options with a random handle token and nothing else set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the kinds of sources offered.
+    pub fn types(mut self, types: SourceType) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    /// Sets how the cursor appears in the streams. Needs version 2 of the
+    /// interface.
+    pub fn cursor_mode(mut self, cursor_mode: CursorMode) -> Self {
+        self.cursor_mode = Some(cursor_mode);
+        self
+    }
+
+    /// Sets whether the user may select several sources.
+    pub fn multiple(mut self, multiple: bool) -> Self {
+        self.multiple = Some(multiple);
+        self
+    }
+
+    /// Sets how long the portal remembers the selection. Left out for portals
+    /// older than version 4 of the interface.
+    pub fn persist_mode(mut self, persist_mode: PersistMode) -> Self {
+        self.persist_mode = Some(persist_mode);
+        self
+    }
+
+    /// Restores the selection of an earlier session, skipping the dialog when
+    /// the portal still knows the token. Left out for portals older than
+    /// version 4 of the interface.
+    pub fn restore_token(mut self, restore_token: impl Into<String>) -> Self {
+        self.restore_token = Some(restore_token.into());
+        self
+    }
+
+    /// The kinds of sources offered, if set.
+    pub fn source_types(&self) -> Option<SourceType> {
+        self.types
+    }
+
+    /// How the cursor appears, if set.
+    pub fn cursor(&self) -> Option<CursorMode> {
+        self.cursor_mode
+    }
+
+    /// Whether several sources may be selected, if set.
+    pub fn is_multiple(&self) -> Option<bool> {
+        self.multiple
+    }
+}
+
+/// Why a [`SourceSelection`] can't be built.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidSelection {
+    /// No kind of source was allowed.
+    #[error("No source type is selected")]
+    NoSourceTypes,
+    /// Zero streams were expected.
+    #[error("At least one stream must be expected")]
+    NoStreams,
+    /// Several streams were expected from a single source.
+    #[error("{0} streams are expected but only one source may be selected")]
+    SingleSource(usize),
+}
+
+/// What the user is asked to share, checked to make sense before anything is
+/// sent to the portal.
+///
+/// With a restore token the portal may skip its dialog and share the sources
+/// chosen earlier. Which those are can be told from [`Stream::mapping_id`]
+/// and [`Stream::id`].
+///
+/// ```
+/// # use wlscreenaccess::screencast::{InvalidSelection, SourceSelection, SourceType};
+/// let selection = SourceSelection::monitor().streams(2).build();
+/// assert_eq!(selection, Err(InvalidSelection::SingleSource(2)));
+///
+/// let selection = SourceSelection::monitor()
+///     .multiple(true)
+///     .streams(2)
+///     .restore_token(Some("4f0ab2a7".to_owned()))
+///     .build()?;
+/// assert_eq!(selection.types(), SourceType::MONITOR);
+/// assert_eq!(selection.expected_streams(), 2);
+/// # Ok::<(), InvalidSelection>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSelection {
+    types: SourceType,
+    multiple: bool,
+    streams: usize,
+    cursor_mode: Option<CursorMode>,
+    persist_mode: PersistMode,
+    restore_token: Option<String>,
+}
+
+impl SourceSelection {
+    /// Starts a selection of one of the `types` of sources.
+    pub fn builder(types: SourceType) -> SourceSelectionBuilder {
+        SourceSelectionBuilder(Self {
+            types,
+            multiple: false,
+            streams: 1,
+            cursor_mode: None,
+            persist_mode: PersistMode::None,
+            restore_token: None,
+        })
+    }
+
+    /// Starts a selection of a monitor.
+    pub fn monitor() -> SourceSelectionBuilder {
+        Self::builder(SourceType::MONITOR)
+    }
+
+    /// Starts a selection of a window.
+    pub fn window() -> SourceSelectionBuilder {
+        Self::builder(SourceType::WINDOW)
+    }
+
+    /// Starts a selection of a virtual monitor, which needs no physical
+    /// output, for headless sessions.
+    pub fn virtual_monitor() -> SourceSelectionBuilder {
+        Self::builder(SourceType::VIRTUAL)
+    }
+
+    /// The kinds of sources offered.
+    pub fn types(&self) -> SourceType {
+        self.types
+    }
+
+    /// Whether several sources may be selected.
+    pub fn is_multiple(&self) -> bool {
+        self.multiple
+    }
+
+    /// How many streams the caller expects.
+    pub fn expected_streams(&self) -> usize {
+        self.streams
+    }
+
+    /// The token of the earlier session to restore, if any.
+    pub fn restore_token(&self) -> Option<&str> {
+        self.restore_token.as_deref()
+    }
+}
+
+/// Builds a [`SourceSelection`], see [`SourceSelection::builder`].
+#[derive(Debug, Clone)]
+pub struct SourceSelectionBuilder(SourceSelection);
+
+impl SourceSelectionBuilder {
+    /// Sets whether the user may select several sources, `false` by default.
+    pub fn multiple(mut self, multiple: bool) -> Self {
+        self.0.multiple = multiple;
+        self
+    }
+
+    /// Sets how many streams are expected, 1 by default.
+    pub fn streams(mut self, streams: usize) -> Self {
+        self.0.streams = streams;
+        self
+    }
+
+    /// Sets how the cursor appears in the streams.
+    pub fn cursor_mode(mut self, cursor_mode: CursorMode) -> Self {
+        self.0.cursor_mode = Some(cursor_mode);
+        self
+    }
+
+    /// Sets how long the portal remembers the selection. A restore token
+    /// makes it persistent unless set otherwise.
+    pub fn persist_mode(mut self, persist_mode: PersistMode) -> Self {
+        self.0.persist_mode = persist_mode;
+        self
+    }
+
+    /// Sets the token of the earlier session to restore, as loaded from a
+    /// [`RestoreTokenStore`].
+    pub fn restore_token(mut self, restore_token: Option<String>) -> Self {
+        if restore_token.is_some() && self.0.persist_mode == PersistMode::None {
+            self.0.persist_mode = PersistMode::Persistent;
+        }
+        self.0.restore_token = restore_token;
+        self
+    }
+
+    /// Checks the selection is consistent.
+    pub fn build(self) -> Result<SourceSelection, InvalidSelection> {
+        let selection = self.0;
+        if selection.types.is_empty() {
+            return Err(InvalidSelection::NoSourceTypes);
+        }
+        match selection.streams {
+            0 => Err(InvalidSelection::NoStreams),
+            streams if streams > 1 && !selection.multiple => {
+                Err(InvalidSelection::SingleSource(streams))
+            }
+            _ => Ok(selection),
+        }
+    }
+}
+
+impl From<SourceSelection> for SelectSourcesOptions {
+    fn from(selection: SourceSelection) -> Self {
+        let mut options = Self::new()
+            .types(selection.types)
+            .multiple(selection.multiple);
+        options.cursor_mode = selection.cursor_mode;
+        if selection.persist_mode != PersistMode::None {
+            options.persist_mode = Some(selection.persist_mode);
+        }
+        options.restore_token = selection.restore_token;
+        options
+    }
+}
+
+#[derive(SerializeDict, Type, Debug, Default)]
+#[zvariant(signature = "dict")]
+pub(crate) struct StartOptions {
+    pub(crate) handle_token: HandleToken,
+}
+
+/// The results of a Start request.
+#[derive(DeserializeDict, Type, Debug)]
+#[zvariant(signature = "dict")]
+struct StartResults {
+    streams: Vec<Stream>,
+    restore_token: Option<String>,
+}
+
+impl response::ResponseResults for StartResults {
+    fn check(results: &HashMap<String, OwnedValue>) -> Result<(), Error> {
+        response::require_field(results, "streams", &["a(ua{sv})"])
+    }
+}
+
+/// A stream the user agreed to share, to be consumed through PipeWire.
+///
+/// Backends describe streams very differently, so everything but the node is
+/// optional, and values of an unexpected type are treated as missing.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use wlscreenaccess::screencast::{SourceType, Stream};
+/// # use zbus::zvariant::Value;
+/// # fn parse(node: u32, properties: HashMap<&str, Value<'_>>) -> zbus::Result<Stream> {
+/// #     zbus::Message::signal(
+/// #         None::<&str>,
+/// #         None::<&str>,
+/// #         "/org/freedesktop/portal/desktop",
+/// #         "org.freedesktop.portal.Request",
+/// #         "Response",
+/// #         &(node, properties),
+/// #     )?
+/// #     .body()
+/// # }
+/// // As sent by GNOME.
+/// let mut properties = HashMap::new();
+/// properties.insert("position", Value::from((0i32, 0i32)));
+/// properties.insert("size", Value::from((1920i32, 1080i32)));
+/// properties.insert("source_type", Value::from(1u32));
+/// properties.insert("mapping_id", Value::from("DP-1"));
+/// let stream = parse(57, properties)?;
+/// assert_eq!(stream.pipewire_node_id, 57);
+/// assert_eq!(stream.position, Some((0, 0)));
+/// assert_eq!(stream.size, Some((1920, 1080)));
+/// assert_eq!(stream.source_type, Some(SourceType::MONITOR));
+/// assert_eq!(stream.mapping_id.as_deref(), Some("DP-1"));
+///
+/// // As sent by wlroots.
+/// let mut properties = HashMap::new();
+/// properties.insert("source_type", Value::from(1u32));
+/// let stream = parse(43, properties)?;
+/// assert_eq!(stream.pipewire_node_id, 43);
+/// assert_eq!((stream.position, stream.size, stream.id), (None, None, None));
+/// assert_eq!(stream.source_type, Some(SourceType::MONITOR));
+/// # Ok::<(), zbus::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stream {
+    /// The PipeWire node of the stream.
+    pub pipewire_node_id: u32,
+    /// Where the source is in the compositor's space, for monitors. Virtual
+    /// monitors have none.
+    pub position: Option<(i32, i32)>,
+    /// The size of the source in the compositor's space.
+    pub size: Option<(i32, i32)>,
+    /// Which kind of source the user picked.
+    pub source_type: Option<SourceType>,
+    /// An identifier which stays the same when the session is restored.
+    pub id: Option<String>,
+    /// The identifier of the source in other protocols, like the name of a
+    /// monitor.
+    pub mapping_id: Option<String>,
+}
+
+impl Stream {
+    /// The PipeWire node of the stream, for applications connecting to
+    /// PipeWire themselves, through [`Session::open_pipewire_remote`].
+    pub fn pipewire_node_id(&self) -> u32 {
+        self.pipewire_node_id
+    }
+}
+
+impl Type for Stream {
+    fn signature() -> Signature<'static> {
+        <(u32, HashMap<String, OwnedValue>)>::signature()
+    }
+}
+
+impl<'de> Deserialize<'de> for Stream {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (pipewire_node_id, properties) =
+            <(u32, HashMap<String, OwnedValue>)>::deserialize(deserializer)?;
+        let string = |key| {
+            properties
+                .get(key)
+                .and_then(|value| value.downcast_ref::<str>())
+                .map(ToOwned::to_owned)
+        };
+        Ok(Self {
+            pipewire_node_id,
+            position: pair(properties.get("position")),
+            size: pair(properties.get("size")),
+            source_type: properties
+                .get("source_type")
+                .and_then(|value| value.downcast_ref::<u32>())
+                .map(|bits| SourceType::from_bits_retain(*bits)),
+            id: string("id"),
+            mapping_id: string("mapping_id"),
+        })
+    }
+}
+
+/// Reads a `(ii)` value.
+fn pair(value: Option<&OwnedValue>) -> Option<(i32, i32)> {
+    let structure = value?.downcast_ref::<Structure<'_>>()?;
+    match structure.fields() {
+        [x, y] => Some((*x.downcast_ref::<i32>()?, *y.downcast_ref::<i32>()?)),
+        _ => None,
+    }
+}
+
+/// A client for the ScreenCast portal.
+///
+/// Like [`Portal`](crate::Portal) it owns the bus connection and the proxy,
+/// and clones share them.
+#[derive(Clone, Debug)]
+pub struct ScreenCast {
+    connection: Connection,
+    proxy: ScreenCastProxy<'static>,
+}
+
+impl ScreenCast {
+    /// Connects to the session bus and creates the portal proxy.
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Creates the portal proxy on an existing connection.
+    pub async fn with_connection(connection: &Connection) -> Result<Self, Error> {
+        let proxy = ScreenCastProxy::new(connection).await?;
+        Ok(Self {
+            connection: connection.clone(),
+            proxy,
+        })
+    }
+
+    /// The connection the requests are made on.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The version of the ScreenCast interface the portal implements.
+    pub async fn version(&self) -> Result<u32, Error> {
+        version(&self.proxy).await
+    }
+
+    /// The kinds of sources the portal can share, to offer only those.
+    pub async fn available_source_types(&self) -> Result<SourceType, Error> {
+        self.proxy
+            .available_source_types()
+            .await
+            .map(SourceType::from_bits_retain)
+            .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+    }
+
+    /// The cursor modes the portal supports. Portals older than version 2 of
+    /// the interface support none.
+    pub async fn available_cursor_modes(&self) -> Result<CursorMode, Error> {
+        if self.version().await? < 2 {
+            return Ok(CursorMode::empty());
+        }
+        self.proxy
+            .available_cursor_modes()
+            .await
+            .map(CursorMode::from_bits_retain)
+            .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+    }
+
+    /// Creates a screencast session, the first step before selecting sources
+    /// and starting it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn create_session(&self) -> Result<Session, Error> {
+        let options = CreateSessionOptions::default();
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "CreateSession", &token, &options);
+        let results: CreateSessionResults = response::wait_for_response(
+            &self.connection,
+            &token,
+            self.proxy.create_session(options),
+        )
+        .await?;
+        Session::new(&self.connection, results.session_handle, None).await
+    }
+
+    /// Runs the whole handshake: creates a session, lets the user select the
+    /// sources as `options` says, and starts the session, with the dialogs
+    /// parented to the given window.
+    ///
+    /// `options` may be a [`SourceSelection`] too.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "screencast", skip_all)
+    )]
+    pub async fn start(
+        &self,
+        identifier: &WindowIdentifier,
+        options: impl Into<SelectSourcesOptions>,
+    ) -> Result<(Session, Vec<Stream>), Error> {
+        let session = self.create_session().await?;
+        session.select_sources(options.into()).await?;
+        let streams = session.start(identifier).await?;
+        Ok((session, streams))
+    }
+}
+
+/// A screencast session, made by [`ScreenCast::create_session`], or a remote
+/// desktop one, made by
+/// [`RemoteDesktop::create_session`](crate::remotedesktop::RemoteDesktop::create_session).
+///
+/// Either side may close it. Dropping it closes it too, so the compositor
+/// stops capturing once the session goes out of scope.
+#[derive(Debug)]
+pub struct Session {
+    pub(crate) connection: Connection,
+    proxy: ScreenCastProxy<'static>,
+    session: SessionProxy<'static>,
+    pub(crate) path: OwnedObjectPath,
+    closed: Arc<AtomicBool>,
+    pub(crate) restore_token: Mutex<Option<String>>,
+    /// Set for sessions made by the RemoteDesktop portal.
+    pub(crate) remote: Option<RemoteDesktopProxy<'static>>,
+    /// The devices selected on remote desktop sessions.
+    pub(crate) selected_devices: Mutex<Option<DeviceType>>,
+    /// The devices the user allowed to control on remote desktop sessions.
+    pub(crate) devices: Mutex<DeviceType>,
+}
+
+impl Session {
+    /// Wraps the session at `path`, made by the RemoteDesktop portal if
+    /// `remote` is set.
+    pub(crate) async fn new(
+        connection: &Connection,
+        path: String,
+        remote: Option<RemoteDesktopProxy<'static>>,
+    ) -> Result<Self, Error> {
+        let path = OwnedObjectPath::try_from(path)
+            .map_err(|err| Error::UnexpectedResponse(format!("Invalid session handle: {}", err)))?;
+        // Bound to the portal, which is running since it made the session, for
+        // the Closed signal.
+        let builder = SessionProxy::builder(connection)
+            .path(path.clone())?
+            .cache_properties(CacheProperties::No);
+        let session = connection::bind_to_portal(connection, builder)
+            .await?
+            .build()
+            .await?;
+        Ok(Self {
+            connection: connection.clone(),
+            proxy: ScreenCastProxy::new(connection).await?,
+            session,
+            path,
+            closed: Arc::new(AtomicBool::new(false)),
+            restore_token: Mutex::new(None),
+            remote,
+            selected_devices: Mutex::new(None),
+            devices: Mutex::new(DeviceType::empty()),
+        })
+    }
+
+    /// The path of the `org.freedesktop.portal.Session` object.
+    pub fn path(&self) -> &OwnedObjectPath {
+        &self.path
+    }
+
+    /// The token to restore this session's selection with, sent by the portal
+    /// once started if a persist mode was asked for. It should be saved, see
+    /// [`RestoreTokenStore`].
+    pub fn restore_token(&self) -> Option<String> {
+        self.restore_token.lock().unwrap().clone()
+    }
+
+    /// Opens a connection to the PipeWire remote giving access to the
+    /// session's streams, once started.
+    ///
+    /// The file descriptor is a socket to the PipeWire daemon, to hand to
+    /// `pw_context_connect_fd` or the bindings' equivalent. It's owned, closing
+    /// it when dropped.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn open_pipewire_remote(&self) -> Result<std::os::fd::OwnedFd, Error> {
+        let fd = self
+            .proxy
+            .open_pipewire_remote(&self.path, HashMap::new())
+            .await
+            .map_err(|err| Error::from_call(err, PORTAL_SERVICE))?;
+        Ok(owned_fd(fd))
+    }
+
+    /// Replaces the session by a new one sharing the sources `options` says,
+    /// for when its streams are gone, like when a monitor is unplugged.
+    ///
+    /// This session is closed. Unless `options` has one, its restore token is
+    /// used so the user isn't asked again when the portal can avoid it.
+    /// Remote desktop sessions are remade with the devices selected before.
+    pub async fn restart(
+        self,
+        identifier: &WindowIdentifier,
+        options: impl Into<SelectSourcesOptions>,
+    ) -> Result<(Session, Vec<Stream>), Error> {
+        let mut options = options.into();
+        if options.restore_token.is_none() {
+            options.restore_token = self.restore_token();
+        }
+        if options.restore_token.is_some() && options.persist_mode.is_none() {
+            options.persist_mode = Some(PersistMode::Persistent);
+        }
+        // The portal may have dropped the session already.
+        let _ = self.close().await;
+        if self.remote.is_some() {
+            return self.restart_remote(identifier, options).await;
+        }
+        let portal = ScreenCast {
+            connection: self.connection.clone(),
+            proxy: self.proxy.clone(),
+        };
+        portal.start(identifier, options).await
+    }
+
+    /// Closes the session, ending the streams. Does nothing if it was already
+    /// closed, by either side.
+    pub async fn close(&self) -> Result<(), Error> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        Ok(self.session.close().await?)
+    }
+
+    /// Waits for the portal to close the session, yielding the details it
+    /// sends along. The session isn't closed again on drop after that.
+    pub async fn receive_closed(
+        &self,
+    ) -> Result<impl futures_lite::Stream<Item = HashMap<String, OwnedValue>>, Error> {
+        let closed = self.closed.clone();
+        let signals = self.session.receive_closed().await?;
+        Ok(signals.map(move |signal| {
+            closed.store(true, Ordering::SeqCst);
+            signal.args().map(|args| args.details).unwrap_or_default()
+        }))
+    }
+
+    /// Lets the user choose what to share, through the portal's dialog.
+    ///
+    /// Fails with [`Error::SourceTypeUnavailable`] if the portal can share
+    /// none of the kinds of sources asked for, like virtual monitors on a
+    /// portal without them, before any dialog is shown.
+    ///
+    /// Fails with [`Error::UnsupportedVersion`] if a cursor mode is asked of
+    /// a portal too old to know about them. The persist mode and restore
+    /// token are dropped for portals too old for them, the user is asked
+    /// again instead.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn select_sources(&self, mut options: SelectSourcesOptions) -> Result<(), Error> {
+        if let Some(requested) = options.types {
+            let available = self
+                .proxy
+                .available_source_types()
+                .await
+                .map(SourceType::from_bits_retain)
+                .map_err(|err| Error::from_call(err, PORTAL_SERVICE))?;
+            if !available.intersects(requested) {
+                return Err(Error::SourceTypeUnavailable {
+                    requested,
+                    available,
+                });
+            }
+        }
+        let persists = options.persist_mode.is_some() || options.restore_token.is_some();
+        if options.cursor_mode.is_some() || persists {
+            let found = version(&self.proxy).await?;
+            if options.cursor_mode.is_some() && found < 2 {
+                return Err(Error::UnsupportedVersion { required: 2, found });
+            }
+            if found < 4 {
+                trace::warning!(version = found, "portal too old to persist sessions");
+                options.persist_mode = None;
+                options.restore_token = None;
+            }
+        }
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "SelectSources", &token, &options);
+        response::wait_for_response::<response::BasicResponse>(
+            &self.connection,
+            &token,
+            self.proxy.select_sources(&self.path, options),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Starts sharing the selected sources, with the dialog parented to the
+    /// given window, returning their streams.
+    ///
+    /// Remote desktop sessions are started through the RemoteDesktop portal,
+    /// with the devices selected, and have no streams if no sources were.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn start(&self, identifier: &WindowIdentifier) -> Result<Vec<Stream>, Error> {
+        if let Some(remote) = &self.remote {
+            return self.start_remote(remote, identifier).await;
+        }
+        let options = StartOptions::default();
+        let token = options.handle_token.clone();
+        record::call(self.proxy.inner(), "Start", &token, &options);
+        let results: StartResults = response::wait_for_response(
+            &self.connection,
+            &token,
+            self.proxy.start(&self.path, identifier, options),
+        )
+        .await?;
+        *self.restore_token.lock().unwrap() = results.restore_token;
+        Ok(results.streams)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let session = self.session.clone();
+        runtime::detach(async move {
+            let _ = session.close().await;
+        });
+    }
+}
+
+/// Where restore tokens are kept between runs.
+pub trait RestoreTokenStore {
+    /// The saved token, `None` if there is none.
+    fn load(&self) -> Result<Option<String>, Error>;
+    /// Saves `token`, replacing the previous one.
+    fn save(&self, token: &str) -> Result<(), Error>;
+    /// Forgets the saved token, for when the portal refused it.
+    fn clear(&self) -> Result<(), Error>;
+}
+
+/// Keeps the restore token in a file, by default under `$XDG_STATE_HOME`.
+///
+/// ```
+/// # use wlscreenaccess::screencast::{FileTokenStore, RestoreTokenStore};
+/// # let path = std::env::temp_dir().join("wlscreenaccess-restore-token");
+/// let store = FileTokenStore::at(&path);
+/// # store.clear()?;
+/// assert_eq!(store.load()?, None);
+/// store.save("4f0ab2a7")?;
+/// assert_eq!(store.load()?.as_deref(), Some("4f0ab2a7"));
+/// store.clear()?;
+/// assert_eq!(store.load()?, None);
+/// # Ok::<(), wlscreenaccess::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Stores the token of the application `app_id` in
+    /// `$XDG_STATE_HOME/<app_id>/screencast-restore-token`, with
+    /// `~/.local/state` when the variable isn't set. `None` without a home
+    /// directory either.
+    pub fn new(app_id: &str) -> Option<Self> {
+        let state = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+        Some(Self::at(
+            state.join(app_id).join("screencast-restore-token"),
+        ))
+    }
+
+    /// Stores the token in the file at `path`.
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The file the token is kept in.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    fn error(&self, source: std::io::Error) -> Error {
+        Error::File {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+impl RestoreTokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<String>, Error> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(token) => Ok(Some(token.trim().to_owned()).filter(|token| !token.is_empty())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(self.error(err)),
+        }
+    }
+
+    fn save(&self, token: &str) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| self.error(err))?;
+        }
+        std::fs::write(&self.path, token).map_err(|err| self.error(err))
+    }
+
+    fn clear(&self) -> Result<(), Error> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(self.error(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn version(proxy: &ScreenCastProxy<'_>) -> Result<u32, Error> {
+    proxy
+        .version()
+        .await
+        .map_err(|err| Error::from_call(err, PORTAL_SERVICE))
+}
+
+/// The descriptor zbus received, as a standard one to hand to other crates.
+pub(crate) fn owned_fd(fd: OwnedFd) -> std::os::fd::OwnedFd {
+    // SAFETY: `into_raw_fd` gives up the descriptor, which nothing else
+    // closes.
+    unsafe { std::os::fd::OwnedFd::from_raw_fd(fd.into_raw_fd()) }
+}
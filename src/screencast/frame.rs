@@ -0,0 +1,167 @@
+/// How the pixels of a [`Frame`] are laid out, four bytes each in memory
+/// order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Blue, green, red, and an unused byte.
+    Bgrx,
+    /// Blue, green, red, alpha.
+    Bgra,
+    /// Red, green, blue, and an unused byte.
+    Rgbx,
+    /// Red, green, blue, alpha.
+    Rgba,
+}
+
+impl PixelFormat {
+    /// The bytes a pixel takes.
+    pub const fn bytes_per_pixel(self) -> usize {
+        4
+    }
+}
+
+/// A single frame of a screencast stream.
+///
+/// Rows are `stride` bytes apart, which may be more than the pixels take.
+///
+/// ```
+/// # use wlscreenaccess::screencast::{Frame, PixelFormat};
+/// // One row of two pixels, padded to 12 bytes.
+/// let data = vec![0, 0, 255, 0, 255, 0, 0, 0, 9, 9, 9, 9];
+/// let frame = Frame::new(2, 1, 12, PixelFormat::Bgrx, data).unwrap();
+/// assert_eq!((frame.width(), frame.height(), frame.stride()), (2, 1, 12));
+///
+/// assert!(Frame::new(2, 1, 4, PixelFormat::Bgrx, vec![0; 8]).is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    /// Wraps the pixels of a frame, `None` if `stride` is zero or too small
+    /// for `width`, or `data` too short for `height` rows.
+    pub fn new(
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: PixelFormat,
+        data: Vec<u8>,
+    ) -> Option<Self> {
+        let row = (width as usize).checked_mul(format.bytes_per_pixel())?;
+        if stride == 0 || stride < row {
+            return None;
+        }
+        let needed = match height {
+            0 => 0,
+            height => stride.checked_mul(height as usize - 1)?.checked_add(row)?,
+        };
+        (data.len() >= needed).then_some(Self {
+            width,
+            height,
+            stride,
+            format,
+            data,
+        })
+    }
+
+    /// The pixels in a row.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The rows of pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The bytes from the start of a row to the next.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// How the pixels are laid out.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The pixels as delivered, rows padded to the stride.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Takes the pixels as delivered, without copying them.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The pixels as tightly packed RGBA, without the padding of the rows.
+    /// Formats without alpha are made opaque.
+    ///
+    /// ```
+    /// # use wlscreenaccess::screencast::{Frame, PixelFormat};
+    /// // Two rows of a red and a blue pixel, padded with a 5th unused pixel.
+    /// let padded = |pixels: [[u8; 4]; 2]| {
+    ///     let row = [pixels[0], pixels[1], [7; 4]].concat();
+    ///     [row.clone(), row].concat()
+    /// };
+    /// let rgba = [[255, 0, 0, 255], [0, 0, 255, 128]];
+    /// let opaque = [[255, 0, 0, 255], [0, 0, 255, 255]];
+    /// for (format, pixels, expected) in [
+    ///     (PixelFormat::Bgrx, [[0, 0, 255, 0], [255, 0, 0, 0]], opaque),
+    ///     (PixelFormat::Bgra, [[0, 0, 255, 255], [255, 0, 0, 128]], rgba),
+    ///     (PixelFormat::Rgbx, [[255, 0, 0, 9], [0, 0, 255, 9]], opaque),
+    ///     (PixelFormat::Rgba, rgba, rgba),
+    /// ] {
+    ///     let frame = Frame::new(2, 2, 12, format, padded(pixels)).unwrap();
+    ///     assert_eq!(frame.to_rgba8(), [expected.concat(), expected.concat()].concat());
+    /// }
+    /// ```
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let row = self.width as usize * self.format.bytes_per_pixel();
+        let mut rgba = vec![0; row * self.height as usize];
+        if row == 0 {
+            return rgba;
+        }
+        let lines = self.data.chunks(self.stride).map(|line| &line[..row]);
+        for (out, line) in rgba.chunks_exact_mut(row).zip(lines) {
+            // One loop per format, so each is a plain shuffle the compiler can
+            // vectorize.
+            let pixels = out.chunks_exact_mut(4).zip(line.chunks_exact(4));
+            match self.format {
+                PixelFormat::Rgba => out.copy_from_slice(line),
+                PixelFormat::Rgbx => pixels.for_each(|(out, pixel)| {
+                    out.copy_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+                }),
+                PixelFormat::Bgra => pixels.for_each(|(out, pixel)| {
+                    out.copy_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+                }),
+                PixelFormat::Bgrx => pixels.for_each(|(out, pixel)| {
+                    out.copy_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+                }),
+            }
+        }
+        rgba
+    }
+}
+
+#[cfg(feature = "image")]
+impl Frame {
+    /// Converts the frame to an RGBA image.
+    ///
+    /// ```
+    /// # use wlscreenaccess::screencast::{Frame, PixelFormat};
+    /// let data = vec![0, 0, 255, 0, 255, 0, 0, 0, 9, 9, 9, 9];
+    /// let frame = Frame::new(2, 1, 12, PixelFormat::Bgrx, data).unwrap();
+    /// let image = frame.to_image();
+    /// assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    /// assert_eq!(image.get_pixel(1, 0).0, [0, 0, 255, 255]);
+    /// ```
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.to_rgba8())
+            .expect("The buffer holds every pixel")
+    }
+}
@@ -0,0 +1,246 @@
+//! Reading the desktop's appearance through the Settings portal.
+//!
+//! ```no_run
+//! # use futures_lite::StreamExt;
+//! # use wlscreenaccess::settings::{read_accent_color, Settings};
+//! # async fn run() -> Result<(), wlscreenaccess::Error> {
+//! if let Some(accent) = read_accent_color().await? {
+//!     println!("accent {}", accent);
+//! }
+//! let settings = Settings::new().await?;
+//! let mut changes = settings.receive_setting_changed().await?;
+//! while let Some(change) = changes.next().await {
+//!     if let Some(scheme) = change.color_scheme() {
+//!         println!("now {:?}", scheme);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use futures_lite::{Stream, StreamExt};
+use zbus::{
+    dbus_proxy,
+    zvariant::{OwnedValue, Structure, Value},
+    Connection,
+};
+
+use crate::{connection, trace, Error, PORTAL_SERVICE, RGB};
+
+const APPEARANCE: &str = "org.freedesktop.appearance";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Settings {
+    fn read(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+    #[dbus_proxy(signal)]
+    fn setting_changed(
+        &self,
+        namespace: String,
+        key: String,
+        value: OwnedValue,
+    ) -> zbus::Result<()>;
+}
+
+/// Whether the user prefers a dark or a light appearance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorScheme {
+    /// No preference, also used for values newer than this crate.
+    #[default]
+    NoPreference,
+    PreferDark,
+    PreferLight,
+}
+
+impl From<u32> for ColorScheme {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::PreferDark,
+            2 => Self::PreferLight,
+            _ => Self::NoPreference,
+        }
+    }
+}
+
+/// Reads an `accent-color`, unset when out of the 0.0 to 1.0 range.
+fn accent_color(value: &Value<'_>) -> Option<RGB> {
+    let structure = value.downcast_ref::<Structure<'_>>()?;
+    let [red, green, blue] = structure.fields() else {
+        return None;
+    };
+    let color = [
+        *red.downcast_ref::<f64>()?,
+        *green.downcast_ref::<f64>()?,
+        *blue.downcast_ref::<f64>()?,
+    ];
+    color
+        .iter()
+        .all(|component| (0.0..=1.0).contains(component))
+        .then(|| RGB::from(color))
+}
+
+fn color_scheme(value: &Value<'_>) -> Option<ColorScheme> {
+    value.downcast_ref::<u32>().copied().map(ColorScheme::from)
+}
+
+/// A setting which changed, see [`Settings::receive_setting_changed`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedSetting {
+    pub namespace: String,
+    pub key: String,
+    pub value: OwnedValue,
+}
+
+impl ChangedSetting {
+    /// The new accent color, if that's what changed and it's set.
+    pub fn accent_color(&self) -> Option<RGB> {
+        self.is(APPEARANCE, "accent-color")
+            .then(|| accent_color(&self.value))
+            .flatten()
+    }
+
+    /// The new color scheme, if that's what changed.
+    pub fn color_scheme(&self) -> Option<ColorScheme> {
+        self.is(APPEARANCE, "color-scheme")
+            .then(|| color_scheme(&self.value))
+            .flatten()
+    }
+
+    fn is(&self, namespace: &str, key: &str) -> bool {
+        self.namespace == namespace && self.key == key
+    }
+}
+
+/// A client for the Settings portal.
+///
+/// Like [`Portal`](crate::Portal) it owns the bus connection and the proxy,
+/// and clones share them.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    proxy: SettingsProxy<'static>,
+}
+
+impl Settings {
+    /// Connects to the session bus and creates the portal proxy.
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Creates the portal proxy on an existing connection.
+    pub async fn with_connection(connection: &Connection) -> Result<Self, Error> {
+        let proxy = SettingsProxy::new(connection).await?;
+        Ok(Self { proxy })
+    }
+
+    /// Reads the setting `key` of `namespace`, `None` if the portal doesn't
+    /// have it.
+    ///
+    /// Portals older than version 2 of the interface are read with the
+    /// deprecated `Read` method, unwrapping the extra variant it adds.
+    pub async fn read(&self, namespace: &str, key: &str) -> Result<Option<OwnedValue>, Error> {
+        let err = match self.proxy.read_one(namespace, key).await {
+            Ok(value) => return Ok(Some(value)),
+            Err(err) => Error::from_call(err, PORTAL_SERVICE),
+        };
+        if !err.is_unsupported() {
+            return not_found(err);
+        }
+        trace::warning!(namespace, key, "ReadOne unsupported, falling back to Read");
+        match self.proxy.read(namespace, key).await {
+            Ok(value) => Ok(Some(match &*value {
+                Value::Value(inner) => OwnedValue::from((**inner).clone()),
+                _ => value,
+            })),
+            Err(err) => not_found(Error::from_call(err, PORTAL_SERVICE)),
+        }
+    }
+
+    /// The accent color the user chose, `None` if they didn't or the portal
+    /// doesn't know about accent colors.
+    pub async fn read_accent_color(&self) -> Result<Option<RGB>, Error> {
+        let value = self.read(APPEARANCE, "accent-color").await?;
+        Ok(value.and_then(|value| accent_color(&value)))
+    }
+
+    /// The color scheme the user prefers, no preference if the portal doesn't
+    /// know about color schemes.
+    pub async fn read_color_scheme(&self) -> Result<ColorScheme, Error> {
+        let value = self.read(APPEARANCE, "color-scheme").await?;
+        Ok(value
+            .and_then(|value| color_scheme(&value))
+            .unwrap_or_default())
+    }
+
+    /// Yields the settings as they change.
+    pub async fn receive_setting_changed(
+        &self,
+    ) -> Result<impl Stream<Item = ChangedSetting>, Error> {
+        let connection = self.proxy.connection();
+        let builder = SettingsProxy::builder(connection);
+        let signals = connection::bind_to_portal(connection, builder)
+            .await?
+            .build()
+            .await?
+            .receive_setting_changed()
+            .await?;
+        Ok(signals.filter_map(|signal| {
+            let args = signal.args().ok()?;
+            Some(ChangedSetting {
+                namespace: args.namespace,
+                key: args.key,
+                value: args.value,
+            })
+        }))
+    }
+}
+
+/// Settings the portal doesn't have aren't an error.
+fn not_found(err: Error) -> Result<Option<OwnedValue>, Error> {
+    match err {
+        Error::Zbus(zbus::Error::MethodError(name, _, _))
+            if name.as_str() == "org.freedesktop.portal.Error.NotFound" =>
+        {
+            Ok(None)
+        }
+        err => Err(err),
+    }
+}
+
+/// Reads the accent color, see [`Settings::read_accent_color`].
+pub async fn read_accent_color() -> Result<Option<RGB>, Error> {
+    connection::with_session(|connection| async move {
+        Settings::with_connection(&connection)
+            .await?
+            .read_accent_color()
+            .await
+    })
+    .await
+}
+
+/// Reads the color scheme, see [`Settings::read_color_scheme`].
+pub async fn read_color_scheme() -> Result<ColorScheme, Error> {
+    connection::with_session(|connection| async move {
+        Settings::with_connection(&connection)
+            .await?
+            .read_color_scheme()
+            .await
+    })
+    .await
+}
+
+/// Yields the settings as they change, see
+/// [`Settings::receive_setting_changed`].
+pub async fn receive_setting_changed() -> Result<impl Stream<Item = ChangedSetting>, Error> {
+    connection::with_session(|connection| async move {
+        Settings::with_connection(&connection)
+            .await?
+            .receive_setting_changed()
+            .await
+    })
+    .await
+}
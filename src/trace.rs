@@ -0,0 +1,46 @@
+//! Events of the request lifecycle, sent to `tracing` with the `tracing`
+//! feature and compiled out without it.
+//!
+//! The macros take the arguments of their `tracing` counterparts.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+pub(crate) use {debug, warning};
+
+/// Sends the response received for the request at `path`, or why it
+/// couldn't be decoded.
+#[cfg(feature = "tracing")]
+pub(crate) fn received<T>(
+    path: &zbus::zvariant::OwnedObjectPath,
+    response: &Result<crate::response::Response<T>, crate::Error>,
+) where
+    T: for<'de> serde::Deserialize<'de> + zbus::zvariant::Type,
+{
+    use crate::{response::Response, ResponseError};
+
+    // The portal puts the handle token last in the request path.
+    let token = path.as_str().rsplit('/').next().unwrap_or_default();
+    let code = match response {
+        Ok(Response::Ok(_)) => 0,
+        Ok(Response::Err(ResponseError::Cancelled)) => 1,
+        Ok(Response::Err(ResponseError::Other(_))) => 2,
+        Err(crate::Error::UnknownResponseCode(code)) => *code,
+        Err(err) => {
+            tracing::warn!(token, error = %err, "response received but not decoded");
+            return;
+        }
+    };
+    tracing::debug!(token, code, "response received");
+}
@@ -0,0 +1,127 @@
+//! Setting the desktop wallpaper through the Wallpaper portal.
+//!
+//! Screenshots can be made the wallpaper straight away:
+//!
+//! ```no_run
+//! # use wlscreenaccess::wallpaper::{set_wallpaper, WallpaperTarget};
+//! # async fn run() -> Result<(), wlscreenaccess::Error> {
+//! let screenshot = wlscreenaccess::screenshot().await?;
+//! set_wallpaper(screenshot.url(), WallpaperTarget::Both, true).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Serialize, Serializer};
+use zbus::{
+    dbus_proxy,
+    zvariant::{OwnedObjectPath, SerializeDict, Signature, Type},
+    Connection,
+};
+
+use crate::{
+    connection, record,
+    response::{wait_for_response, BasicResponse},
+    Error, HandleToken, WindowIdentifier,
+};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Wallpaper",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Wallpaper {
+    #[dbus_proxy(name = "SetWallpaperURI")]
+    fn set_wallpaper_uri(
+        &self,
+        parent_window: &WindowIdentifier,
+        uri: &str,
+        options: WallpaperOptions,
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// Where the wallpaper is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WallpaperTarget {
+    /// The desktop background.
+    Background,
+    /// The lock screen.
+    Lockscreen,
+    /// Both the background and the lock screen.
+    #[default]
+    Both,
+}
+
+impl Type for WallpaperTarget {
+    fn signature() -> Signature<'static> {
+        str::signature()
+    }
+}
+
+impl Serialize for WallpaperTarget {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Background => "background",
+            Self::Lockscreen => "lockscreen",
+            Self::Both => "both",
+        })
+    }
+}
+
+#[derive(SerializeDict, Type, Debug, Default)]
+#[zvariant(signature = "dict")]
+struct WallpaperOptions {
+    handle_token: HandleToken,
+    #[zvariant(rename = "show-preview")]
+    show_preview: Option<bool>,
+    #[zvariant(rename = "set-on")]
+    set_on: Option<WallpaperTarget>,
+}
+
+/// Sets the image at `uri` as the wallpaper of `target`, letting the user
+/// preview it first if `show_preview` is set.
+///
+/// Declining is [`Error::Cancelled`].
+pub async fn set_wallpaper(
+    uri: &url::Url,
+    target: WallpaperTarget,
+    show_preview: bool,
+) -> Result<(), Error> {
+    connection::with_session(|connection| async move {
+        set_wallpaper_on(
+            &connection,
+            &WindowIdentifier::None,
+            uri,
+            target,
+            show_preview,
+        )
+        .await
+    })
+    .await
+}
+
+/// Sets the wallpaper through the portal on `connection`, with the preview
+/// parented to the given window, see [`set_wallpaper`].
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub async fn set_wallpaper_on(
+    connection: &Connection,
+    identifier: &WindowIdentifier,
+    uri: &url::Url,
+    target: WallpaperTarget,
+    show_preview: bool,
+) -> Result<(), Error> {
+    let proxy = WallpaperProxy::new(connection).await?;
+    let options = WallpaperOptions {
+        show_preview: Some(show_preview),
+        set_on: Some(target),
+        ..Default::default()
+    };
+    let token = options.handle_token.clone();
+    record::call(proxy.inner(), "SetWallpaperURI", &token, &options);
+    wait_for_response::<BasicResponse>(
+        connection,
+        &token,
+        proxy.set_wallpaper_uri(identifier, uri.as_str(), options),
+    )
+    .await?;
+    Ok(())
+}
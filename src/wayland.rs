@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_surface::WlSurface},
+    Connection, Dispatch, QueueHandle,
+};
+use wayland_protocols::xdg::foreign::zv2::client::{
+    zxdg_exported_v2::{self, ZxdgExportedV2},
+    zxdg_exporter_v2::ZxdgExporterV2,
+};
+
+use crate::{window_identifier::WaylandHandle, Error};
+
+/// A surface exported through `xdg_foreign`, un-exported when dropped.
+#[derive(Debug)]
+pub(crate) struct Exported {
+    connection: Connection,
+    exported: ZxdgExportedV2,
+}
+
+impl Drop for Exported {
+    fn drop(&mut self) {
+        self.exported.destroy();
+        let _ = self.connection.flush();
+    }
+}
+
+#[derive(Default)]
+struct State {
+    handle: Option<String>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZxdgExporterV2, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZxdgExporterV2,
+        _: <ZxdgExporterV2 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZxdgExportedV2, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgExportedV2,
+        event: zxdg_exported_v2::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zxdg_exported_v2::Event::Handle { handle } = event {
+            state.handle = Some(handle);
+        }
+    }
+}
+
+/// Exports `surface` and waits for the compositor to hand out its handle.
+pub(crate) fn export(connection: &Connection, surface: &WlSurface) -> Result<WaylandHandle, Error> {
+    let failed = |err: &dyn std::fmt::Display| {
+        Error::WindowIdentifier(format!("Failed to export the Wayland surface: {}", err))
+    };
+    let (globals, mut queue) =
+        registry_queue_init::<State>(connection).map_err(|err| failed(&err))?;
+    let exporter: ZxdgExporterV2 = globals
+        .bind(&queue.handle(), 1..=1, ())
+        .map_err(|err| failed(&format!("zxdg_exporter_v2: {}", err)))?;
+    let exported = exporter.export_toplevel(surface, &queue.handle(), ());
+    exporter.destroy();
+    let mut state = State::default();
+    queue.roundtrip(&mut state).map_err(|err| failed(&err))?;
+    let exported = Exported {
+        connection: connection.clone(),
+        exported,
+    };
+    match state.handle {
+        Some(handle) => Ok(WaylandHandle::exported(handle, Arc::new(exported))),
+        None => Err(failed(&"the compositor sent no handle")),
+    }
+}
+
+/// Exports a surface known only by the raw pointers of a toolkit's window.
+///
+/// # Safety
+///
+/// `display` and `surface` must be a live `wl_display` and `wl_surface`, and
+/// stay so while the returned handle lives.
+#[cfg(feature = "rwh")]
+pub(crate) unsafe fn export_raw(
+    display: std::ptr::NonNull<std::ffi::c_void>,
+    surface: std::ptr::NonNull<std::ffi::c_void>,
+) -> Result<WaylandHandle, Error> {
+    use wayland_backend::client::{Backend, ObjectId};
+    use wayland_client::Proxy;
+
+    let connection =
+        Connection::from_backend(Backend::from_foreign_display(display.as_ptr().cast()));
+    let surface = ObjectId::from_ptr(WlSurface::interface(), surface.as_ptr().cast())
+        .and_then(|id| WlSurface::from_id(&connection, id))
+        .map_err(|err| Error::WindowIdentifier(format!("Invalid Wayland surface: {}", err)))?;
+    export(&connection, &surface)
+}
@@ -0,0 +1,221 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use zbus::zvariant::Type;
+
+/// The window a portal dialog is parented to.
+///
+/// On the wire it is a string: empty for no parent, `wayland:HANDLE` for a
+/// surface exported through `xdg_foreign`, and `x11:0xXID` for an X11 window.
+/// `Display` and `FromStr` convert it to and from that string.
+///
+/// ```
+/// # use wlscreenaccess::WindowIdentifier;
+/// let identifier: WindowIdentifier = "x11:0x3a00007".parse().unwrap();
+/// assert_eq!(identifier.to_string(), "x11:0x3a00007");
+/// assert!("mir:1".parse::<WindowIdentifier>().is_err());
+/// ```
+#[derive(Type, Debug, Clone, PartialEq, Eq, Default)]
+#[zvariant(signature = "s")]
+pub enum WindowIdentifier {
+    /// No parent, the dialog floats on its own.
+    #[default]
+    None,
+    /// A Wayland surface, by the handle it was exported with.
+    Wayland(WaylandHandle),
+    /// An X11 window, by its XID.
+    X11(u64),
+    /// An identifier obtained by other means, sent as is.
+    Raw(String),
+}
+
+impl WindowIdentifier {
+    /// Identifies the dialog's parent by the XID of an X11 window, as used by
+    /// apps on X11 or XWayland.
+    ///
+    /// The window has to be mapped for backends to honor it. XID 0 is no
+    /// window at all and is rejected.
+    ///
+    /// ```
+    /// # use wlscreenaccess::WindowIdentifier;
+    /// let identifier = WindowIdentifier::from_xid(0x3A00007).unwrap();
+    /// assert_eq!(identifier, WindowIdentifier::X11(0x3a00007));
+    /// assert!(WindowIdentifier::from_xid(0).is_err());
+    /// ```
+    pub fn from_xid(xid: u64) -> Result<Self, crate::Error> {
+        if xid == 0 {
+            return Err(crate::Error::WindowIdentifier(
+                "XID 0 doesn't name an X11 window".to_owned(),
+            ));
+        }
+        Ok(Self::X11(xid))
+    }
+
+    /// Wraps an identifier already in the portal's format, for setups the
+    /// other variants don't cover. It isn't checked.
+    pub fn from_raw(identifier: impl Into<String>) -> Self {
+        Self::Raw(identifier.into())
+    }
+}
+
+#[cfg(feature = "wayland")]
+impl WindowIdentifier {
+    /// Exports `surface` through the `zxdg_exporter_v2` protocol and
+    /// identifies the dialog's parent by the resulting handle.
+    ///
+    /// The surface stays exported for as long as the identifier, or a clone of
+    /// it, lives. This blocks on a roundtrip with the compositor.
+    pub fn from_wayland_surface(
+        connection: &wayland_client::Connection,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
+    ) -> Result<Self, crate::Error> {
+        crate::wayland::export(connection, surface).map(Self::Wayland)
+    }
+}
+
+#[cfg(feature = "rwh")]
+impl WindowIdentifier {
+    /// Identifies the dialog's parent by a toolkit's window, for the toolkits
+    /// exposing `raw-window-handle` like winit.
+    ///
+    /// Wayland surfaces are exported as in
+    /// [`from_wayland_surface`](Self::from_wayland_surface). X11 windows go by
+    /// their XID. Windows of other platforms are an error.
+    ///
+    /// # Safety
+    ///
+    /// For a Wayland window, the identifier and all its clones must be dropped
+    /// before the window's `wl_display` and `wl_surface` are destroyed, since
+    /// dropping the last of them un-exports the surface through that display.
+    pub unsafe fn from_window<W>(window: &W) -> Result<Self, crate::Error>
+    where
+        W: raw_window_handle::HasWindowHandle + raw_window_handle::HasDisplayHandle,
+    {
+        use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+
+        let invalid = |err: raw_window_handle::HandleError| {
+            crate::Error::WindowIdentifier(format!("No handle for the window: {}", err))
+        };
+        let display = window.display_handle().map_err(invalid)?.as_raw();
+        match (window.window_handle().map_err(invalid)?.as_raw(), display) {
+            (RawWindowHandle::Wayland(window), RawDisplayHandle::Wayland(display)) => {
+                // SAFETY: both pointers come from live handles of the same
+                // window, which the caller promised outlives the export.
+                unsafe { crate::wayland::export_raw(display.display, window.surface) }
+                    .map(Self::Wayland)
+            }
+            // `c_ulong` is only 32 bits wide on some targets.
+            #[allow(clippy::unnecessary_cast)]
+            (RawWindowHandle::Xlib(window), _) => Self::from_xid(window.window as u64),
+            (RawWindowHandle::Xcb(window), _) => Self::from_xid(window.window.get() as u64),
+            (window, _) => Err(crate::Error::WindowIdentifier(format!(
+                "Unsupported window handle {:?}, only Wayland and X11 windows can be parents",
+                window
+            ))),
+        }
+    }
+}
+
+/// The handle of a Wayland surface exported through `xdg_foreign`.
+///
+/// When made by `WindowIdentifier::from_wayland_surface` it also keeps the
+/// export alive.
+#[derive(Debug, Clone)]
+pub struct WaylandHandle {
+    handle: String,
+    #[cfg(feature = "wayland")]
+    _exported: Option<std::sync::Arc<crate::wayland::Exported>>,
+}
+
+impl WaylandHandle {
+    /// Wraps a handle exported by other means.
+    pub fn new(handle: impl Into<String>) -> Self {
+        Self {
+            handle: handle.into(),
+            #[cfg(feature = "wayland")]
+            _exported: None,
+        }
+    }
+
+    #[cfg(feature = "wayland")]
+    pub(crate) fn exported(
+        handle: String,
+        exported: std::sync::Arc<crate::wayland::Exported>,
+    ) -> Self {
+        Self {
+            handle,
+            _exported: Some(exported),
+        }
+    }
+
+    /// The handle sent to the portal.
+    pub fn as_str(&self) -> &str {
+        &self.handle
+    }
+}
+
+impl PartialEq for WaylandHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl Eq for WaylandHandle {}
+
+impl fmt::Display for WindowIdentifier {
+    /// Formats the identifier as sent to the portal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::Wayland(handle) => write!(f, "wayland:{}", handle.as_str()),
+            Self::X11(xid) => write!(f, "x11:0x{:x}", xid),
+            Self::Raw(identifier) => f.write_str(identifier),
+        }
+    }
+}
+
+impl FromStr for WindowIdentifier {
+    type Err = crate::Error;
+
+    /// Parses an identifier in the portal's format, as made by `Display`.
+    fn from_str(identifier: &str) -> Result<Self, Self::Err> {
+        if identifier.is_empty() {
+            Ok(Self::None)
+        } else if let Some(handle) = identifier.strip_prefix("wayland:") {
+            Ok(Self::Wayland(WaylandHandle::new(handle)))
+        } else if let Some(xid) = identifier.strip_prefix("x11:0x") {
+            u64::from_str_radix(xid, 16)
+                .ok()
+                .filter(|&xid| xid != 0)
+                .map(Self::X11)
+                .ok_or_else(|| {
+                    crate::Error::WindowIdentifier(format!("Invalid XID in {}", identifier))
+                })
+        } else {
+            Err(crate::Error::WindowIdentifier(format!(
+                "Unknown window identifier {}",
+                identifier
+            )))
+        }
+    }
+}
+
+impl Serialize for WindowIdentifier {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowIdentifier {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,79 @@
+//! Allocations a screenshot request makes on the client, counted on the
+//! thread driving it while the portal is served from another.
+
+mod common;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::mpsc,
+};
+
+use common::{Bus, MockScreenshot, Reply};
+use wlscreenaccess::{Portal, ScreenshotOptions, WindowIdentifier};
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations of each thread.
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Allocations while the thread is torn down aren't counted.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const REQUESTS: usize = 20;
+
+#[test]
+fn screenshot_request_allocations() {
+    let bus = Bus::spawn();
+    let (served, stop) = (mpsc::channel(), mpsc::channel::<()>());
+    std::thread::scope(|scope| {
+        let bus = &bus;
+        scope.spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let _server = runtime.block_on(bus.serve_interface(MockScreenshot::new(
+                Reply::screenshot("file:///tmp/Screenshot.png"),
+            )));
+            served.0.send(()).unwrap();
+            let _ = stop.1.recv();
+        });
+        served.1.recv().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let portal = Portal::with_connection(&bus.connect().await).await.unwrap();
+            let screenshot = || {
+                portal
+                    .screenshot_with_options(&WindowIdentifier::None, ScreenshotOptions::default())
+            };
+            // The first request sets up what the following ones reuse.
+            screenshot().await.unwrap();
+            let before = ALLOCATIONS.with(Cell::get);
+            for _ in 0..REQUESTS {
+                screenshot().await.unwrap();
+            }
+            let per_request = (ALLOCATIONS.with(Cell::get) - before) / REQUESTS;
+            println!("{} allocations per screenshot request", per_request);
+            // About 330 with a proxy and a match rule per request, the rule
+            // removed on the connection's executor once answered.
+            assert!(per_request < 400, "{} allocations per request", per_request);
+        });
+        stop.0.send(()).unwrap();
+    });
+}
@@ -0,0 +1,31 @@
+//! Requests driven by async-io alone, as with the default features. Nothing
+//! here runs inside a tokio runtime, so anything needing one would panic.
+
+mod common;
+
+use common::{Bus, MockScreenshot, Reply};
+use wlscreenaccess::Portal;
+
+#[test]
+fn requests_run_without_tokio() {
+    let bus = Bus::spawn();
+    let path = std::env::temp_dir().join(format!("wlsa-async-io-{}.png", std::process::id()));
+    std::fs::write(&path, b"\x89PNG async-io").unwrap();
+    let uri = url::Url::from_file_path(&path).unwrap();
+
+    async_io::block_on(async {
+        let _server = bus
+            .serve_interface(MockScreenshot::new(Reply::screenshot(uri.as_str())))
+            .await;
+        let portal = Portal::connect_to_address(bus.address()).await.unwrap();
+        let screenshot = portal.screenshot().await.unwrap();
+        assert_eq!(screenshot.read_bytes().await.unwrap(), b"\x89PNG async-io");
+        let mut reader = screenshot.reader().await.unwrap();
+        let mut bytes = Vec::new();
+        futures_lite::AsyncReadExt::read_to_end(&mut reader, &mut bytes)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"\x89PNG async-io");
+    });
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Bus, MockScreenshot, Reply};
+use wlscreenaccess::{blocking::Portal, Error, ScreenshotOptions, WindowIdentifier};
+use zbus::Connection;
+
+/// A blocking client of a backend served from a runtime of its own, dropped
+/// in that order.
+struct Served {
+    portal: Portal,
+    _server: Connection,
+    _runtime: tokio::runtime::Runtime,
+}
+
+fn serve(bus: &Bus, backend: MockScreenshot) -> Served {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(bus.serve_interface(backend));
+    let connection = zbus::blocking::ConnectionBuilder::address(bus.address())
+        .unwrap()
+        .build()
+        .unwrap();
+    Served {
+        portal: Portal::with_connection(&connection).unwrap(),
+        _server: server,
+        _runtime: runtime,
+    }
+}
+
+#[test]
+fn screenshot_blocks_until_the_response() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::screenshot("file:///tmp/Screenshot.png"));
+    let served = serve(&bus, backend);
+
+    let response = served.portal.screenshot().unwrap();
+    assert_eq!(response.url().as_str(), "file:///tmp/Screenshot.png");
+}
+
+#[test]
+fn timeout_closes_the_request() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::Never);
+    let served = serve(&bus, backend.clone());
+
+    let result = served.portal.screenshot_with_timeout(
+        &WindowIdentifier::None,
+        ScreenshotOptions::default(),
+        Duration::from_millis(100),
+    );
+    assert!(matches!(result, Err(Error::Timeout)));
+    // The request is closed from a thread of its own.
+    for _ in 0..50 {
+        if !backend.closed.lock().unwrap().is_empty() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("The request wasn't closed");
+}
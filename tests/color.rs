@@ -0,0 +1,129 @@
+//! Round trips and edge cases of the color conversions, over whole ranges.
+
+use wlscreenaccess::{Hsl, Hsv, InvalidHexColor, RGB};
+
+/// `0.0..=1.0` in `count` even steps.
+fn steps(count: u32) -> impl Iterator<Item = f64> + Clone {
+    (0..=count).map(move |step| f64::from(step) / f64::from(count))
+}
+
+#[test]
+fn hex_round_trips_however_written() {
+    for byte in 0..=255u8 {
+        let lower = format!("{:02x}{:02x}{:02x}", byte, 255 - byte, byte / 2);
+        for hex in [
+            format!("#{}", lower),
+            lower.clone(),
+            lower.to_uppercase(),
+            format!("#{}ff", lower),
+        ] {
+            assert_eq!(RGB::from_hex(&hex).unwrap().to_hex(), format!("#{}", lower));
+        }
+    }
+    for digit in "0123456789abcdefABCDEF".chars() {
+        let short = RGB::from_hex(&digit.to_string().repeat(3)).unwrap();
+        let long = digit.to_ascii_lowercase().to_string().repeat(6);
+        assert_eq!(short.to_hex(), format!("#{}", long));
+    }
+}
+
+#[test]
+fn invalid_hex_is_an_error() {
+    for invalid in [
+        "",
+        "#",
+        "#f",
+        "#ff",
+        "#ffff",
+        "#fffff",
+        "#fffffff",
+        "#fffffffff",
+        "##fff",
+    ] {
+        assert!(RGB::from_hex(invalid).is_err(), "{}", invalid);
+    }
+    assert_eq!(RGB::from_hex(" fff"), Err(InvalidHexColor::Character(' ')));
+    assert_eq!(RGB::from_hex("#+ff"), Err(InvalidHexColor::Character('+')));
+    assert_eq!(RGB::from_hex("#ffé"), Err(InvalidHexColor::Character('é')));
+}
+
+#[test]
+fn integers_round_trip() {
+    // 127.5 is a tie, rounded to the even 128, unlike 0.5 * 255 truncated.
+    let color = RGB {
+        red: 127.5 / 255.0,
+        green: 0.998,
+        blue: f64::NAN,
+    };
+    assert_eq!(color.to_u8(), [128, 254, 0]);
+    for byte in 0..=255 {
+        assert_eq!(RGB::from_u8([byte; 3]).to_u8(), [byte; 3]);
+    }
+    for value in (0..=65535).step_by(7) {
+        assert_eq!(RGB::from_u16([value; 3]).to_u16(), [value; 3]);
+    }
+}
+
+#[test]
+fn linear_light_round_trips() {
+    for red in steps(50) {
+        for green in steps(50) {
+            for blue in steps(50) {
+                let color = RGB { red, green, blue };
+                let back = color.to_linear().to_srgb();
+                assert!(back.approx_eq(&color, 1e-6), "{:?}", color);
+            }
+        }
+    }
+}
+
+#[test]
+fn hsl_and_hsv_round_trip() {
+    for red in steps(20) {
+        for green in steps(20) {
+            for blue in steps(20) {
+                let rgb = RGB { red, green, blue };
+                for back in [rgb.to_hsl().to_rgb(), rgb.to_hsv().to_rgb()] {
+                    assert!(back.approx_eq(&rgb, 1e-12), "{:?}", rgb);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn hues_wrap_around() {
+    let hsl = Hsl {
+        hue: 360.0,
+        saturation: 1.0,
+        lightness: 0.5,
+    };
+    assert_eq!(
+        hsl.to_rgb(),
+        RGB {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0
+        }
+    );
+    let hsv = Hsv {
+        hue: -120.0,
+        saturation: 1.0,
+        value: 1.0,
+    };
+    assert_eq!(
+        hsv.to_rgb(),
+        RGB {
+            red: 0.0,
+            green: 0.0,
+            blue: 1.0
+        }
+    );
+}
+
+#[test]
+fn xterm256_round_trips() {
+    for i in 16..=255 {
+        assert_eq!(RGB::from_xterm256(i).to_xterm256(), i);
+    }
+}
@@ -0,0 +1,843 @@
+//! A private bus with a mock portal backend, to run the request handshakes
+//! against.
+
+// Each test crate uses only part of it.
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, BufReader, Read, Seek, Write},
+    os::fd::{AsRawFd, FromRawFd},
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wlscreenaccess::{ColorResponse, Portal, ScreenshotResponse};
+use zbus::{
+    dbus_interface, fdo,
+    zvariant::{Fd, OwnedObjectPath, OwnedValue, Value},
+    Connection, ConnectionBuilder, Message, MessageBuilder, MessageHeader,
+};
+
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// The token [`MockScreenCast`] answers to Start when asked to persist.
+pub const RESTORE_TOKEN: &str = "f0c3b1e2-restore";
+
+/// A `dbus-daemon` of our own, killed on drop.
+pub struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    /// Starts the daemon.
+    ///
+    /// Panics when it can't be run, rather than passing tests which didn't
+    /// check anything: `dbus-daemon` is needed to run the tests.
+    pub fn spawn() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|err| panic!("Couldn't run dbus-daemon: {}", err));
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Self {
+            daemon,
+            address: address.trim().to_owned(),
+        }
+    }
+
+    /// The address clients connect to, as `DBUS_SESSION_BUS_ADDRESS`.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Connects a client to the bus.
+    pub async fn connect(&self) -> Connection {
+        ConnectionBuilder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Serves `backend` as the portal implementing its interface.
+    pub async fn serve_interface<I: zbus::Interface>(&self, backend: I) -> Connection {
+        ConnectionBuilder::address(self.address.as_str())
+            .unwrap()
+            .name("org.freedesktop.portal.Desktop")
+            .unwrap()
+            .serve_at(PORTAL_PATH, backend)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Serves `backend` as both the ScreenCast and the RemoteDesktop portal.
+    pub async fn serve_remote(&self, backend: MockScreenCast) -> Connection {
+        let remote = MockRemoteDesktop {
+            screencast: backend.clone(),
+        };
+        let clipboard = MockClipboard {
+            calls: backend.calls.clone(),
+            files: Default::default(),
+        };
+        ConnectionBuilder::address(self.address.as_str())
+            .unwrap()
+            .name("org.freedesktop.portal.Desktop")
+            .unwrap()
+            .serve_at(PORTAL_PATH, backend)
+            .unwrap()
+            .serve_at(PORTAL_PATH, remote)
+            .unwrap()
+            .serve_at(PORTAL_PATH, clipboard)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// The path of the request or session made by `sender` with `token`.
+pub fn handle_path(kind: &str, header: &MessageHeader<'_>, token: &str) -> OwnedObjectPath {
+    let sender = header.sender().unwrap().unwrap();
+    let sender = sender.trim_start_matches(':').replace('.', "_");
+    OwnedObjectPath::try_from(format!("{}/{}/{}/{}", PORTAL_PATH, kind, sender, token)).unwrap()
+}
+
+pub fn token(options: &HashMap<String, OwnedValue>, key: &str) -> String {
+    let value: &str = options[key].downcast_ref().unwrap();
+    value.to_owned()
+}
+
+/// Answers the request at `path` with a success and `results`.
+pub async fn respond(
+    connection: &Connection,
+    path: &OwnedObjectPath,
+    results: HashMap<&str, Value<'_>>,
+) {
+    connection
+        .emit_signal(
+            None::<&str>,
+            path,
+            "org.freedesktop.portal.Request",
+            "Response",
+            &(0u32, results),
+        )
+        .await
+        .unwrap();
+}
+
+/// A ScreenCast backend agreeing to everything, sharing `streams`.
+#[derive(Clone)]
+pub struct MockScreenCast {
+    /// The version of the interface implemented.
+    pub version: u32,
+    /// The bits of the source types offered.
+    pub source_types: u32,
+    /// The streams answered to Start.
+    pub streams: Vec<(u32, HashMap<String, OwnedValue>)>,
+    /// The methods called, in order.
+    pub calls: Arc<Mutex<Vec<String>>>,
+    /// The options of the last SelectSources call.
+    pub selected: Arc<Mutex<HashMap<String, OwnedValue>>>,
+    /// The file passed as the PipeWire remote.
+    pub remote: Option<Arc<std::os::fd::OwnedFd>>,
+}
+
+impl Default for MockScreenCast {
+    fn default() -> Self {
+        Self {
+            version: 4,
+            source_types: 3,
+            streams: Vec::new(),
+            calls: Default::default(),
+            selected: Default::default(),
+            remote: None,
+        }
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.ScreenCast")]
+impl MockScreenCast {
+    async fn create_session(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.calls.lock().unwrap().push("CreateSession".to_owned());
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        let session = handle_path("session", &header, &token(&options, "session_handle_token"));
+        let mock = MockSession {
+            calls: self.calls.clone(),
+        };
+        connection.object_server().at(&session, mock).await.unwrap();
+        let mut results = HashMap::new();
+        results.insert("session_handle", Value::from(session.as_str()));
+        respond(connection, &request, results).await;
+        Ok(request)
+    }
+
+    async fn select_sources(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _session_handle: OwnedObjectPath,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.calls.lock().unwrap().push("SelectSources".to_owned());
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        *self.selected.lock().unwrap() = options;
+        respond(connection, &request, HashMap::new()).await;
+        Ok(request)
+    }
+
+    async fn start(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _session_handle: OwnedObjectPath,
+        _parent_window: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.calls.lock().unwrap().push("Start".to_owned());
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        let mut results = HashMap::new();
+        results.insert("streams", Value::from(self.streams.clone()));
+        if self.selected.lock().unwrap().contains_key("persist_mode") {
+            results.insert("restore_token", Value::from(RESTORE_TOKEN));
+        }
+        respond(connection, &request, results).await;
+        Ok(request)
+    }
+
+    #[dbus_interface(name = "OpenPipeWireRemote")]
+    fn open_pipewire_remote(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<Fd> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push("OpenPipeWireRemote".to_owned());
+        let remote = self
+            .remote
+            .as_ref()
+            .ok_or_else(|| fdo::Error::NotSupported("No remote".to_owned()))?;
+        Ok(Fd::from(remote.as_raw_fd()))
+    }
+
+    #[dbus_interface(property)]
+    fn available_source_types(&self) -> u32 {
+        self.source_types
+    }
+
+    #[dbus_interface(property)]
+    fn available_cursor_modes(&self) -> u32 {
+        5
+    }
+
+    #[dbus_interface(property, name = "version")]
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// A RemoteDesktop backend agreeing to everything, sharing the state of a
+/// [`MockScreenCast`] so sources can be selected on its sessions.
+pub struct MockRemoteDesktop {
+    screencast: MockScreenCast,
+}
+
+impl MockRemoteDesktop {
+    fn record(&self, call: String) {
+        self.screencast.calls.lock().unwrap().push(call);
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.RemoteDesktop")]
+impl MockRemoteDesktop {
+    async fn create_session(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        let path = self
+            .screencast
+            .create_session(header, connection, options)
+            .await?;
+        let mut calls = self.screencast.calls.lock().unwrap();
+        *calls.last_mut().unwrap() = "RemoteDesktop.CreateSession".to_owned();
+        Ok(path)
+    }
+
+    async fn select_devices(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _session_handle: OwnedObjectPath,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        let types: u32 = *options["types"].downcast_ref().unwrap();
+        self.record(format!("SelectDevices {}", types));
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        respond(connection, &request, HashMap::new()).await;
+        Ok(request)
+    }
+
+    async fn start(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _session_handle: OwnedObjectPath,
+        _parent_window: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.record("RemoteDesktop.Start".to_owned());
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        let mut results = HashMap::new();
+        results.insert("devices", Value::from(3u32));
+        if self
+            .screencast
+            .calls
+            .lock()
+            .unwrap()
+            .contains(&"SelectSources".to_owned())
+        {
+            results.insert("streams", Value::from(self.screencast.streams.clone()));
+        }
+        respond(connection, &request, results).await;
+        Ok(request)
+    }
+
+    fn notify_pointer_motion(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+        dx: f64,
+        dy: f64,
+    ) {
+        self.record(format!("NotifyPointerMotion {} {}", dx, dy));
+    }
+
+    fn notify_pointer_button(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+        button: i32,
+        state: u32,
+    ) {
+        self.record(format!("NotifyPointerButton {:#x} {}", button, state));
+    }
+
+    fn notify_pointer_axis(
+        &self,
+        _session_handle: OwnedObjectPath,
+        options: HashMap<String, OwnedValue>,
+        dx: f64,
+        dy: f64,
+    ) {
+        let finish = options.contains_key("finish");
+        self.record(format!("NotifyPointerAxis {} {} {}", dx, dy, finish));
+    }
+
+    fn notify_pointer_axis_discrete(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+        axis: u32,
+        steps: i32,
+    ) {
+        self.record(format!("NotifyPointerAxisDiscrete {} {}", axis, steps));
+    }
+
+    fn notify_keyboard_keycode(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+        keycode: i32,
+        state: u32,
+    ) {
+        self.record(format!("NotifyKeyboardKeycode {} {}", keycode, state));
+    }
+
+    #[dbus_interface(property)]
+    fn available_device_types(&self) -> u32 {
+        7
+    }
+
+    #[dbus_interface(property, name = "version")]
+    fn version(&self) -> u32 {
+        2
+    }
+}
+
+/// The session objects made by [`MockScreenCast`], sharing its calls.
+pub struct MockSession {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Session")]
+impl MockSession {
+    fn close(&self) {
+        self.calls.lock().unwrap().push("Close".to_owned());
+    }
+}
+
+/// The data [`MockClipboard`] has on the clipboard.
+pub const REMOTE_CLIPBOARD: &str = "copied remotely";
+
+/// A Clipboard backend asking for the data as soon as it's offered.
+pub struct MockClipboard {
+    calls: Arc<Mutex<Vec<String>>>,
+    /// The files passed to the client, by serial, 0 for the ones read.
+    files: Mutex<HashMap<u32, std::fs::File>>,
+}
+
+impl MockClipboard {
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Clipboard")]
+impl MockClipboard {
+    fn request_clipboard(
+        &self,
+        _session_handle: OwnedObjectPath,
+        _options: HashMap<String, OwnedValue>,
+    ) {
+        self.record("RequestClipboard".to_owned());
+    }
+
+    async fn set_selection(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        session_handle: OwnedObjectPath,
+        options: HashMap<String, OwnedValue>,
+    ) {
+        let mime_types = Vec::<String>::try_from(options["mime_types"].clone()).unwrap();
+        self.record(format!("SetSelection {}", mime_types.join(",")));
+        let mut owner = HashMap::new();
+        owner.insert("mime_types", Value::from(mime_types.clone()));
+        owner.insert("session_is_owner", Value::from(true));
+        let clipboard = "org.freedesktop.portal.Clipboard";
+        connection
+            .emit_signal(
+                None::<&str>,
+                PORTAL_PATH,
+                clipboard,
+                "SelectionOwnerChanged",
+                &(&session_handle, owner),
+            )
+            .await
+            .unwrap();
+        connection
+            .emit_signal(
+                None::<&str>,
+                PORTAL_PATH,
+                clipboard,
+                "SelectionTransfer",
+                &(&session_handle, &mime_types[0], 7u32),
+            )
+            .await
+            .unwrap();
+    }
+
+    fn selection_write(&self, _session_handle: OwnedObjectPath, serial: u32) -> Fd {
+        let file = memfd();
+        let fd = Fd::from(file.as_raw_fd());
+        self.files.lock().unwrap().insert(serial, file);
+        fd
+    }
+
+    fn selection_write_done(&self, _session_handle: OwnedObjectPath, serial: u32, success: bool) {
+        let mut file = self.files.lock().unwrap().remove(&serial).unwrap();
+        let mut written = String::new();
+        file.rewind().unwrap();
+        file.read_to_string(&mut written).unwrap();
+        self.record(format!(
+            "SelectionWriteDone {} {} {}",
+            serial, success, written
+        ));
+    }
+
+    fn selection_read(&self, _session_handle: OwnedObjectPath, mime_type: String) -> Fd {
+        self.record(format!("SelectionRead {}", mime_type));
+        let mut file = memfd();
+        file.write_all(REMOTE_CLIPBOARD.as_bytes()).unwrap();
+        file.rewind().unwrap();
+        let fd = Fd::from(file.as_raw_fd());
+        self.files.lock().unwrap().insert(0, file);
+        fd
+    }
+}
+
+/// Closes the session at `path` from the backend's side.
+pub async fn close_session(server: &Connection, path: &OwnedObjectPath) {
+    let details: HashMap<&str, Value<'_>> = HashMap::new();
+    server
+        .emit_signal(
+            None::<&str>,
+            path,
+            "org.freedesktop.portal.Session",
+            "Closed",
+            &(details,),
+        )
+        .await
+        .unwrap();
+}
+
+/// An anonymous file, to pass as a file descriptor.
+pub fn memfd() -> std::fs::File {
+    // SAFETY: a fresh descriptor, owned by the `File` from then on.
+    unsafe {
+        let fd = libc::memfd_create(c"remote".as_ptr(), 0);
+        assert!(fd >= 0);
+        std::fs::File::from_raw_fd(fd)
+    }
+}
+
+/// How [`MockScreenshot`] answers its requests.
+#[derive(Clone, Debug)]
+pub enum Reply {
+    /// A success with these results.
+    Success(HashMap<String, OwnedValue>),
+    /// The user dismissing the dialog.
+    Cancelled,
+    /// A failure for another reason, explained by this message.
+    Failed(String),
+    /// The reply, once the time is up.
+    Delayed(Duration, Box<Reply>),
+    /// The first reply, then the second to the same request, as some backends
+    /// answer twice.
+    Duplicated(Box<Reply>, Box<Reply>),
+    /// A success whose results aren't a vardict.
+    Malformed,
+    /// Nothing, the request stays open until closed.
+    Never,
+    /// The next of these recorded responses, as they were received.
+    Replay(Arc<Mutex<VecDeque<Recorded>>>),
+}
+
+impl Reply {
+    /// A successful screenshot at `uri`.
+    pub fn screenshot(uri: &str) -> Self {
+        let mut results = HashMap::new();
+        results.insert("uri".to_owned(), OwnedValue::from(Value::from(uri)));
+        Self::Success(results)
+    }
+
+    /// A successfully picked color.
+    pub fn color(color: [f64; 3]) -> Self {
+        let color = Value::from((color[0], color[1], color[2]));
+        let mut results = HashMap::new();
+        results.insert("color".to_owned(), OwnedValue::from(color));
+        Self::Success(results)
+    }
+
+    /// The responses of a recording, in order.
+    pub fn replay(recording: &[Recorded]) -> Self {
+        let responses = recording
+            .iter()
+            .filter(|entry| entry.kind == "response")
+            .cloned();
+        Self::Replay(Arc::new(Mutex::new(responses.collect())))
+    }
+}
+
+/// A Screenshot backend answering both Screenshot and PickColor with `reply`.
+///
+/// Each request object is served until answered, recording Close calls.
+#[derive(Clone)]
+pub struct MockScreenshot {
+    pub reply: Reply,
+    /// The version of the interface implemented.
+    pub version: u32,
+    /// The options of the requests, in order.
+    pub options: Arc<Mutex<Vec<HashMap<String, OwnedValue>>>>,
+    /// The unique names the requests were sent from, in order.
+    pub senders: Arc<Mutex<Vec<String>>>,
+    /// The requests closed.
+    pub closed: Arc<Mutex<Vec<OwnedObjectPath>>>,
+}
+
+impl MockScreenshot {
+    pub fn new(reply: Reply) -> Self {
+        Self {
+            reply,
+            version: 2,
+            options: Default::default(),
+            senders: Default::default(),
+            closed: Default::default(),
+        }
+    }
+
+    /// Serves the request object and answers it as `reply` says.
+    async fn request(
+        &self,
+        header: &MessageHeader<'_>,
+        connection: &Connection,
+        options: HashMap<String, OwnedValue>,
+    ) -> OwnedObjectPath {
+        let path = handle_path("request", header, &token(&options, "handle_token"));
+        self.options.lock().unwrap().push(options);
+        let sender = header.sender().unwrap().unwrap().to_string();
+        self.senders.lock().unwrap().push(sender);
+        let request = MockRequest {
+            path: path.clone(),
+            closed: self.closed.clone(),
+        };
+        connection.object_server().at(&path, request).await.unwrap();
+        let (connection, reply, answered) = (connection.clone(), self.reply.clone(), path.clone());
+        // Answered after the method returned, like the portal does. From a
+        // thread of its own, as zbus may run on either runtime.
+        std::thread::spawn(move || {
+            async_io::block_on(async move { answer(&connection, &answered, reply).await })
+        });
+        path
+    }
+}
+
+async fn answer(connection: &Connection, path: &OwnedObjectPath, mut reply: Reply) {
+    while let Reply::Delayed(delay, delayed) = reply {
+        async_io::Timer::after(delay).await;
+        reply = *delayed;
+    }
+    let interface = "org.freedesktop.portal.Request";
+    let sent = match reply {
+        Reply::Success(results) => {
+            connection
+                .emit_signal(None::<&str>, path, interface, "Response", &(0u32, results))
+                .await
+        }
+        Reply::Cancelled => {
+            let results: HashMap<&str, Value<'_>> = HashMap::new();
+            connection
+                .emit_signal(None::<&str>, path, interface, "Response", &(1u32, results))
+                .await
+        }
+        Reply::Failed(message) => {
+            let results = HashMap::from([("error", Value::from(message))]);
+            connection
+                .emit_signal(None::<&str>, path, interface, "Response", &(2u32, results))
+                .await
+        }
+        Reply::Malformed => {
+            connection
+                .emit_signal(
+                    None::<&str>,
+                    path,
+                    interface,
+                    "Response",
+                    &(0u32, "garbage"),
+                )
+                .await
+        }
+        Reply::Replay(responses) => {
+            let recorded = responses.lock().unwrap().pop_front().unwrap();
+            let message = recorded.message(path);
+            connection.send_message(message).await.map(|_| ())
+        }
+        Reply::Duplicated(first, second) => {
+            Box::pin(answer(connection, path, *first)).await;
+            Box::pin(answer(connection, path, *second)).await;
+            return;
+        }
+        Reply::Never | Reply::Delayed(..) => return,
+    };
+    sent.unwrap();
+    let _ = connection
+        .object_server()
+        .remove::<MockRequest, _>(path)
+        .await;
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Screenshot")]
+impl MockScreenshot {
+    async fn screenshot(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _parent_window: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> OwnedObjectPath {
+        self.request(&header, connection, options).await
+    }
+
+    async fn pick_color(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _parent_window: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> OwnedObjectPath {
+        self.request(&header, connection, options).await
+    }
+
+    #[dbus_interface(property, name = "version")]
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// A request object of [`MockScreenshot`].
+pub struct MockRequest {
+    path: OwnedObjectPath,
+    closed: Arc<Mutex<Vec<OwnedObjectPath>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Request")]
+impl MockRequest {
+    fn close(&self) {
+        self.closed.lock().unwrap().push(self.path.clone());
+    }
+}
+
+/// A line of a recording made with `WLSCREENACCESS_RECORD`.
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    /// `call` or `response`.
+    pub kind: String,
+    /// The method called, empty for responses.
+    pub method: String,
+    /// The handle token of the call, empty for responses.
+    pub token: String,
+    /// The request path of the response, empty for calls.
+    pub path: String,
+    /// The byte order of the body, `l` or `B`.
+    pub endian: String,
+    pub signature: String,
+    pub body: Vec<u8>,
+}
+
+impl Recorded {
+    /// The Response signal as received, sent on the request at `path`.
+    ///
+    /// zbus only builds messages in our byte order, so the body has to be in
+    /// it too.
+    pub fn message(&self, path: &OwnedObjectPath) -> Message {
+        let native = if cfg!(target_endian = "little") {
+            "l"
+        } else {
+            "B"
+        };
+        assert_eq!(self.endian, native, "recorded in another byte order");
+        let builder =
+            MessageBuilder::signal(path, "org.freedesktop.portal.Request", "Response").unwrap();
+        // SAFETY: no file descriptors are passed, so none can be left dangling.
+        unsafe { builder.build_raw_body(&self.body, self.signature.as_str(), vec![]) }.unwrap()
+    }
+
+    /// The Response signal as received, on the recorded request path.
+    pub fn response(&self) -> Message {
+        self.message(&OwnedObjectPath::try_from(self.path.as_str()).unwrap())
+    }
+}
+
+/// Takes a screenshot and picks a color, each from a backend answering with
+/// `screenshot` and `color`.
+pub async fn session(screenshot: Reply, color: Reply) -> (ScreenshotResponse, ColorResponse) {
+    let (first, second) = (Bus::spawn(), Bus::spawn());
+    let _server = first.serve_interface(MockScreenshot::new(screenshot)).await;
+    let portal = Portal::connect_to_address(first.address()).await.unwrap();
+    let screenshot = portal.screenshot().await.unwrap();
+    let _server = second.serve_interface(MockScreenshot::new(color)).await;
+    let portal = Portal::connect_to_address(second.address()).await.unwrap();
+    (screenshot, portal.pick_color().await.unwrap())
+}
+
+/// Replays the responses of `recording` through the request handling.
+pub async fn replay(recording: &[Recorded]) -> (ScreenshotResponse, ColorResponse) {
+    let responses = Reply::replay(recording);
+    session(responses.clone(), responses).await
+}
+
+/// Reads the recording at `path`.
+pub fn load_recording(path: &Path) -> Vec<Recorded> {
+    let recording = std::fs::read_to_string(path).unwrap();
+    recording
+        .lines()
+        .map(|line| {
+            let mut fields = parse_line(line);
+            let mut take = |key: &str| fields.remove(key).unwrap_or_default();
+            let body = take("body");
+            Recorded {
+                kind: take("kind"),
+                method: take("method"),
+                token: take("token"),
+                path: take("path"),
+                endian: take("endian"),
+                signature: take("signature"),
+                body: (0..body.len())
+                    .step_by(2)
+                    .map(|at| u8::from_str_radix(&body[at..at + 2], 16).unwrap())
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// The fields of a flat JSON object of strings and numbers, numbers kept as
+/// their text.
+fn parse_line(line: &str) -> HashMap<String, String> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = HashMap::new();
+    assert_eq!(chars.next(), Some('{'));
+    let string = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+        assert_eq!(chars.next(), Some('"'));
+        let mut string = String::new();
+        loop {
+            match chars.next().unwrap() {
+                '"' => return string,
+                '\\' => match chars.next().unwrap() {
+                    'n' => string.push('\n'),
+                    'u' => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&code, 16).unwrap();
+                        string.push(char::from_u32(code).unwrap());
+                    }
+                    c => string.push(c),
+                },
+                c => string.push(c),
+            }
+        }
+    };
+    while chars.peek() != Some(&'}') {
+        let key = string(&mut chars);
+        assert_eq!(chars.next(), Some(':'));
+        let value = match chars.peek() {
+            Some('"') => string(&mut chars),
+            _ => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                    number.push(c);
+                }
+                number
+            }
+        };
+        fields.insert(key, value);
+        if chars.peek() == Some(&',') {
+            chars.next();
+        }
+    }
+    fields
+}
@@ -0,0 +1,68 @@
+//! The connection shared by the free functions. Its own test crate, as it
+//! points the whole process at the private bus.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::Bus;
+use wlscreenaccess::{
+    reset_connection,
+    settings::{read_color_scheme, ColorScheme},
+};
+use zbus::{dbus_interface, zvariant::OwnedValue, MessageHeader};
+
+/// A Settings backend recording who reads it.
+#[derive(Clone, Default)]
+struct MockSettings {
+    senders: Arc<Mutex<Vec<String>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Settings")]
+impl MockSettings {
+    async fn read_one(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        _namespace: String,
+        _key: String,
+    ) -> OwnedValue {
+        let sender = header.sender().unwrap().unwrap().to_string();
+        self.senders.lock().unwrap().push(sender);
+        OwnedValue::from(1u32)
+    }
+}
+
+impl MockSettings {
+    fn senders(&self) -> Vec<String> {
+        self.senders.lock().unwrap().clone()
+    }
+}
+
+#[tokio::test]
+async fn free_functions_share_and_restore_the_connection() {
+    let bus = Bus::spawn();
+    let backend = MockSettings::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", bus.address());
+
+    for _ in 0..3 {
+        assert_eq!(read_color_scheme().await.unwrap(), ColorScheme::PreferDark);
+    }
+    let senders = backend.senders();
+    assert!(senders.iter().all(|sender| sender == &senders[0]));
+
+    reset_connection().await;
+    read_color_scheme().await.unwrap();
+    assert_ne!(backend.senders()[3], senders[0]);
+
+    // The bus going away fails the call in flight, and the next one connects
+    // to the new bus.
+    drop(bus);
+    let bus = Bus::spawn();
+    let backend = MockSettings::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", bus.address());
+    assert!(read_color_scheme().await.is_err());
+    assert_eq!(read_color_scheme().await.unwrap(), ColorScheme::PreferDark);
+    assert_eq!(backend.senders().len(), 1);
+}
@@ -0,0 +1,49 @@
+//! Memory taken by encoding a screenshot, tracked by the allocator of this
+//! test binary alone.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use wlscreenaccess::ScreenshotResponse;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Tracks the bytes allocated, and the most at once.
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[test]
+fn large_file_is_encoded_without_reading_it_all_first() {
+    let path = std::env::temp_dir().join(format!("wlsa-data-uri-{}.png", std::process::id()));
+    let response = ScreenshotResponse::new(url::Url::from_file_path(&path).unwrap());
+    let mut large = b"\x89PNG\r\n\x1a\n".to_vec();
+    large.resize(6 << 20, 0xab);
+    std::fs::write(&path, &large).unwrap();
+    drop(large);
+
+    let before = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let encoded = response.to_base64().unwrap();
+    assert_eq!(encoded.len(), 8 << 20);
+    // The encoded copy, and a chunk of the file at a time.
+    assert!(PEAK.load(Ordering::SeqCst) - before < encoded.len() + (256 << 10));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,18 @@
+//! Clustering the colors of a capture as large as a 4K screen.
+
+use wlscreenaccess::dominant_colors;
+
+#[test]
+fn large_capture_is_clustered_the_same_each_time() {
+    let noise = image::RgbImage::from_fn(3840, 2160, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    });
+    let noise = image::DynamicImage::ImageRgb8(noise);
+
+    let colors = dominant_colors(&noise, 5);
+    assert_eq!(colors.len(), 5);
+    let weights: f32 = colors.iter().map(|(_, weight)| weight).sum();
+    assert!((weights - 1.0).abs() < 1e-4, "{}", weights);
+    assert_eq!(colors, dominant_colors(&noise, 5));
+    assert!(dominant_colors(&noise, 0).is_empty());
+}
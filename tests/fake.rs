@@ -0,0 +1,91 @@
+//! Testing an application the way its own CI would, without a portal.
+//!
+//! The fixtures are global to the process, so everything runs in one test.
+
+mod common;
+
+use std::path::Path;
+
+use common::Bus;
+use wlscreenaccess::{
+    fake, Error, Portal, ScreenshotOptions, ScreenshotResponse, WindowIdentifier,
+};
+
+/// What an application might do: take a screenshot and keep its bytes along
+/// with the color the user picked.
+async fn capture() -> Result<(Vec<u8>, String), Error> {
+    let screenshot = wlscreenaccess::screenshot().await?;
+    let bytes = screenshot.read_bytes().await?;
+    let color = wlscreenaccess::pick_color_hex().await?;
+    Ok((bytes, color))
+}
+
+#[test]
+fn requests_are_answered_without_a_portal() {
+    // Nothing may connect: there's no bus at this address.
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent/bus");
+    let fixture = std::env::temp_dir().join(format!("wlsa-fake-{}.png", std::process::id()));
+    std::fs::write(&fixture, b"\x89PNG fixture").unwrap();
+
+    futures_lite::future::block_on(async {
+        // The feature alone changes nothing.
+        assert!(!fake::is_enabled());
+        assert!(matches!(capture().await, Err(Error::Zbus(_))));
+
+        fake::enable();
+        fake::set_screenshot_path(&fixture);
+        fake::set_color([0.2, 0.4, 1.0]);
+        let (bytes, color) = capture().await.unwrap();
+        assert_eq!(bytes, b"\x89PNG fixture");
+        assert_eq!(color, "#3366ff");
+        #[cfg(feature = "image")]
+        {
+            let rect = wlscreenaccess::Rect {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            };
+            let color = wlscreenaccess::pick_color_fallback(rect).await.unwrap();
+            assert_eq!(color.to_hex(), "#3366ff");
+        }
+        let screenshot = wlscreenaccess::screenshot_interactive().await.unwrap();
+        assert_eq!(screenshot.to_path().unwrap(), fixture);
+
+        // A `Portal` answers too, requests sent without waiting included,
+        // without asking the portal: there's none on this bus.
+        {
+            let bus = Bus::spawn();
+            let portal = Portal::connect_to_address(bus.address()).await.unwrap();
+            let request = portal
+                .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+                .await
+                .unwrap();
+            let screenshot = request
+                .receive_response::<ScreenshotResponse>()
+                .await
+                .unwrap();
+            assert_eq!(screenshot.to_path().unwrap(), fixture);
+            let (_, color) = portal
+                .start_pick_color(&WindowIdentifier::None)
+                .await
+                .unwrap();
+            assert_eq!(color.await.unwrap().to_rgb().to_hex(), "#3366ff");
+        }
+
+        fake::clear();
+        let err = capture().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The request failed: no fake screenshot was registered"
+        );
+        fake::set_screenshot_path(Path::new("relative.png"));
+        let screenshot = wlscreenaccess::screenshot().await.unwrap();
+        assert_eq!(
+            screenshot.to_path().unwrap(),
+            std::env::current_dir().unwrap().join("relative.png")
+        );
+        assert!(Portal::new().await.is_err());
+    });
+    std::fs::remove_file(fixture).unwrap();
+}
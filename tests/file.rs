@@ -0,0 +1,180 @@
+//! Saving, moving and hashing the screenshot's file.
+
+use std::{
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use wlscreenaccess::{Error, Sandbox, ScreenshotResponse};
+
+/// A fresh directory in `parent` for the test `name`.
+fn scratch(parent: &Path, name: &str) -> PathBuf {
+    let dir = parent.join(format!("wlsa-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A screenshot at `path` holding `bytes`.
+fn screenshot(path: &Path, bytes: &[u8]) -> ScreenshotResponse {
+    std::fs::write(path, bytes).unwrap();
+    ScreenshotResponse::new(url::Url::from_file_path(path).unwrap())
+}
+
+#[test]
+fn saving_over_itself_keeps_the_screenshot() {
+    let dir = scratch(&std::env::temp_dir(), "save-same");
+    let path = dir.join("shot.png");
+    let response = screenshot(&path, b"\x89PNG");
+    let linked = dir.join("link.png");
+    std::os::unix::fs::symlink(&path, &linked).unwrap();
+
+    for dest in [&path, &dir.join("./shot.png"), &linked] {
+        let err = response.save_to(dest, true).unwrap_err();
+        assert!(matches!(err, Error::SameFile(_)), "{:?}", err);
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), b"\x89PNG");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn moving_across_filesystems_copies() {
+    // `/dev/shm` is a tmpfs on most systems, skip where it's not separate.
+    let (home, other) = (std::env::temp_dir(), Path::new("/dev/shm"));
+    let device = |path: &Path| std::fs::metadata(path).map(|metadata| metadata.dev());
+    match (device(&home), device(other)) {
+        (Ok(home), Ok(other)) if home != other => {}
+        _ => return,
+    }
+    let (from, to) = (scratch(&home, "move-from"), scratch(other, "move-to"));
+
+    for overwrite in [false, true] {
+        let source = from.join("shot.png");
+        let response = screenshot(&source, b"\x89PNG moved");
+        let dest = to.join(format!("shot-{}.png", overwrite));
+        assert_eq!(response.move_to(&dest, overwrite).unwrap(), dest);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"\x89PNG moved");
+        assert!(!source.exists());
+    }
+
+    // Without `overwrite`, the copy doesn't replace anything either.
+    let source = from.join("shot.png");
+    let response = screenshot(&source, b"\x89PNG new");
+    let dest = to.join("shot-false.png");
+    let err = response.move_to(&dest, false).unwrap_err();
+    assert!(matches!(err, Error::AlreadyExists(_)), "{:?}", err);
+    assert_eq!(std::fs::read(&dest).unwrap(), b"\x89PNG moved");
+    assert!(source.exists());
+
+    std::fs::remove_dir_all(from).unwrap();
+    std::fs::remove_dir_all(to).unwrap();
+}
+
+#[test]
+fn removing_a_gone_source_is_fine() {
+    let dir = scratch(Path::new("/tmp"), "remove-gone");
+    let path = dir.join("shot.png");
+    let response = screenshot(&path, b"\x89PNG");
+    std::fs::remove_file(&path).unwrap();
+    response.remove_source().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn removing_elsewhere_is_refused() {
+    // Anywhere out of the home directory and `/tmp` does, skip where
+    // `/dev/shm` isn't.
+    let other = Path::new("/dev/shm");
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if !other.is_dir() || home.is_some_and(|home| other.starts_with(home)) {
+        return;
+    }
+    let dir = scratch(other, "remove-elsewhere");
+    let path = dir.join("shot.png");
+    let response = screenshot(&path, b"\x89PNG");
+    let err = response.remove_source().unwrap_err();
+    assert!(
+        matches!(&err, Error::UnsafeRemoval(refused) if *refused == path),
+        "{:?}",
+        err
+    );
+    assert!(path.exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The hash of `response` in hex.
+fn hex(response: &ScreenshotResponse) -> String {
+    let hash = response.content_hash().unwrap();
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn content_hash_is_sha256() {
+    let dir = scratch(&std::env::temp_dir(), "hash");
+    let path = dir.join("shot.png");
+    // The test vectors of FIPS 180-2, the last spanning many chunks.
+    for (bytes, expected) in [
+        (
+            b"".to_vec(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            b"abc".to_vec(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".to_vec(),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+        (
+            vec![b'a'; 1_000_000],
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+        ),
+    ] {
+        assert_eq!(hex(&screenshot(&path, &bytes)), expected);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sandboxed_host_paths_open_the_exported_document() {
+    let dir = scratch(&std::env::temp_dir(), "sandbox");
+    let documents = dir.join("doc");
+    std::fs::create_dir_all(documents.join("a1b2c3")).unwrap();
+    let exported = documents.join("a1b2c3").join("shot.png");
+    let sandbox = Sandbox::flatpak(&documents);
+
+    // The portal sent the host path, which isn't there in the sandbox.
+    std::fs::write(&exported, b"\x89PNG").unwrap();
+    let host = ScreenshotResponse::new(url::Url::from_file_path("/home/me/shot.png").unwrap());
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut host.open_with(&sandbox).unwrap(), &mut bytes).unwrap();
+    assert_eq!(bytes, b"\x89PNG");
+
+    // The portal sent the document's path.
+    let document = ScreenshotResponse::new(url::Url::from_file_path(&exported).unwrap());
+    assert!(document.open_with(&sandbox).is_ok());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unreadable_paths_in_a_sandbox_are_access_errors() {
+    let dir = scratch(&std::env::temp_dir(), "sandbox-denied");
+    let documents = dir.join("doc");
+    std::fs::create_dir_all(&documents).unwrap();
+    let host = ScreenshotResponse::new(url::Url::from_file_path("/home/me/shot.png").unwrap());
+
+    let err = host.open_with(&Sandbox::flatpak(&documents)).unwrap_err();
+    assert!(matches!(err, Error::SandboxAccess { .. }), "{:?}", err);
+    let err = host.open_with(&Sandbox::NONE).unwrap_err();
+    assert!(matches!(err, Error::File { .. }), "{:?}", err);
+
+    // Missing documents aren't the sandbox's fault.
+    let gone = documents.join("d4e5f6").join("shot.png");
+    let document = ScreenshotResponse::new(url::Url::from_file_path(gone).unwrap());
+    let err = document
+        .open_with(&Sandbox::flatpak(&documents))
+        .unwrap_err();
+    assert!(matches!(err, Error::File { .. }), "{:?}", err);
+    std::fs::remove_dir_all(dir).unwrap();
+}
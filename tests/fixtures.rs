@@ -0,0 +1,119 @@
+//! Response signal bodies in the layouts the backends send, decoded as the
+//! crate decodes live responses.
+//!
+//! Each `tests/fixtures/*.response` file holds the body signature on its
+//! first line, followed by the body in the D-Bus wire format. Record new
+//! ones with `cargo run --example capture_response`.
+
+use std::path::Path;
+
+use wlscreenaccess::{
+    response::{BasicResponse, Response},
+    ColorResponse, Error, ResponseError, ScreenshotResponse, RGB,
+};
+use zbus::{Message, MessageBuilder};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn load(path: &Path) -> Message {
+    let bytes = std::fs::read(path).unwrap();
+    let newline = bytes.iter().position(|byte| *byte == b'\n').unwrap();
+    let signature = std::str::from_utf8(&bytes[..newline]).unwrap();
+    let builder = MessageBuilder::signal(
+        "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+        "org.freedesktop.portal.Request",
+        "Response",
+    )
+    .unwrap();
+    // SAFETY: no file descriptors are passed, so none can be left dangling.
+    unsafe { builder.build_raw_body(&bytes[newline + 1..], signature, vec![]) }.unwrap()
+}
+
+fn fixture(name: &str) -> Message {
+    load(&Path::new(FIXTURES).join(name).with_extension("response"))
+}
+
+#[test]
+fn every_fixture_decodes() {
+    for entry in std::fs::read_dir(FIXTURES).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "response")
+        {
+            let message = load(&path);
+            let response = Response::<BasicResponse>::from_message(&message);
+            assert!(response.is_ok(), "{}: {:?}", path.display(), response);
+        }
+    }
+}
+
+#[test]
+fn screenshot_is_decoded() {
+    let response = Response::<ScreenshotResponse>::from_message(&fixture("screenshot")).unwrap();
+    assert_eq!(
+        response.ok().unwrap().url().path(),
+        "/home/user/Pictures/Screenshots/Screenshot%20from%202024-05-01%2012-30-00.png"
+    );
+}
+
+#[test]
+fn screenshot_extra_keys_are_skipped() {
+    let message = fixture("screenshot-extra-keys");
+    let response = Response::<ScreenshotResponse>::from_message(&message).unwrap();
+    assert_eq!(
+        response.ok().unwrap().url().path(),
+        "/home/user/Pictures/Screenshot_20240501_123000.png"
+    );
+}
+
+#[test]
+fn pick_color_is_decoded() {
+    let response = Response::<ColorResponse>::from_message(&fixture("pick-color")).unwrap();
+    assert_eq!(response.ok().unwrap().to_rgb(), RGB::from([0.2, 0.4, 0.6]));
+}
+
+#[test]
+fn pick_color_extra_keys_are_skipped() {
+    let message = fixture("pick-color-extra-keys");
+    let response = Response::<ColorResponse>::from_message(&message).unwrap();
+    assert_eq!(response.ok().unwrap().to_rgb(), RGB::from([0.2, 0.4, 0.6]));
+}
+
+#[test]
+fn cancellation_is_decoded() {
+    for name in ["cancelled", "cancelled-without-results"] {
+        let response = Response::<ScreenshotResponse>::from_message(&fixture(name)).unwrap();
+        assert!(response.is_cancelled(), "{}", name);
+    }
+}
+
+#[test]
+fn other_failure_keeps_its_message() {
+    let response = Response::<ColorResponse>::from_message(&fixture("other-with-error")).unwrap();
+    let err = response.into_result().unwrap_err();
+    assert!(matches!(err, ResponseError::Other(_)));
+    assert_eq!(err.message(), Some("No screenshot source available"));
+}
+
+#[test]
+fn success_without_results_is_empty() {
+    let message = fixture("success-without-results");
+    assert!(matches!(
+        Response::<ScreenshotResponse>::from_message(&message),
+        Err(Error::EmptyResults)
+    ));
+    let basic = Response::<BasicResponse>::from_message(&message).unwrap();
+    assert!(basic.ok().unwrap().results().is_empty());
+}
+
+#[test]
+fn mistyped_uri_is_an_error() {
+    let message = fixture("screenshot-uri-not-a-string");
+    let err = Response::<ScreenshotResponse>::from_message(&message).unwrap_err();
+    assert!(
+        matches!(&err, Error::InvalidField { field: "uri", expected, .. } if expected == "s"),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,258 @@
+//! Decodes generated Response bodies, checking that junk is an error rather
+//! than a panic.
+//!
+//! The cases come from a fixed seed so failures reproduce; set
+//! `WLSCREENACCESS_FUZZ_SEED` to try others.
+
+use std::{collections::HashMap, panic::AssertUnwindSafe};
+
+use wlscreenaccess::{response::Response, ColorResponse, Error, ScreenshotResponse};
+use zbus::{
+    zvariant::{OwnedValue, StructureBuilder, Value},
+    Message,
+};
+
+const CASES: usize = 2000;
+
+/// Deeper than D-Bus allows, so too deep bodies are covered as well.
+const MAX_DEPTH: u32 = 40;
+
+/// SplitMix64, as in the crate, to not depend on a fuzzing framework.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    fn f64(&mut self) -> f64 {
+        let random = f64::from_bits(self.next());
+        *self.pick(&[
+            0.0,
+            0.5,
+            1.0,
+            -1.0,
+            1e300,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            random,
+        ])
+    }
+
+    fn string(&mut self) -> String {
+        match self.below(4) {
+            0 => String::new(),
+            1 => (*self.pick(&[
+                "file:///tmp/Screenshot.png",
+                "file:///tmp/Screenshot%201.png",
+                "file://",
+                "https://example.org/shot.png",
+                "not a uri",
+                "file:///\u{fffd}",
+            ]))
+            .to_owned(),
+            _ => (0..self.below(24))
+                .map(|_| char::from_u32(self.below(0x800) as u32).unwrap_or('?'))
+                .collect(),
+        }
+    }
+
+    fn key(&mut self) -> String {
+        match self.below(3) {
+            0 => self.string(),
+            _ => (*self.pick(&["uri", "color", "error", "message", "handle_token"])).to_owned(),
+        }
+    }
+
+    fn code(&mut self) -> u32 {
+        match self.below(3) {
+            0 => self.next() as u32,
+            _ => *self.pick(&[0, 0, 1, 2, 3, u32::MAX]),
+        }
+    }
+
+    fn value(&mut self, depth: u32) -> Value<'static> {
+        let leaf = depth >= MAX_DEPTH || self.below(3) == 0;
+        if leaf {
+            return match self.below(9) {
+                0 => Value::from(self.next() as u8),
+                1 => Value::from(self.below(2) == 0),
+                2 => Value::from(self.next() as i16),
+                3 => Value::from(self.next() as u32),
+                4 => Value::from(self.next() as i64),
+                5 => Value::from(self.next()),
+                6 => Value::from(self.f64()),
+                _ => Value::from(self.string()),
+            };
+        }
+        match self.below(6) {
+            // The colors the portal sends, with arbitrary components.
+            0 => Value::from((self.f64(), self.f64(), self.f64())),
+            1 => {
+                let count = self.below(5);
+                let mut builder = StructureBuilder::new().append_field(self.value(depth + 1));
+                for _ in 0..count {
+                    builder = builder.append_field(self.value(depth + 1));
+                }
+                Value::from(builder.build())
+            }
+            2 => Value::from((0..self.below(5)).map(|_| self.f64()).collect::<Vec<_>>()),
+            3 => Value::from(
+                (0..self.below(4))
+                    .map(|_| self.value(depth + 1))
+                    .collect::<Vec<_>>(),
+            ),
+            4 => Value::from(self.results(depth + 1)),
+            _ => Value::Value(Box::new(self.value(depth + 1))),
+        }
+    }
+
+    fn results(&mut self, depth: u32) -> HashMap<String, Value<'static>> {
+        (0..self.below(5))
+            .map(|_| (self.key(), self.value(depth)))
+            .collect()
+    }
+}
+
+fn seed() -> u64 {
+    std::env::var("WLSCREENACCESS_FUZZ_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0x5eed)
+}
+
+fn signal(code: u32, results: HashMap<String, Value<'static>>) -> Option<Message> {
+    // Bodies zvariant refuses to serialize, e.g. too deep ones, can't be
+    // received either.
+    Message::signal(
+        None::<&str>,
+        None::<&str>,
+        "/org/freedesktop/portal/desktop/request/1_42/wlsa_token",
+        "org.freedesktop.portal.Request",
+        "Response",
+        &(code, results),
+    )
+    .ok()
+}
+
+/// Decodes `message` as `from_message` and as a plain body.
+fn decode(message: &Message) {
+    let _ = Response::<ScreenshotResponse>::from_message(message);
+    let _ = Response::<ColorResponse>::from_message(message);
+    let _ = message.body::<Response<ScreenshotResponse>>();
+    let _ = message.body::<Response<ColorResponse>>();
+    let _ = message.body::<(u32, HashMap<String, OwnedValue>)>();
+}
+
+#[test]
+fn generated_responses_never_panic() {
+    let seed = seed();
+    let mut rng = Rng(seed);
+    for case in 0..CASES {
+        let code = rng.code();
+        let results = rng.results(0);
+        let Some(message) = signal(code, results.clone()) else {
+            continue;
+        };
+        let decoded = std::panic::catch_unwind(AssertUnwindSafe(|| decode(&message)));
+        assert!(
+            decoded.is_ok(),
+            "case {} of seed {:#x} panicked: code {} with {:?}",
+            case,
+            seed,
+            code,
+            results
+        );
+    }
+}
+
+#[test]
+fn absurd_codes_are_errors() {
+    let mut rng = Rng(seed());
+    for code in [3, 4, 255, u32::MAX] {
+        let message = signal(code, rng.results(0)).unwrap();
+        assert!(matches!(
+            Response::<ScreenshotResponse>::from_message(&message),
+            Err(Error::UnknownResponseCode(found)) if found == code
+        ));
+        assert!(matches!(
+            Response::<ColorResponse>::from_message(&message),
+            Err(Error::UnknownResponseCode(found)) if found == code
+        ));
+    }
+}
+
+#[test]
+fn empty_successes_are_errors() {
+    let message = signal(0, HashMap::new()).unwrap();
+    assert!(matches!(
+        Response::<ScreenshotResponse>::from_message(&message),
+        Err(Error::MissingField { field: "uri", present }) if present.is_empty()
+    ));
+    assert!(matches!(
+        Response::<ColorResponse>::from_message(&message),
+        Err(Error::MissingField { field: "color", present }) if present.is_empty()
+    ));
+}
+
+#[test]
+fn uri_of_another_type_is_an_error() {
+    let mut results = HashMap::new();
+    results.insert("uri".to_owned(), Value::from((0.0, 0.5, 0.5)));
+    let message = signal(0, results).unwrap();
+    let err = Response::<ScreenshotResponse>::from_message(&message).unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            Error::InvalidField { field: "uri", expected, found } if expected == "s" && found == "(ddd)"
+        ),
+        "{:?}",
+        err
+    );
+    assert!(message.body::<Response<ScreenshotResponse>>().is_err());
+}
+
+#[test]
+fn missing_fields_are_named() {
+    let mut results = HashMap::new();
+    results.insert("message".to_owned(), Value::from("done"));
+    let message = signal(0, results).unwrap();
+    let err = Response::<ScreenshotResponse>::from_message(&message).unwrap_err();
+    assert!(
+        matches!(&err, Error::MissingField { field: "uri", present } if present == &["message"]),
+        "{:?}",
+        err
+    );
+    let err = Response::<ColorResponse>::from_message(&message).unwrap_err();
+    assert!(
+        matches!(&err, Error::MissingField { field: "color", present } if present == &["message"]),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn color_of_another_type_is_an_error() {
+    let mut results = HashMap::new();
+    results.insert("color".to_owned(), Value::from("#ff0000"));
+    let message = signal(0, results).unwrap();
+    let err = Response::<ColorResponse>::from_message(&message).unwrap_err();
+    assert!(
+        matches!(&err, Error::InvalidField { field: "color", found, .. } if found == "s"),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,28 @@
+//! Handle tokens given by the caller.
+
+use wlscreenaccess::HandleToken;
+use zbus::names::MemberName;
+
+/// Whether `HandleToken` accepts `token` exactly when it's a member name.
+fn agrees(token: String) {
+    let valid = MemberName::try_from(token.as_str()).is_ok();
+    assert_eq!(
+        HandleToken::try_from(token.as_str()).is_ok(),
+        valid,
+        "{:?}",
+        token
+    );
+}
+
+#[test]
+fn tokens_are_checked_like_member_names() {
+    // The checks cover every rule of member names, so no string panics.
+    for a in 0..128u8 {
+        agrees(char::from(a).to_string());
+        for b in 0..128u8 {
+            agrees(format!("{}{}", char::from(a), char::from(b)));
+        }
+    }
+    agrees("a".repeat(255));
+    agrees("a".repeat(256));
+}
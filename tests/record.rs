@@ -0,0 +1,44 @@
+//! Recording the portal traffic with `WLSCREENACCESS_RECORD`.
+//!
+//! The recording is global to the process, so this is the only test here.
+
+mod common;
+
+use common::{load_recording, replay, session, Reply};
+
+#[tokio::test]
+async fn session_is_recorded_and_replayed() {
+    let path = std::env::temp_dir().join(format!("wlsa-record-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    // Before any request, the recording is opened once.
+    std::env::set_var("WLSCREENACCESS_RECORD", &path);
+    let recorded = session(
+        Reply::screenshot("file:///tmp/recorded.png"),
+        Reply::color([0.2, 0.4, 0.6]),
+    )
+    .await;
+    let recording = load_recording(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let kinds: Vec<_> = recording
+        .iter()
+        .map(|entry| (entry.kind.as_str(), entry.method.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("call", "Screenshot"),
+            ("response", ""),
+            ("call", "PickColor"),
+            ("response", ""),
+        ]
+    );
+    for pair in recording.chunks(2) {
+        let (call, response) = (&pair[0], &pair[1]);
+        assert_eq!(call.signature, "a{sv}");
+        assert!(["l", "B"].contains(&call.endian.as_str()));
+        assert!(["l", "B"].contains(&response.endian.as_str()));
+        assert!(response.path.ends_with(&format!("/{}", call.token)));
+    }
+    assert_eq!(replay(&recording).await, recorded);
+}
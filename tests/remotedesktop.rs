@@ -0,0 +1,140 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+};
+
+use common::{Bus, MockScreenCast, REMOTE_CLIPBOARD};
+use wlscreenaccess::{
+    remotedesktop::{Axis, Button, DeviceType, KeyState, RemoteDesktop, SelectDevicesOptions},
+    screencast::{ScreenCast, SelectSourcesOptions},
+    Error, WindowIdentifier,
+};
+use zbus::{export::futures_util::StreamExt, zvariant::OwnedValue};
+
+#[tokio::test]
+async fn one_session_shares_the_screen_and_takes_input() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast {
+        streams: vec![(42, HashMap::<String, OwnedValue>::new())],
+        ..Default::default()
+    };
+    let _server = bus.serve_remote(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = RemoteDesktop::with_connection(&connection).await.unwrap();
+    assert_eq!(
+        portal.available_device_types().await.unwrap(),
+        DeviceType::all()
+    );
+    let devices = SelectDevicesOptions::new().types(DeviceType::KEYBOARD | DeviceType::POINTER);
+    let (session, streams) = portal
+        .start(
+            &WindowIdentifier::None,
+            devices,
+            Some(SelectSourcesOptions::new()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(streams[0].pipewire_node_id(), 42);
+    assert_eq!(
+        session.devices(),
+        DeviceType::KEYBOARD | DeviceType::POINTER
+    );
+
+    session.notify_pointer_motion(1.5, -2.0).await.unwrap();
+    session
+        .notify_pointer_button(Button::LEFT, KeyState::Pressed)
+        .await
+        .unwrap();
+    session.notify_pointer_axis(0.0, 3.0, true).await.unwrap();
+    session
+        .notify_pointer_axis_discrete(Axis::Horizontal, -1)
+        .await
+        .unwrap();
+    session
+        .notify_keyboard_keycode(30, KeyState::Released)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *backend.calls.lock().unwrap(),
+        [
+            "RemoteDesktop.CreateSession",
+            "SelectDevices 3",
+            "SelectSources",
+            "RemoteDesktop.Start",
+            "NotifyPointerMotion 1.5 -2",
+            "NotifyPointerButton 0x110 1",
+            "NotifyPointerAxis 0 3 true",
+            "NotifyPointerAxisDiscrete 1 -1",
+            "NotifyKeyboardKeycode 30 0",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn screencast_sessions_take_no_input() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_remote(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let session = portal.create_session().await.unwrap();
+    let err = session.notify_pointer_motion(1.0, 1.0).await.unwrap_err();
+    assert!(matches!(err, Error::NotRemoteDesktop));
+    assert_eq!(session.devices(), DeviceType::empty());
+}
+
+#[tokio::test]
+async fn clipboard_is_offered_and_read() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_remote(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = RemoteDesktop::with_connection(&connection).await.unwrap();
+    let session = portal.create_session().await.unwrap();
+    session
+        .select_devices(SelectDevicesOptions::new().types(DeviceType::KEYBOARD))
+        .await
+        .unwrap();
+    session.request_clipboard().await.unwrap();
+    session.start(&WindowIdentifier::None).await.unwrap();
+
+    let mut owners = session.receive_selection_owner_changed().await.unwrap();
+    let mut transfers = session.receive_selection_transfer().await.unwrap();
+    session.set_selection(&["text/plain"]).await.unwrap();
+    let owner = owners.next().await.unwrap();
+    assert_eq!(owner.mime_types, ["text/plain"]);
+    assert!(owner.session_is_owner);
+    let transfer = transfers.next().await.unwrap();
+    assert_eq!(transfer.mime_type, "text/plain");
+    let mut file = File::from(session.selection_write(transfer.serial).await.unwrap());
+    file.write_all(b"copied locally").unwrap();
+    drop(file);
+    session
+        .selection_write_done(transfer.serial, true)
+        .await
+        .unwrap();
+
+    let mut file = File::from(session.selection_read("text/plain").await.unwrap());
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, REMOTE_CLIPBOARD);
+
+    assert_eq!(
+        backend.calls.lock().unwrap()[1..],
+        [
+            "SelectDevices 1",
+            "RequestClipboard",
+            "RemoteDesktop.Start",
+            "SetSelection text/plain",
+            "SelectionWriteDone 7 true copied locally",
+            "SelectionRead text/plain",
+        ]
+    );
+}
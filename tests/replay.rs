@@ -0,0 +1,36 @@
+//! Replaying recordings like the ones attached to bug reports, made with
+//! `WLSCREENACCESS_RECORD`.
+
+mod common;
+
+use std::path::Path;
+
+use common::{load_recording, replay};
+use wlscreenaccess::{
+    response::{BasicResponse, Response},
+    ScreenshotResponse, RGB,
+};
+
+#[tokio::test]
+async fn recording_replays() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/session.jsonl");
+    let recording = load_recording(&path);
+    let responses: Vec<_> = recording
+        .iter()
+        .filter(|entry| entry.kind == "response")
+        .collect();
+    assert_eq!(responses.len(), 2);
+    for response in &responses {
+        let message = response.response();
+        assert!(Response::<BasicResponse>::from_message(&message).is_ok());
+    }
+    let screenshot = Response::<ScreenshotResponse>::from_message(&responses[0].response());
+    assert_eq!(
+        screenshot.unwrap().ok().unwrap().url().as_str(),
+        "file:///tmp/recorded.png"
+    );
+
+    let (screenshot, color) = replay(&recording).await;
+    assert_eq!(screenshot.url().as_str(), "file:///tmp/recorded.png");
+    assert_eq!(color.to_rgb(), RGB::from([0.2, 0.4, 0.6]));
+}
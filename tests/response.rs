@@ -0,0 +1,57 @@
+//! Waiting for responses with `wait_for_response`, as crates wrapping other
+//! portals do.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{Bus, MockScreenshot, Reply};
+use wlscreenaccess::{response::wait_for_response, Error, HandleToken, ScreenshotResponse};
+use zbus::{
+    zvariant::{OwnedObjectPath, Value},
+    Proxy,
+};
+
+/// Makes a Screenshot request by hand, answered as `reply` says.
+async fn screenshot(reply: Reply) -> Result<ScreenshotResponse, Error> {
+    let bus = Bus::spawn();
+    let _server = bus.serve_interface(MockScreenshot::new(reply)).await;
+    let connection = bus.connect().await;
+    let proxy = Proxy::new(
+        &connection,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Screenshot",
+    )
+    .await
+    .unwrap();
+    let token = HandleToken::unique();
+    let body = (
+        "",
+        HashMap::from([("handle_token", Value::from(token.as_str()))]),
+    );
+    let call = proxy.call::<_, _, OwnedObjectPath>("Screenshot", &body);
+    wait_for_response(&connection, &token, call).await
+}
+
+#[tokio::test]
+async fn success_is_answered() {
+    let response = screenshot(Reply::screenshot("file:///tmp/waited.png")).await;
+    assert_eq!(response.unwrap().url().path(), "/tmp/waited.png");
+}
+
+#[tokio::test]
+async fn cancelled_is_an_error() {
+    let response = screenshot(Reply::Cancelled).await;
+    assert!(matches!(response, Err(Error::Cancelled)), "{:?}", response);
+}
+
+#[tokio::test]
+async fn malformed_body_is_invalid() {
+    let response = screenshot(Reply::Malformed).await;
+    assert!(
+        matches!(&response, Err(Error::InvalidResponse { signature, .. }) if signature == "us"),
+        "{:?}",
+        response
+    );
+}
@@ -0,0 +1,402 @@
+mod common;
+
+use std::collections::HashMap;
+
+use std::{
+    fs::File,
+    io::{Read, Seek, Write},
+    os::fd::OwnedFd,
+    sync::Arc,
+    time::Duration,
+};
+
+use common::{close_session, memfd, Bus, MockScreenCast, RESTORE_TOKEN};
+use wlscreenaccess::{
+    screencast::{
+        CursorMode, PersistMode, ScreenCast, SelectSourcesOptions, SourceSelection, SourceType,
+    },
+    Error, WindowIdentifier,
+};
+use zbus::{
+    export::futures_util::StreamExt,
+    zvariant::{OwnedValue, Value},
+};
+
+#[tokio::test]
+async fn start_walks_the_handshake() {
+    let bus = Bus::spawn();
+    let mut properties = HashMap::new();
+    properties.insert(
+        "size".to_owned(),
+        OwnedValue::from(Value::from((1920i32, 1080i32))),
+    );
+    properties.insert("source_type".to_owned(), OwnedValue::from(2u32));
+    let backend = MockScreenCast {
+        streams: vec![(42, properties.clone())],
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let (session, streams) = portal
+        .start(
+            &WindowIdentifier::None,
+            SelectSourcesOptions::new().multiple(true),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *backend.calls.lock().unwrap(),
+        ["CreateSession", "SelectSources", "Start"]
+    );
+    assert!(session
+        .path()
+        .as_str()
+        .starts_with("/org/freedesktop/portal/desktop/session/"));
+    let multiple: bool = *backend.selected.lock().unwrap()["multiple"]
+        .downcast_ref()
+        .unwrap();
+    assert!(multiple);
+    assert_eq!(streams.len(), 1);
+    assert_eq!(streams[0].pipewire_node_id(), 42);
+    assert_eq!(streams[0].size, Some((1920, 1080)));
+    assert_eq!(streams[0].source_type, Some(SourceType::WINDOW));
+    assert_eq!(streams[0].position, None);
+}
+
+#[tokio::test]
+async fn select_sources_sends_the_flags() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    assert_eq!(
+        portal.available_source_types().await.unwrap(),
+        SourceType::MONITOR | SourceType::WINDOW
+    );
+    assert_eq!(
+        portal.available_cursor_modes().await.unwrap(),
+        CursorMode::HIDDEN | CursorMode::METADATA
+    );
+    let session = portal.create_session().await.unwrap();
+    session
+        .select_sources(
+            SelectSourcesOptions::new()
+                .types(SourceType::WINDOW)
+                .cursor_mode(CursorMode::METADATA),
+        )
+        .await
+        .unwrap();
+
+    let selected = backend.selected.lock().unwrap();
+    assert_eq!(selected["types"].downcast_ref::<u32>(), Some(&2));
+    assert_eq!(selected["cursor_mode"].downcast_ref::<u32>(), Some(&4));
+    assert!(!selected.contains_key("multiple"));
+}
+
+#[tokio::test]
+async fn cursor_mode_needs_version_2() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast {
+        version: 1,
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    assert_eq!(
+        portal.available_cursor_modes().await.unwrap(),
+        CursorMode::empty()
+    );
+    let session = portal.create_session().await.unwrap();
+    let err = session
+        .select_sources(SelectSourcesOptions::new().cursor_mode(CursorMode::EMBEDDED))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::UnsupportedVersion {
+            required: 2,
+            found: 1
+        }
+    ));
+    assert_eq!(*backend.calls.lock().unwrap(), ["CreateSession"]);
+}
+
+/// Waits for the calls made from the thread closing dropped sessions.
+async fn wait_for_calls(backend: &MockScreenCast, count: usize) -> Vec<String> {
+    for _ in 0..100 {
+        if backend.calls.lock().unwrap().len() >= count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    backend.calls.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn closing_twice_calls_close_once() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let session = portal.create_session().await.unwrap();
+    session.close().await.unwrap();
+    session.close().await.unwrap();
+    drop(session);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        wait_for_calls(&backend, 2).await,
+        ["CreateSession", "Close"]
+    );
+}
+
+#[tokio::test]
+async fn dropping_closes_the_session() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    drop(portal.create_session().await.unwrap());
+
+    assert_eq!(
+        wait_for_calls(&backend, 2).await,
+        ["CreateSession", "Close"]
+    );
+}
+
+#[tokio::test]
+async fn backend_closing_is_received() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let session = portal.create_session().await.unwrap();
+    let mut closed = session.receive_closed().await.unwrap();
+    close_session(&server, session.path()).await;
+    assert!(closed.next().await.unwrap().is_empty());
+    drop(closed);
+    drop(session);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(wait_for_calls(&backend, 2).await, ["CreateSession"]);
+}
+
+#[tokio::test]
+async fn restore_token_round_trips() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let options = SelectSourcesOptions::new()
+        .persist_mode(PersistMode::Persistent)
+        .restore_token("previous");
+    let (session, _) = portal
+        .start(&WindowIdentifier::None, options)
+        .await
+        .unwrap();
+
+    assert_eq!(session.restore_token().as_deref(), Some(RESTORE_TOKEN));
+    let selected = backend.selected.lock().unwrap();
+    assert_eq!(selected["persist_mode"].downcast_ref::<u32>(), Some(&2));
+    assert_eq!(
+        selected["restore_token"].downcast_ref::<str>(),
+        Some("previous")
+    );
+}
+
+#[tokio::test]
+async fn restore_options_are_left_out_before_version_4() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast {
+        version: 3,
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let options = SelectSourcesOptions::new()
+        .cursor_mode(CursorMode::EMBEDDED)
+        .persist_mode(PersistMode::Transient)
+        .restore_token("previous");
+    let (session, _) = portal
+        .start(&WindowIdentifier::None, options)
+        .await
+        .unwrap();
+
+    assert_eq!(session.restore_token(), None);
+    let selected = backend.selected.lock().unwrap();
+    assert!(selected.contains_key("cursor_mode"));
+    assert!(!selected.contains_key("persist_mode"));
+    assert!(!selected.contains_key("restore_token"));
+}
+
+#[tokio::test]
+async fn pipewire_remote_is_passed_over() {
+    let bus = Bus::spawn();
+    let mut memfd = memfd();
+    memfd.write_all(b"pipewire-0").unwrap();
+    let backend = MockScreenCast {
+        remote: Some(Arc::new(OwnedFd::from(memfd))),
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let (session, _) = portal
+        .start(&WindowIdentifier::None, SelectSourcesOptions::new())
+        .await
+        .unwrap();
+    let mut remote = File::from(session.open_pipewire_remote().await.unwrap());
+
+    let mut contents = String::new();
+    remote.rewind().unwrap();
+    remote.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "pipewire-0");
+}
+
+#[tokio::test]
+async fn source_selection_restores_a_window() {
+    let bus = Bus::spawn();
+    let mut properties = HashMap::new();
+    properties.insert("id".to_owned(), OwnedValue::from(Value::from("window-7")));
+    properties.insert("source_type".to_owned(), OwnedValue::from(2u32));
+    let backend = MockScreenCast {
+        streams: vec![(42, properties)],
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let selection = SourceSelection::window()
+        .restore_token(Some("previous".to_owned()))
+        .build()
+        .unwrap();
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let (session, streams) = portal
+        .start(&WindowIdentifier::None, selection)
+        .await
+        .unwrap();
+
+    assert_eq!(streams[0].id.as_deref(), Some("window-7"));
+    assert_eq!(session.restore_token().as_deref(), Some(RESTORE_TOKEN));
+    let selected = backend.selected.lock().unwrap();
+    assert_eq!(selected["types"].downcast_ref::<u32>(), Some(&2));
+    assert_eq!(selected["multiple"].downcast_ref::<bool>(), Some(&false));
+    assert_eq!(selected["persist_mode"].downcast_ref::<u32>(), Some(&2));
+    assert_eq!(
+        selected["restore_token"].downcast_ref::<str>(),
+        Some("previous")
+    );
+}
+
+#[tokio::test]
+async fn restart_reuses_the_restore_token() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let options = SelectSourcesOptions::new().persist_mode(PersistMode::Persistent);
+    let (session, _) = portal
+        .start(&WindowIdentifier::None, options)
+        .await
+        .unwrap();
+    let old_path = session.path().clone();
+    let (session, _) = session
+        .restart(&WindowIdentifier::None, SelectSourcesOptions::new())
+        .await
+        .unwrap();
+
+    assert_ne!(session.path(), &old_path);
+    assert_eq!(
+        *backend.calls.lock().unwrap(),
+        [
+            "CreateSession",
+            "SelectSources",
+            "Start",
+            "Close",
+            "CreateSession",
+            "SelectSources",
+            "Start"
+        ]
+    );
+    let selected = backend.selected.lock().unwrap();
+    assert_eq!(
+        selected["restore_token"].downcast_ref::<str>(),
+        Some(RESTORE_TOKEN)
+    );
+}
+
+#[tokio::test]
+async fn virtual_monitors_have_no_position() {
+    let bus = Bus::spawn();
+    let mut properties = HashMap::new();
+    properties.insert(
+        "size".to_owned(),
+        OwnedValue::from(Value::from((1280i32, 720i32))),
+    );
+    properties.insert("source_type".to_owned(), OwnedValue::from(4u32));
+    let backend = MockScreenCast {
+        source_types: 7,
+        streams: vec![(44, properties)],
+        ..Default::default()
+    };
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let selection = SourceSelection::virtual_monitor().build().unwrap();
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let (_session, streams) = portal
+        .start(&WindowIdentifier::None, selection)
+        .await
+        .unwrap();
+
+    assert_eq!(streams[0].source_type, Some(SourceType::VIRTUAL));
+    assert_eq!(streams[0].position, None);
+    assert_eq!(streams[0].size, Some((1280, 720)));
+}
+
+#[tokio::test]
+async fn unavailable_source_types_fail_before_the_dialog() {
+    let bus = Bus::spawn();
+    let backend = MockScreenCast::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let selection = SourceSelection::virtual_monitor().build().unwrap();
+    let portal = ScreenCast::with_connection(&connection).await.unwrap();
+    let err = portal
+        .start(&WindowIdentifier::None, selection)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        Error::SourceTypeUnavailable { requested, available }
+            if requested == SourceType::VIRTUAL
+                && available == SourceType::MONITOR | SourceType::WINDOW
+    ));
+    assert!(!backend
+        .calls
+        .lock()
+        .unwrap()
+        .contains(&"SelectSources".to_owned()));
+}
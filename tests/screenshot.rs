@@ -0,0 +1,394 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use common::{Bus, MockScreenshot, Reply};
+use wlscreenaccess::{
+    Error, HandleToken, Portal, ScreenshotOptions, ScreenshotResponse, WindowIdentifier, RGB,
+};
+use zbus::zvariant::{OwnedValue, Value};
+
+async fn portal(bus: &Bus) -> Portal {
+    Portal::connect_to_address(bus.address()).await.unwrap()
+}
+
+#[tokio::test]
+async fn screenshot_is_answered() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::screenshot("file:///tmp/Screenshot%201.png"));
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let response = portal(&bus).await.screenshot().await.unwrap();
+    assert_eq!(response.url().path(), "/tmp/Screenshot%201.png");
+    let options = backend.options.lock().unwrap();
+    assert!(options[0].contains_key("handle_token"));
+}
+
+#[tokio::test]
+async fn only_the_set_options_are_sent() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::screenshot("file:///tmp/options.png"));
+    let _server = bus.serve_interface(backend.clone()).await;
+    let portal = portal(&bus).await;
+
+    let token = HandleToken::new("options").unwrap();
+    let options = ScreenshotOptions::new().handle_token(token.clone());
+    portal
+        .screenshot_with_options(&WindowIdentifier::None, options)
+        .await
+        .unwrap();
+    let options = ScreenshotOptions::new().modal(false).interactive(true);
+    portal
+        .screenshot_with_options(&WindowIdentifier::None, options)
+        .await
+        .unwrap();
+
+    let sent = backend.options.lock().unwrap();
+    let keys = |options: &HashMap<String, OwnedValue>| {
+        let mut keys: Vec<_> = options.keys().cloned().collect();
+        keys.sort();
+        keys
+    };
+    assert_eq!(keys(&sent[0]), ["handle_token"]);
+    assert_eq!(sent[0]["handle_token"], Value::from(token.as_str()).into());
+    assert_eq!(keys(&sent[1]), ["handle_token", "interactive", "modal"]);
+    assert_eq!(sent[1]["modal"], false.into());
+    assert_eq!(sent[1]["interactive"], true.into());
+}
+
+#[tokio::test]
+async fn malformed_address_is_an_error() {
+    assert!(Portal::connect_to_address("nonsense").await.is_err());
+}
+
+#[tokio::test]
+async fn color_is_answered() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::color([1.0, 0.5, 0.0]));
+    let _server = bus.serve_interface(backend).await;
+
+    let response = portal(&bus).await.pick_color().await.unwrap();
+    assert_eq!(response.to_rgb(), RGB::from([1.0, 0.5, 0.0]));
+}
+
+#[tokio::test]
+async fn cancelling_is_an_error() {
+    let bus = Bus::spawn();
+    let _server = bus
+        .serve_interface(MockScreenshot::new(Reply::Cancelled))
+        .await;
+
+    let err = portal(&bus).await.screenshot().await.unwrap_err();
+    assert!(err.is_cancelled(), "{:?}", err);
+}
+
+#[tokio::test]
+async fn interactive_screenshot_needs_version_2() {
+    let bus = Bus::spawn();
+    let mut backend = MockScreenshot::new(Reply::screenshot("file:///tmp/interactive.png"));
+    backend.version = 1;
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let err = portal(&bus)
+        .await
+        .screenshot_interactive()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::UnsupportedVersion {
+                required: 2,
+                found: 1
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert!(backend.options.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn interactive_screenshot_is_answered_by_version_2() {
+    let bus = Bus::spawn();
+    let mut backend = MockScreenshot::new(Reply::screenshot("file:///tmp/interactive.png"));
+    backend.version = 2;
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let response = portal(&bus).await.screenshot_interactive().await.unwrap();
+    assert_eq!(response.url().path(), "/tmp/interactive.png");
+    let options = backend.options.lock().unwrap();
+    assert_eq!(options[0]["interactive"], true.into());
+}
+
+#[tokio::test]
+async fn duplicate_response_is_ignored() {
+    let bus = Bus::spawn();
+    let reply = Reply::Duplicated(
+        Box::new(Reply::screenshot("file:///tmp/first.png")),
+        Box::new(Reply::screenshot("file:///tmp/duplicate.png")),
+    );
+    let backend = MockScreenshot::new(reply);
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let portal = portal(&bus).await;
+    for _ in 0..2 {
+        let response = portal.screenshot().await.unwrap();
+        assert_eq!(response.url().path(), "/tmp/first.png");
+    }
+    assert_eq!(backend.options.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn failing_is_another_error() {
+    let bus = Bus::spawn();
+    let reply = Reply::Failed("no output to capture".to_owned());
+    let _server = bus.serve_interface(MockScreenshot::new(reply)).await;
+
+    let portal = portal(&bus).await;
+    for err in [
+        portal.screenshot().await.unwrap_err(),
+        portal.pick_color().await.unwrap_err(),
+    ] {
+        assert!(!err.is_cancelled(), "{:?}", err);
+        assert!(
+            matches!(&err, Error::Other { message: Some(message), .. } if message == "no output to capture"),
+            "{:?}",
+            err
+        );
+    }
+}
+
+#[tokio::test]
+async fn late_response_is_awaited() {
+    let bus = Bus::spawn();
+    let reply = Reply::Delayed(
+        Duration::from_millis(100),
+        Box::new(Reply::screenshot("file:///tmp/late.png")),
+    );
+    let _server = bus.serve_interface(MockScreenshot::new(reply)).await;
+
+    let response = portal(&bus).await.screenshot().await.unwrap();
+    assert_eq!(response.url().path(), "/tmp/late.png");
+}
+
+#[tokio::test]
+async fn malformed_response_is_invalid() {
+    let bus = Bus::spawn();
+    let _server = bus
+        .serve_interface(MockScreenshot::new(Reply::Malformed))
+        .await;
+
+    let err = portal(&bus).await.screenshot().await.unwrap_err();
+    assert!(
+        matches!(&err, Error::InvalidResponse { signature, .. } if signature == "us"),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn request_is_made_at_the_predicted_path() {
+    let bus = Bus::spawn();
+    let _server = bus.serve_interface(MockScreenshot::new(Reply::Never)).await;
+
+    let request = portal(&bus)
+        .await
+        .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+    assert!(request.is_expected_path());
+}
+
+#[tokio::test]
+async fn pending_request_does_not_stall_the_connection() {
+    let bus = Bus::spawn();
+    let reply = Reply::screenshot("file:///tmp/pending.png");
+    let _server = bus.serve_interface(MockScreenshot::new(reply)).await;
+    let portal = portal(&bus).await;
+    let request = portal
+        .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+
+    // Well over the 64 messages zbus queues for a subscriber before it stops
+    // reading the connection.
+    for _ in 0..200 {
+        portal
+            .connection()
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus"),
+                "GetId",
+                &(),
+            )
+            .await
+            .unwrap();
+    }
+    let response = request
+        .receive_response::<ScreenshotResponse>()
+        .await
+        .unwrap();
+    assert_eq!(response.url().path(), "/tmp/pending.png");
+}
+
+#[tokio::test]
+async fn response_from_another_peer_is_ignored() {
+    let bus = Bus::spawn();
+    let reply = Reply::Delayed(
+        Duration::from_millis(100),
+        Box::new(Reply::screenshot("file:///tmp/genuine.png")),
+    );
+    let _server = bus.serve_interface(MockScreenshot::new(reply)).await;
+    let portal = portal(&bus).await;
+    let request = portal
+        .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+
+    // Sent to us alone, so it gets through the match rule on the portal.
+    let impostor = bus.connect().await;
+    let results = HashMap::from([("uri", Value::from("file:///tmp/forged.png"))]);
+    impostor
+        .emit_signal(
+            portal.connection().unique_name(),
+            request.path(),
+            "org.freedesktop.portal.Request",
+            "Response",
+            &(0u32, results),
+        )
+        .await
+        .unwrap();
+    let response = request
+        .receive_response::<ScreenshotResponse>()
+        .await
+        .unwrap();
+    assert_eq!(response.url().path(), "/tmp/genuine.png");
+}
+
+#[tokio::test]
+async fn bus_going_away_is_portal_gone() {
+    let bus = Bus::spawn();
+    let _server = bus.serve_interface(MockScreenshot::new(Reply::Never)).await;
+    let request = portal(&bus)
+        .await
+        .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+
+    drop(bus);
+    let timeout = Duration::from_secs(5);
+    let response = tokio::time::timeout(timeout, request.receive_response::<ScreenshotResponse>())
+        .await
+        .unwrap();
+    assert!(matches!(response, Err(Error::PortalGone)), "{:?}", response);
+}
+
+#[tokio::test]
+async fn unanswered_request_times_out_and_is_closed() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::Never);
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let result = portal(&bus)
+        .await
+        .screenshot_with_timeout(
+            &WindowIdentifier::None,
+            ScreenshotOptions::default(),
+            Duration::from_millis(100),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::Timeout)));
+    wait_closed(&backend).await;
+}
+
+#[tokio::test]
+async fn closing_the_handle_cancels_the_response() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::Never);
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let (handle, response) = portal(&bus)
+        .await
+        .start_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+    handle.close().await.unwrap();
+    assert!(matches!(response.await, Err(Error::Cancelled)));
+    assert_eq!(
+        backend.closed.lock().unwrap().as_slice(),
+        [handle.path().clone()]
+    );
+}
+
+#[tokio::test]
+async fn requests_on_a_connection_are_sent_from_it() {
+    let bus = Bus::spawn();
+    // Results both requests can read.
+    let results = HashMap::from([
+        ("uri".to_owned(), Value::from("file:///tmp/on.png").into()),
+        ("color".to_owned(), Value::from((0.2, 0.4, 0.6)).into()),
+    ]);
+    let backend = MockScreenshot::new(Reply::Success(results));
+    let _server = bus.serve_interface(backend.clone()).await;
+    // Another connection to the bus, which mustn't be used instead.
+    let _other = bus.connect().await;
+    let connection = bus.connect().await;
+
+    let options = ScreenshotOptions::default();
+    let response = wlscreenaccess::screenshot_on(&connection, options).await;
+    assert_eq!(response.unwrap().url().path(), "/tmp/on.png");
+    let response = wlscreenaccess::color_pick_on(&connection).await;
+    assert_eq!(response.unwrap().to_rgb().to_hex(), "#336699");
+
+    let name = connection.unique_name().unwrap().to_string();
+    assert_eq!(*backend.senders.lock().unwrap(), [name.clone(), name]);
+}
+
+#[tokio::test]
+async fn closed_request_is_cancelled() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::Never);
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let request = portal(&bus)
+        .await
+        .send_screenshot(&WindowIdentifier::None, ScreenshotOptions::default())
+        .await
+        .unwrap();
+    let path = request.path().clone();
+    request.close().await.unwrap();
+    let timeout = Duration::from_secs(5);
+    let response = tokio::time::timeout(timeout, request.receive_response::<ScreenshotResponse>())
+        .await
+        .unwrap();
+    assert!(matches!(response, Err(Error::Cancelled)), "{:?}", response);
+    assert_eq!(backend.closed.lock().unwrap().as_slice(), [path]);
+}
+
+#[tokio::test]
+async fn dropping_a_pending_request_closes_it() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::Never);
+    let _server = bus.serve_interface(backend.clone()).await;
+
+    let portal = portal(&bus).await;
+    let timeout = Duration::from_millis(100);
+    assert!(tokio::time::timeout(timeout, portal.screenshot())
+        .await
+        .is_err());
+    wait_closed(&backend).await;
+}
+
+/// Waits for the single request of `backend` to be closed, which the client
+/// does from a thread of its own.
+async fn wait_closed(backend: &MockScreenshot) {
+    for _ in 0..50 {
+        if backend.closed.lock().unwrap().len() == 1 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("The request wasn't closed");
+}
@@ -0,0 +1,94 @@
+//! The futures of the crate can be handed to `tokio::spawn` and the like.
+//! Only compiling matters, none of them is polled.
+
+use std::time::Duration;
+
+use wlscreenaccess::{
+    color_pick, pick_color_hex,
+    remotedesktop::{RemoteDesktop, SelectDevicesOptions},
+    screencast::{ScreenCast, Session, SourceSelection},
+    screenshot, screenshot_interactive, screenshot_with_timeout,
+    settings::{read_accent_color, read_color_scheme, receive_setting_changed, Settings},
+    Portal, ScreenshotOptions, WindowIdentifier,
+};
+
+fn assert_send<T: Send + 'static>(_: T) {}
+
+#[test]
+fn free_function_futures_are_send() {
+    assert_send(screenshot());
+    assert_send(screenshot_interactive());
+    assert_send(screenshot_with_timeout(Duration::from_secs(1)));
+    assert_send(color_pick());
+    assert_send(pick_color_hex());
+    assert_send(read_accent_color());
+    assert_send(read_color_scheme());
+    assert_send(receive_setting_changed());
+    assert_send(Portal::new());
+    assert_send(ScreenCast::new());
+    assert_send(RemoteDesktop::new());
+    assert_send(Settings::new());
+}
+
+/// The methods borrow their client, so each is moved into a future owning
+/// it, as a spawned task would.
+#[allow(dead_code)]
+fn client_futures_are_send(
+    portal: Portal,
+    screencast: ScreenCast,
+    remote: RemoteDesktop,
+    session: Session,
+    settings: Settings,
+) {
+    let parent = WindowIdentifier::None;
+    assert_send({
+        let portal = portal.clone();
+        async move { portal.screenshot().await }
+    });
+    assert_send({
+        let portal = portal.clone();
+        async move { portal.pick_color().await }
+    });
+    assert_send({
+        let (portal, parent) = (portal.clone(), parent.clone());
+        async move {
+            let options = ScreenshotOptions::default();
+            let timeout = Duration::from_secs(1);
+            portal
+                .screenshot_with_timeout(&parent, options, timeout)
+                .await
+        }
+    });
+    assert_send({
+        let parent = parent.clone();
+        async move {
+            let (_handle, response) = portal
+                .start_screenshot(&parent, ScreenshotOptions::default())
+                .await?;
+            response.await
+        }
+    });
+    assert_send({
+        let parent = parent.clone();
+        let selection = SourceSelection::monitor().build().unwrap();
+        async move {
+            let (session, _streams) = screencast.start(&parent, selection).await?;
+            session.close().await
+        }
+    });
+    assert_send({
+        let parent = parent.clone();
+        async move {
+            remote
+                .start(&parent, SelectDevicesOptions::new(), None)
+                .await
+        }
+    });
+    assert_send(async move {
+        session.start(&parent).await?;
+        session.notify_pointer_motion(1.0, 1.0).await?;
+        session.set_selection(&["text/plain"]).await?;
+        session.open_pipewire_remote().await
+    });
+    assert_send(async move { settings.read_color_scheme().await });
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{Bus, PORTAL_PATH};
+use futures_lite::StreamExt;
+use wlscreenaccess::{
+    settings::{ColorScheme, Settings},
+    RGB,
+};
+use zbus::{
+    dbus_interface,
+    zvariant::{OwnedValue, Value},
+    SignalContext,
+};
+
+#[derive(zbus::DBusError, Debug)]
+#[dbus_error(prefix = "org.freedesktop.portal.Error")]
+enum PortalError {
+    #[dbus_error(zbus_error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+}
+
+/// Settings keyed by namespace and key.
+type Values = HashMap<(String, String), OwnedValue>;
+
+fn lookup(values: &Values, namespace: String, key: String) -> Result<OwnedValue, PortalError> {
+    values
+        .get(&(namespace, key))
+        .cloned()
+        .ok_or_else(|| PortalError::NotFound("Requested setting not found".to_owned()))
+}
+
+fn appearance(values: &[(&str, Value<'_>)]) -> Values {
+    values
+        .iter()
+        .map(|(key, value)| {
+            let key = ("org.freedesktop.appearance".to_owned(), key.to_string());
+            (key, OwnedValue::from(value.clone()))
+        })
+        .collect()
+}
+
+/// A Settings backend of version 2.
+struct MockSettings {
+    values: Values,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Settings")]
+impl MockSettings {
+    async fn read_one(&self, namespace: String, key: String) -> Result<OwnedValue, PortalError> {
+        lookup(&self.values, namespace, key)
+    }
+
+    #[dbus_interface(signal)]
+    async fn setting_changed(
+        ctxt: &SignalContext<'_>,
+        namespace: &str,
+        key: &str,
+        value: Value<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// A Settings backend predating `ReadOne`, wrapping values in a variant.
+struct LegacySettings {
+    values: Values,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Settings")]
+impl LegacySettings {
+    async fn read(&self, namespace: String, key: String) -> Result<OwnedValue, PortalError> {
+        let value = lookup(&self.values, namespace, key)?;
+        Ok(Value::Value(Box::new(value.into())).into())
+    }
+}
+
+#[tokio::test]
+async fn appearance_is_read() {
+    let bus = Bus::spawn();
+    let values = appearance(&[
+        ("accent-color", Value::from((0.2, 0.4, 0.6))),
+        ("color-scheme", Value::from(1u32)),
+    ]);
+    let _server = bus.serve_interface(MockSettings { values }).await;
+    let settings = Settings::with_connection(&bus.connect().await)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        settings.read_accent_color().await.unwrap(),
+        Some(RGB::from([0.2, 0.4, 0.6]))
+    );
+    assert_eq!(
+        settings.read_color_scheme().await.unwrap(),
+        ColorScheme::PreferDark
+    );
+}
+
+#[tokio::test]
+async fn legacy_read_is_unwrapped() {
+    let bus = Bus::spawn();
+    let values = appearance(&[
+        ("accent-color", Value::from((1.0, 0.5, 0.0))),
+        ("color-scheme", Value::from(2u32)),
+    ]);
+    let _server = bus.serve_interface(LegacySettings { values }).await;
+    let settings = Settings::with_connection(&bus.connect().await)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        settings.read_accent_color().await.unwrap(),
+        Some(RGB::from([1.0, 0.5, 0.0]))
+    );
+    assert_eq!(
+        settings.read_color_scheme().await.unwrap(),
+        ColorScheme::PreferLight
+    );
+}
+
+#[tokio::test]
+async fn missing_or_unset_appearance_is_none() {
+    let bus = Bus::spawn();
+    let _server = bus
+        .serve_interface(MockSettings {
+            values: HashMap::new(),
+        })
+        .await;
+    let settings = Settings::with_connection(&bus.connect().await)
+        .await
+        .unwrap();
+    assert_eq!(settings.read_accent_color().await.unwrap(), None);
+    assert_eq!(
+        settings.read_color_scheme().await.unwrap(),
+        ColorScheme::NoPreference
+    );
+}
+
+#[tokio::test]
+async fn out_of_range_accent_color_is_none() {
+    let bus = Bus::spawn();
+    let values = appearance(&[("accent-color", Value::from((-1.0, -1.0, -1.0)))]);
+    let _server = bus.serve_interface(MockSettings { values }).await;
+    let settings = Settings::with_connection(&bus.connect().await)
+        .await
+        .unwrap();
+    assert_eq!(settings.read_accent_color().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn changes_are_received() {
+    let bus = Bus::spawn();
+    let server = bus
+        .serve_interface(MockSettings {
+            values: HashMap::new(),
+        })
+        .await;
+    let settings = Settings::with_connection(&bus.connect().await)
+        .await
+        .unwrap();
+    let mut changes = settings.receive_setting_changed().await.unwrap();
+
+    let ctxt = SignalContext::new(&server, PORTAL_PATH).unwrap();
+    MockSettings::setting_changed(
+        &ctxt,
+        "org.freedesktop.appearance",
+        "color-scheme",
+        Value::from(1u32),
+    )
+    .await
+    .unwrap();
+
+    let change = changes.next().await.unwrap();
+    assert_eq!(change.key, "color-scheme");
+    assert_eq!(change.color_scheme(), Some(ColorScheme::PreferDark));
+    assert_eq!(change.accent_color(), None);
+}
@@ -0,0 +1,98 @@
+//! The request lifecycle as sent to `tracing`.
+
+mod common;
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use common::{Bus, MockScreenshot, Reply};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use wlscreenaccess::Portal;
+
+/// The message and token of an event.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    token: Option<String>,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            "token" => self.token = Some(format!("{:?}", value).trim_matches('"').to_owned()),
+            _ => {}
+        }
+    }
+}
+
+/// Keeps the names of the spans and the events of the crate, events as
+/// `message token`.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Subscriber for Collect {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("wlscreenaccess")
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let mut collected = self.0.lock().unwrap();
+        collected.push(format!(
+            "{} {}",
+            span.metadata().name(),
+            fields.token.unwrap()
+        ));
+        Id::from_u64(collected.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let token = fields.token.unwrap_or_default();
+        let event = format!("{} {}", fields.message, token);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[tokio::test]
+async fn request_lifecycle_is_traced() {
+    let bus = Bus::spawn();
+    let backend = MockScreenshot::new(Reply::screenshot("file:///tmp/traced.png"));
+    let _server = bus.serve_interface(backend).await;
+    let portal = Portal::connect_to_address(bus.address()).await.unwrap();
+
+    let collect = Collect::default();
+    let _default = tracing::subscriber::set_default(collect.clone());
+    portal.screenshot().await.unwrap();
+
+    let collected = collect.0.lock().unwrap();
+    let token = collected[0].strip_prefix("screenshot ").unwrap();
+    let expected: Vec<_> = [
+        "screenshot",
+        "request path predicted",
+        "signal subscribed",
+        "request sent",
+        "response received",
+    ]
+    .iter()
+    .map(|message| format!("{} {}", message, token))
+    .collect();
+    assert_eq!(*collected, expected);
+}
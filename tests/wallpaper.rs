@@ -0,0 +1,69 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use common::{handle_path, respond, token, Bus};
+use wlscreenaccess::{
+    wallpaper::{set_wallpaper_on, WallpaperTarget},
+    WindowIdentifier,
+};
+use zbus::{
+    dbus_interface, fdo,
+    zvariant::{OwnedObjectPath, OwnedValue},
+    Connection, MessageHeader,
+};
+
+/// A URI and the options it was set with.
+type SetCall = (String, HashMap<String, OwnedValue>);
+
+/// A Wallpaper backend recording what it was asked to set.
+#[derive(Clone, Default)]
+struct MockWallpaper {
+    set: Arc<Mutex<Vec<SetCall>>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.portal.Wallpaper")]
+impl MockWallpaper {
+    #[dbus_interface(name = "SetWallpaperURI")]
+    async fn set_wallpaper_uri(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &Connection,
+        _parent_window: String,
+        uri: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<OwnedObjectPath> {
+        let request = handle_path("request", &header, &token(&options, "handle_token"));
+        self.set.lock().unwrap().push((uri, options));
+        respond(connection, &request, HashMap::new()).await;
+        Ok(request)
+    }
+}
+
+#[tokio::test]
+async fn screenshot_uri_is_set_as_wallpaper() {
+    let bus = Bus::spawn();
+    let backend = MockWallpaper::default();
+    let _server = bus.serve_interface(backend.clone()).await;
+    let connection = bus.connect().await;
+
+    let uri = url::Url::parse("file:///tmp/Screenshot%201.png").unwrap();
+    set_wallpaper_on(
+        &connection,
+        &WindowIdentifier::None,
+        &uri,
+        WallpaperTarget::Lockscreen,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let set = backend.set.lock().unwrap();
+    let (set_uri, options) = &set[0];
+    assert_eq!(set_uri, uri.as_str());
+    assert_eq!(options["set-on"].downcast_ref::<str>(), Some("lockscreen"));
+    assert_eq!(options["show-preview"].downcast_ref::<bool>(), Some(&false));
+}
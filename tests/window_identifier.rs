@@ -0,0 +1,78 @@
+//! Parsing and sending the window a dialog is parented to.
+
+use wlscreenaccess::{Error, WaylandHandle, WindowIdentifier};
+use zbus::{Message, MessageBuilder};
+
+/// A message carrying `body`, as the identifier goes over the bus.
+fn message<B>(body: &B) -> Message
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    MessageBuilder::signal("/", "org.example.Test", "Parent")
+        .unwrap()
+        .build(body)
+        .unwrap()
+}
+
+#[test]
+fn wayland_handles_round_trip() {
+    let identifier: WindowIdentifier = "wayland:a1b2-c3".parse().unwrap();
+    assert_eq!(
+        identifier,
+        WindowIdentifier::Wayland(WaylandHandle::new("a1b2-c3"))
+    );
+    assert_eq!(identifier.to_string(), "wayland:a1b2-c3");
+
+    let sent = message(&identifier);
+    assert_eq!(sent.body::<String>().unwrap(), "wayland:a1b2-c3");
+    assert_eq!(sent.body::<WindowIdentifier>().unwrap(), identifier);
+}
+
+#[test]
+fn x11_windows_round_trip() {
+    // Upper case digits are read, lower case ones written.
+    let identifier: WindowIdentifier = "x11:0x3A00007".parse().unwrap();
+    assert_eq!(identifier, WindowIdentifier::X11(0x3a00007));
+    assert_eq!(identifier.to_string(), "x11:0x3a00007");
+
+    let sent = message(&identifier);
+    assert_eq!(sent.body::<String>().unwrap(), "x11:0x3a00007");
+    assert_eq!(sent.body::<WindowIdentifier>().unwrap(), identifier);
+}
+
+#[test]
+fn no_parent_is_the_empty_string() {
+    assert_eq!(
+        "".parse::<WindowIdentifier>().unwrap(),
+        WindowIdentifier::None
+    );
+    let sent = message(&WindowIdentifier::None);
+    assert_eq!(sent.body::<String>().unwrap(), "");
+}
+
+#[test]
+fn invalid_identifiers_are_rejected() {
+    for invalid in [
+        "mir:1",
+        "wayland",
+        "x11:3a00007",
+        "x11:0x",
+        "x11:0x0",
+        "x11:0xnope",
+        "x11:0x10000000000000000",
+        " x11:0x1",
+    ] {
+        let err = invalid.parse::<WindowIdentifier>().unwrap_err();
+        assert!(
+            matches!(err, Error::WindowIdentifier(_)),
+            "{}: {:?}",
+            invalid,
+            err
+        );
+        assert!(
+            message(&invalid).body::<WindowIdentifier>().is_err(),
+            "{}",
+            invalid
+        );
+    }
+}